@@ -0,0 +1,15 @@
+-- base_factor converts one of the unit into the base unit of its dimension:
+-- grams for mass and milliliters for volume. Units without a factor can't be converted.
+ALTER TABLE units
+    ADD COLUMN dimension TEXT NOT NULL DEFAULT 'count' CHECK (dimension IN ('mass', 'volume', 'count')),
+    ADD COLUMN base_factor DOUBLE PRECISION;
+
+UPDATE units SET dimension = 'mass', base_factor = 1 WHERE name = 'gram';
+UPDATE units SET dimension = 'mass', base_factor = 1000 WHERE name = 'kilogram';
+UPDATE units SET dimension = 'mass', base_factor = 28.349523125 WHERE name = 'ounce';
+UPDATE units SET dimension = 'mass', base_factor = 453.59237 WHERE name = 'pound';
+UPDATE units SET dimension = 'volume', base_factor = 1 WHERE name = 'milliliter';
+UPDATE units SET dimension = 'volume', base_factor = 1000 WHERE name = 'liter';
+UPDATE units SET dimension = 'volume', base_factor = 4.92892159375 WHERE name = 'teaspoon';
+UPDATE units SET dimension = 'volume', base_factor = 14.78676478125 WHERE name = 'tablespoon';
+UPDATE units SET dimension = 'volume', base_factor = 236.5882365 WHERE name = 'cup';
@@ -0,0 +1,3 @@
+-- NULL means recipes are shown in the units they were written in
+ALTER TABLE users
+    ADD COLUMN preferred_unit_system TEXT CHECK (preferred_unit_system IN ('metric', 'imperial'));
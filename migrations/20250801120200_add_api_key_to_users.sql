@@ -0,0 +1,2 @@
+-- find_by_api_key and generate_api_key already expect this column
+ALTER TABLE users ADD COLUMN api_key TEXT UNIQUE;
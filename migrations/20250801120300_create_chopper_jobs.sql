@@ -0,0 +1,22 @@
+-- Pages sent to the chopper wait here until the background worker has parsed them
+CREATE TABLE IF NOT EXISTS chopper_jobs (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    source_url TEXT NOT NULL,
+    hostname TEXT NOT NULL,
+    pathname TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'running', 'succeeded', 'failed')),
+    recipe_id INTEGER REFERENCES recipes (id) ON DELETE SET NULL,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_chopper_jobs_user_id ON chopper_jobs(user_id);
+CREATE INDEX idx_chopper_jobs_waiting ON chopper_jobs(created_at) WHERE status IN ('pending', 'running');
+
+CREATE TRIGGER update_chopper_jobs_updated_at
+BEFORE UPDATE ON chopper_jobs
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,4 @@
+-- What to do when the user already has a recipe from the same page, and what was done
+ALTER TABLE chopper_jobs
+    ADD COLUMN on_duplicate TEXT NOT NULL DEFAULT 'skip' CHECK (on_duplicate IN ('skip', 'overwrite', 'copy')),
+    ADD COLUMN outcome TEXT CHECK (outcome IN ('created', 'skipped', 'overwritten', 'copied'));
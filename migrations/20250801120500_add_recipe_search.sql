@@ -0,0 +1,135 @@
+-- Recipes are searched through a tsvector of their name, description, ingredient names and
+-- steps. A generated column can only read its own row, so the ingredient names and steps are
+-- copied onto the recipe by triggers and the vector is generated from the copies.
+ALTER TABLE recipes
+    ADD COLUMN search_ingredients TEXT NOT NULL DEFAULT '',
+    ADD COLUMN search_steps TEXT NOT NULL DEFAULT '',
+    ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', name), 'A') ||
+        setweight(to_tsvector('english', description), 'B') ||
+        setweight(to_tsvector('english', search_ingredients), 'C') ||
+        setweight(to_tsvector('english', search_steps), 'D')
+    ) STORED;
+
+CREATE INDEX idx_recipes_search_vector ON recipes USING GIN (search_vector);
+
+CREATE OR REPLACE FUNCTION refresh_recipe_search(target_recipe_id INTEGER)
+RETURNS VOID AS $$
+BEGIN
+    UPDATE recipes
+    SET
+        search_ingredients = COALESCE((
+            SELECT string_agg(i.name, ', ' ORDER BY rci.id)
+            FROM recipe_components rc
+            JOIN recipe_component_ingredients rci ON rci.recipe_component_id = rc.id
+            JOIN ingredients i ON i.id = rci.ingredient_id
+            WHERE rc.recipe_id = target_recipe_id
+        ), ''),
+        search_steps = COALESCE((
+            SELECT string_agg(ris.content, ' ' ORDER BY ri.order_idx, ris.step_number)
+            FROM recipe_instruction_steps ris
+            JOIN recipe_instructions ri ON ri.id = ris.recipe_instruction_id
+            WHERE ris.recipe_id = target_recipe_id
+        ), '')
+    WHERE id = target_recipe_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Recipes are written an ingredient and a step at a time, so new rows don't refresh the recipe.
+-- Whatever writes a recipe's children calls refresh_recipe_search once it has written them all.
+-- Changes and removals refresh each recipe they touch once per statement.
+CREATE OR REPLACE FUNCTION refresh_recipe_search_for_component_ingredients()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        PERFORM refresh_recipe_search(recipe_id)
+        FROM (
+            SELECT rc.recipe_id FROM recipe_components rc JOIN old_rows o ON o.recipe_component_id = rc.id
+            UNION
+            SELECT rc.recipe_id FROM recipe_components rc JOIN new_rows n ON n.recipe_component_id = rc.id
+        ) AS changed_recipes;
+    ELSE
+        PERFORM refresh_recipe_search(recipe_id)
+        FROM (
+            SELECT DISTINCT rc.recipe_id FROM recipe_components rc JOIN old_rows o ON o.recipe_component_id = rc.id
+        ) AS changed_recipes;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- A trigger with transition tables can only be for one kind of change
+CREATE TRIGGER refresh_recipe_search_on_component_ingredients_update
+AFTER UPDATE ON recipe_component_ingredients
+REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION refresh_recipe_search_for_component_ingredients();
+
+CREATE TRIGGER refresh_recipe_search_on_component_ingredients_delete
+AFTER DELETE ON recipe_component_ingredients
+REFERENCING OLD TABLE AS old_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION refresh_recipe_search_for_component_ingredients();
+
+-- Ingredients deleted along with their component can no longer find the recipe
+CREATE OR REPLACE FUNCTION refresh_recipe_search_for_components()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM refresh_recipe_search(recipe_id) FROM (SELECT DISTINCT recipe_id FROM old_rows) AS changed_recipes;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER refresh_recipe_search_on_components
+AFTER DELETE ON recipe_components
+REFERENCING OLD TABLE AS old_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION refresh_recipe_search_for_components();
+
+CREATE OR REPLACE FUNCTION refresh_recipe_search_for_steps()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        PERFORM refresh_recipe_search(recipe_id)
+        FROM (SELECT recipe_id FROM old_rows UNION SELECT recipe_id FROM new_rows) AS changed_recipes;
+    ELSE
+        PERFORM refresh_recipe_search(recipe_id) FROM (SELECT DISTINCT recipe_id FROM old_rows) AS changed_recipes;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER refresh_recipe_search_on_instruction_steps_update
+AFTER UPDATE ON recipe_instruction_steps
+REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION refresh_recipe_search_for_steps();
+
+CREATE TRIGGER refresh_recipe_search_on_instruction_steps_delete
+AFTER DELETE ON recipe_instruction_steps
+REFERENCING OLD TABLE AS old_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION refresh_recipe_search_for_steps();
+
+-- Renaming an ingredient changes every recipe that uses it
+CREATE OR REPLACE FUNCTION refresh_recipe_search_for_ingredient()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM refresh_recipe_search(recipe_id)
+    FROM (
+        SELECT DISTINCT rc.recipe_id
+        FROM recipe_component_ingredients rci
+        JOIN recipe_components rc ON rc.id = rci.recipe_component_id
+        WHERE rci.ingredient_id = NEW.id
+    ) AS recipes_using_ingredient;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER refresh_recipe_search_on_ingredients
+AFTER UPDATE OF name ON ingredients
+FOR EACH ROW
+WHEN (OLD.name IS DISTINCT FROM NEW.name)
+EXECUTE FUNCTION refresh_recipe_search_for_ingredient();
+
+SELECT refresh_recipe_search(id) FROM recipes;
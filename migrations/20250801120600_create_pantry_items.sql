@@ -0,0 +1,20 @@
+-- What each user already has in the kitchen. The same ingredient can be stocked more than
+-- once, e.g. in different units or with different expiry dates.
+CREATE TABLE IF NOT EXISTS pantry_items (
+    id SERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    ingredient_id INTEGER NOT NULL REFERENCES ingredients (id) ON DELETE CASCADE,
+    unit_id INTEGER NOT NULL REFERENCES units (id) ON DELETE CASCADE,
+    quantity_numerator INTEGER NOT NULL,
+    quantity_denominator INTEGER NOT NULL,
+    expires_on DATE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_pantry_items_user_id ON pantry_items(user_id);
+
+CREATE TRIGGER update_pantry_items_updated_at
+BEFORE UPDATE ON pantry_items
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,18 @@
+-- Recipes planned for a day and meal. Servings override the recipe's own serving count when set.
+CREATE TABLE IF NOT EXISTS meal_plans (
+    id SERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    recipe_id INTEGER NOT NULL REFERENCES recipes (id) ON DELETE CASCADE,
+    planned_on DATE NOT NULL,
+    meal TEXT NOT NULL CHECK (meal IN ('breakfast', 'lunch', 'dinner')),
+    servings INTEGER CHECK (servings > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_meal_plans_user_id_planned_on ON meal_plans(user_id, planned_on);
+
+CREATE TRIGGER update_meal_plans_updated_at
+BEFORE UPDATE ON meal_plans
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,2 @@
+-- The secret in the URL of a user's meal plan feed. Setting it to NULL revokes the feed.
+ALTER TABLE users ADD COLUMN calendar_token TEXT UNIQUE;
@@ -0,0 +1,30 @@
+-- Nutrition per 100 g of a food, loaded from data/nutrition.csv. Ingredients are matched to a
+-- food by its name or one of its aliases. The gram weights turn volumes and counts into grams;
+-- amounts in a unit without a weight can't be counted.
+CREATE TABLE IF NOT EXISTS nutrition_facts (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    aliases TEXT[] NOT NULL DEFAULT '{}',
+    calories DOUBLE PRECISION NOT NULL,
+    protein_grams DOUBLE PRECISION NOT NULL,
+    fat_grams DOUBLE PRECISION NOT NULL,
+    saturated_fat_grams DOUBLE PRECISION NOT NULL,
+    carbohydrate_grams DOUBLE PRECISION NOT NULL,
+    fiber_grams DOUBLE PRECISION NOT NULL,
+    sugar_grams DOUBLE PRECISION NOT NULL,
+    sodium_milligrams DOUBLE PRECISION NOT NULL,
+    calcium_milligrams DOUBLE PRECISION NOT NULL,
+    iron_milligrams DOUBLE PRECISION NOT NULL,
+    potassium_milligrams DOUBLE PRECISION NOT NULL,
+    vitamin_c_milligrams DOUBLE PRECISION NOT NULL,
+    grams_per_cup DOUBLE PRECISION,
+    grams_per_piece DOUBLE PRECISION,
+    grams_per_can DOUBLE PRECISION,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_nutrition_facts_updated_at
+BEFORE UPDATE ON nutrition_facts
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
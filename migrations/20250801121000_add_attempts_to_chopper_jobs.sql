@@ -0,0 +1,2 @@
+-- How many times a worker has picked the job up, so that a job that keeps stopping workers is given up on
+ALTER TABLE chopper_jobs ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,50 @@
+-- The source URL reduced by normalize_source_url, so that imports can find a recipe from the same page
+-- without normalizing every recipe the user has. Only the user's oldest recipe from a page has it;
+-- copies of that recipe keep their source URL but leave this NULL.
+ALTER TABLE recipes ADD COLUMN normalized_source_url TEXT;
+
+-- domain::normalize_source_url in SQL, to fill in the recipes saved before the column existed.
+-- Percent-encoded query parameters aren't decoded, so those few may not be found by imports.
+CREATE FUNCTION normalize_source_url(source_url TEXT) RETURNS TEXT AS $$
+    WITH parts AS (
+        SELECT
+            btrim(source_url) AS url,
+            lower(substring(btrim(source_url) FROM '^([A-Za-z][A-Za-z0-9+.-]*)://')) AS scheme,
+            substring(btrim(source_url) FROM '^[A-Za-z][A-Za-z0-9+.-]*://([^/?#]*)') AS authority,
+            substring(btrim(source_url) FROM '^[A-Za-z][A-Za-z0-9+.-]*://[^/?#]*([^?#]*)') AS path,
+            substring(btrim(source_url) FROM '^[^?#]*\?([^#]*)') AS query
+    )
+    SELECT CASE
+        WHEN authority IS NULL OR authority = '' THEN lower(url)
+        ELSE regexp_replace(
+                regexp_replace(lower(regexp_replace(authority, '^.*@', '')), '^www\.', ''),
+                CASE scheme WHEN 'http' THEN ':80$' WHEN 'https' THEN ':443$' ELSE ':$' END,
+                ''
+            )
+            || rtrim(path, '/')
+            || COALESCE('?' || (
+                SELECT string_agg(pair, '&' ORDER BY split_part(pair, '=', 1) COLLATE "C", pair COLLATE "C")
+                FROM (
+                    SELECT CASE WHEN param LIKE '%=%' THEN replace(param, '+', ' ') ELSE replace(param, '+', ' ') || '=' END AS pair
+                    FROM regexp_split_to_table(query, '&') AS param
+                    WHERE param <> ''
+                ) pairs
+                WHERE split_part(pair, '=', 1) NOT LIKE 'utm\_%'
+                    AND split_part(pair, '=', 1) NOT IN ('fbclid', 'gclid', 'mc_cid', 'mc_eid', 'ref', 'ref_src')
+            ), '')
+    END
+    FROM parts
+$$ LANGUAGE SQL IMMUTABLE;
+
+UPDATE recipes r
+SET normalized_source_url = oldest.normalized_source_url
+FROM (
+    SELECT id, normalized_source_url,
+        row_number() OVER (PARTITION BY user_id, normalized_source_url ORDER BY id) AS nth
+    FROM (SELECT id, user_id, normalize_source_url(source_url) AS normalized_source_url FROM recipes WHERE btrim(source_url) <> '') urls
+) oldest
+WHERE r.id = oldest.id AND oldest.nth = 1;
+
+-- Two imports of the same page at once can't both create a recipe
+CREATE UNIQUE INDEX idx_recipes_user_id_normalized_source_url ON recipes(user_id, normalized_source_url)
+    WHERE normalized_source_url IS NOT NULL;
@@ -0,0 +1,29 @@
+use async_trait::async_trait;
+use super::{ChopperRecipe, ChopperRequest, ParseError, RecipeParser};
+
+/// Always answers with the same recipe, so the chopper can be used in tests and
+/// development without a model
+pub struct FixtureParser {
+    recipe: ChopperRecipe,
+}
+
+impl FixtureParser {
+    pub fn new(recipe: ChopperRecipe) -> Self {
+        Self { recipe }
+    }
+
+    /// Reads a `ChopperRecipe` from a JSON file
+    pub fn from_file(path: &str) -> Result<Self, ParseError> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Self::new(serde_json::from_str(&contents)?))
+    }
+}
+
+#[async_trait]
+impl RecipeParser for FixtureParser {
+    async fn parse(&self, request: &ChopperRequest) -> Result<ChopperRecipe, ParseError> {
+        let mut recipe = self.recipe.clone();
+        recipe.source_url = request.source_url.clone();
+        Ok(recipe)
+    }
+}
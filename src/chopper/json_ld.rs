@@ -0,0 +1,354 @@
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::Value;
+use crate::models::recipe::MAX_STEP_MINUTES;
+use crate::models::unit::default_unit_matcher;
+use super::readability::{looks_like_html, readable_text};
+use super::{
+    ChopperComponentIngredient,
+    ChopperInstructionStep,
+    ChopperRecipe,
+    ChopperRecipeComponent,
+    ChopperRecipeInstruction,
+    ChopperRequest,
+    ParseError,
+    RecipeParser,
+};
+
+/// Reads the schema.org Recipe that most recipe sites embed as JSON-LD and only asks
+/// `fallback` when the page has none that can be saved. HTML is reduced to its readable
+/// text first.
+pub struct JsonLdParser {
+    fallback: std::sync::Arc<dyn RecipeParser>,
+}
+
+impl JsonLdParser {
+    pub fn new(fallback: std::sync::Arc<dyn RecipeParser>) -> Self {
+        Self { fallback }
+    }
+}
+
+#[async_trait]
+impl RecipeParser for JsonLdParser {
+    async fn parse(&self, request: &ChopperRequest) -> Result<ChopperRecipe, ParseError> {
+        if let Some(recipe) = recipe_from_html(&request.text, &request.source_url) {
+            match recipe.validate() {
+                Ok(()) => return Ok(recipe),
+                Err(problems) => tracing::debug!("Reading the page instead of its structured data: {}", problems.join("; ")),
+            }
+        }
+
+        if looks_like_html(&request.text) {
+            let request = ChopperRequest { text: readable_text(&request.text), ..request.clone() };
+            self.fallback.parse(&request).await
+        } else {
+            self.fallback.parse(request).await
+        }
+    }
+}
+
+/// Maps the first schema.org Recipe found in the page's `application/ld+json` scripts
+pub fn recipe_from_html(html: &str, source_url: &str) -> Option<ChopperRecipe> {
+    let script_re = Regex::new(r#"(?is)<script[^>]*type\s*=\s*["']?application/ld\+json["']?[^>]*>(.*?)</script>"#).unwrap();
+    let recipe = script_re
+        .captures_iter(html)
+        .filter_map(|captures| serde_json::from_str::<Value>(captures.get(1)?.as_str().trim()).ok())
+        .find_map(|value| find_recipe(&value).cloned())?;
+
+    Some(map_recipe(&recipe, source_url))
+}
+
+/// Recipes are either the top level object, inside an array or inside an `@graph`
+fn find_recipe(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Array(values) => values.iter().find_map(find_recipe),
+        Value::Object(object) => {
+            if is_type(value, "Recipe") {
+                return Some(value);
+            }
+            object.get("@graph").and_then(find_recipe)
+        },
+        _ => None,
+    }
+}
+
+fn is_type(value: &Value, type_name: &str) -> bool {
+    match &value["@type"] {
+        Value::String(t) => t == type_name,
+        Value::Array(types) => types.iter().any(|t| t.as_str() == Some(type_name)),
+        _ => false,
+    }
+}
+
+fn map_recipe(recipe: &Value, source_url: &str) -> ChopperRecipe {
+    let prep_time = recipe["prepTime"].as_str().and_then(duration_minutes).unwrap_or_default();
+    let cook_time = recipe["cookTime"].as_str().and_then(duration_minutes).unwrap_or_default();
+    // Whatever the total doesn't account for is resting, chilling, rising and so on
+    let rest_time = recipe["totalTime"]
+        .as_str()
+        .and_then(duration_minutes)
+        .map(|total| total.saturating_sub(prep_time).saturating_sub(cook_time).max(0))
+        .unwrap_or_default();
+
+    let units = default_unit_matcher();
+    let ingredients = match &recipe["recipeIngredient"] {
+        Value::Array(lines) => lines
+            .iter()
+            .filter_map(Value::as_str)
+            .map(clean_text)
+            .filter(|line| !line.is_empty())
+            .map(|line| ChopperComponentIngredient::from_line(&line, &units))
+            .collect(),
+        Value::String(line) => vec![ChopperComponentIngredient::from_line(&clean_text(line), &units)],
+        _ => Vec::new(),
+    };
+
+    ChopperRecipe {
+        name: recipe["name"].as_str().map(clean_text).unwrap_or_default(),
+        description: recipe["description"].as_str().map(clean_text).unwrap_or_default(),
+        prep_time,
+        rest_time,
+        cook_time,
+        servings: servings(&recipe["recipeYield"]).unwrap_or_default(),
+        source_url: source_url.to_string(),
+        components: vec![ChopperRecipeComponent {
+            name: "Ingredients".to_string(),
+            is_optional: false,
+            ingredients,
+        }],
+        instructions: instructions(&recipe["recipeInstructions"]),
+    }
+}
+
+/// Converts ISO 8601 durations such as "PT1H30M" into minutes. Durations longer than a
+/// recipe's times may be are left unknown.
+fn duration_minutes(duration: &str) -> Option<i32> {
+    let re = Regex::new(r"^P(?:(\d+)D)?(?:T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+(?:\.\d+)?)S)?)?$").unwrap();
+    let captures = re.captures(duration.trim())?;
+    let part = |idx: usize| captures.get(idx).and_then(|m| m.as_str().parse::<f64>().ok()).unwrap_or_default();
+    let minutes = part(1) * 24.0 * 60.0 + part(2) * 60.0 + part(3) + part(4) / 60.0;
+
+    Some(minutes.round()).filter(|minutes| *minutes <= f64::from(MAX_STEP_MINUTES)).map(|minutes| minutes as i32)
+}
+
+/// recipeYield is a number, a string like "4 servings" or a list of either
+fn servings(recipe_yield: &Value) -> Option<i32> {
+    match recipe_yield {
+        Value::Number(number) => number.as_i64().and_then(|n| i32::try_from(n).ok()),
+        Value::String(text) => {
+            let re = Regex::new(r"\d+").unwrap();
+            re.find(text)?.as_str().parse().ok()
+        },
+        Value::Array(values) => values.iter().find_map(servings),
+        _ => None,
+    }
+}
+
+/// Instructions are a string, a list of strings or HowToSteps, or HowToSections that each
+/// hold their own steps. Loose steps are gathered into one untitled instruction.
+fn instructions(value: &Value) -> Vec<ChopperRecipeInstruction> {
+    let mut instructions: Vec<ChopperRecipeInstruction> = Vec::new();
+    let mut loose_steps: Vec<String> = Vec::new();
+    let items = match value {
+        Value::Array(items) => items.clone(),
+        Value::String(text) => text.lines().map(|line| Value::String(line.to_string())).collect(),
+        _ => Vec::new(),
+    };
+
+    for item in &items {
+        if is_type(item, "HowToSection") {
+            let steps = match &item["itemListElement"] {
+                Value::Array(steps) => steps.iter().filter_map(step_text).collect(),
+                _ => Vec::new(),
+            };
+            instructions.push(instruction(item["name"].as_str().map(clean_text).unwrap_or_default(), steps));
+        } else if let Some(text) = step_text(item) {
+            loose_steps.push(text);
+        }
+    }
+    if !loose_steps.is_empty() {
+        instructions.insert(0, instruction("Instructions".to_string(), loose_steps));
+    }
+    for (idx, instruction) in instructions.iter_mut().enumerate() {
+        instruction.order_idx = idx as i32 + 1;
+    }
+
+    instructions
+}
+
+fn step_text(step: &Value) -> Option<String> {
+    let text = match step {
+        Value::String(text) => clean_text(text),
+        Value::Object(_) => clean_text(step["text"].as_str().or(step["name"].as_str())?),
+        _ => return None,
+    };
+    if text.is_empty() { None } else { Some(text) }
+}
+
+fn instruction(title: String, steps: Vec<String>) -> ChopperRecipeInstruction {
+    ChopperRecipeInstruction {
+        order_idx: 0,
+        title,
+        steps: steps
+            .into_iter()
+            .enumerate()
+            .map(|(idx, content)| ChopperInstructionStep { step_number: idx as i32 + 1, content })
+            .collect(),
+    }
+}
+
+/// JSON-LD strings often still contain markup and entities
+fn clean_text(text: &str) -> String {
+    let tag_re = Regex::new(r"<[^>]*>").unwrap();
+    let text = tag_re.replace_all(text, " ");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#039;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use async_trait::async_trait;
+    use super::{default_unit_matcher, duration_minutes, map_recipe, recipe_from_html, servings, ChopperComponentIngredient};
+    use super::{ChopperRecipe, ChopperRequest, JsonLdParser, ParseError, RecipeParser};
+    use crate::models::recipe::{DuplicatePolicy, MAX_STEP_MINUTES};
+    use serde_json::json;
+
+    /// Remembers the text it was asked to parse
+    #[derive(Default)]
+    struct RecordingParser {
+        text: Mutex<Option<String>>,
+    }
+
+    #[async_trait]
+    impl RecipeParser for RecordingParser {
+        async fn parse(&self, request: &ChopperRequest) -> Result<ChopperRecipe, ParseError> {
+            *self.text.lock().unwrap() = Some(request.text.clone());
+            Err(ParseError::MissingContent)
+        }
+    }
+
+    fn request(text: &str) -> ChopperRequest {
+        ChopperRequest {
+            text: text.to_string(),
+            source_url: "https://example.com/stew".to_string(),
+            hostname: "example.com".to_string(),
+            pathname: "/stew".to_string(),
+            on_duplicate: DuplicatePolicy::Skip,
+        }
+    }
+
+    #[test]
+    fn reads_recipe_inside_a_graph() {
+        let html = include_str!("../../tests/fixtures/json_ld/graph.html");
+        let recipe = recipe_from_html(html, "https://example.com/pancakes").unwrap();
+
+        assert_eq!(recipe.name, "Fluffy Pancakes");
+        assert_eq!(recipe.description, "Light & fluffy pancakes");
+        assert_eq!((recipe.prep_time, recipe.cook_time, recipe.rest_time), (10, 15, 5));
+        assert_eq!(recipe.servings, 4);
+        assert_eq!(recipe.source_url, "https://example.com/pancakes");
+
+        let ingredients = &recipe.components[0].ingredients;
+        assert_eq!(ingredients.len(), 4);
+        assert_eq!((ingredients[0].quantity_numerator, ingredients[0].quantity_denominator), (3, 2));
+        assert_eq!(ingredients[0].unit, "cup");
+        assert_eq!(ingredients[0].name, "all-purpose flour");
+        assert_eq!(ingredients[0].description, "sifted");
+        assert_eq!(ingredients[1].unit, "tablespoon");
+        assert_eq!(ingredients[3].unit, "to_taste");
+
+        assert_eq!(recipe.instructions.len(), 1);
+        assert_eq!(recipe.instructions[0].steps.len(), 3);
+        assert_eq!(recipe.instructions[0].steps[2].step_number, 3);
+    }
+
+    #[test]
+    fn reads_instruction_sections() {
+        let html = include_str!("../../tests/fixtures/json_ld/sections.html");
+        let recipe = recipe_from_html(html, "").unwrap();
+
+        assert_eq!(recipe.name, "Chicken Pot Pie");
+        assert_eq!(recipe.servings, 6);
+        let titles: Vec<&str> = recipe.instructions.iter().map(|i| i.title.as_str()).collect();
+        assert_eq!(titles, vec!["Filling", "Crust"]);
+        assert_eq!(recipe.instructions[1].order_idx, 2);
+        assert_eq!(recipe.instructions[1].steps[0].content, "Roll out the dough.");
+    }
+
+    #[test]
+    fn pages_without_a_recipe_are_ignored() {
+        let html = include_str!("../../tests/fixtures/json_ld/no_recipe.html");
+        assert!(recipe_from_html(html, "").is_none());
+    }
+
+    #[test]
+    fn durations_are_converted_to_minutes() {
+        assert_eq!(duration_minutes("PT1H30M"), Some(90));
+        assert_eq!(duration_minutes("PT45M"), Some(45));
+        assert_eq!(duration_minutes("P0DT0H20M"), Some(20));
+        assert_eq!(duration_minutes("PT90S"), Some(2));
+        assert_eq!(duration_minutes("45 minutes"), None);
+    }
+
+    #[test]
+    fn servings_are_read_from_any_yield() {
+        assert_eq!(servings(&json!(4)), Some(4));
+        assert_eq!(servings(&json!("Makes 12 cookies")), Some(12));
+        assert_eq!(servings(&json!(["8", "8 slices"])), Some(8));
+        assert_eq!(servings(&json!("a few")), None);
+    }
+
+    #[test]
+    fn huge_times_and_yields_are_left_unknown() {
+        let recipe = map_recipe(&json!({
+            "name": "Forever Stew",
+            "prepTime": "P99999999D",
+            "cookTime": "P99999999D",
+            "totalTime": "PT0M",
+            "recipeYield": 4_294_967_297_i64,
+        }), "");
+
+        assert_eq!((recipe.prep_time, recipe.cook_time, recipe.rest_time), (0, 0, 0));
+        assert_eq!(recipe.servings, 0);
+        assert_eq!(duration_minutes("P7D"), Some(MAX_STEP_MINUTES));
+        assert_eq!(duration_minutes("P7DT1M"), None);
+    }
+
+    #[test]
+    fn ingredient_lines_without_an_amount_get_one_piece() {
+        let ingredient = ChopperComponentIngredient::from_line("Eggs", &default_unit_matcher());
+        assert_eq!((ingredient.quantity_numerator, ingredient.unit.as_str(), ingredient.name.as_str()), (1, "piece", "Eggs"));
+    }
+
+    #[tokio::test]
+    async fn pages_without_structured_data_reach_the_fallback_as_readable_text() {
+        let fallback = Arc::new(RecordingParser::default());
+        let parser = JsonLdParser::new(fallback.clone());
+
+        let html = "<html><body><nav>Home</nav><article><h1>Stew</h1><p>Simmer it.</p></article></body></html>";
+        let _ = parser.parse(&request(html)).await;
+        assert_eq!(fallback.text.lock().unwrap().as_deref(), Some("Stew\nSimmer it."));
+
+        let _ = parser.parse(&request("Stew\nSimmer it for <2 hours")).await;
+        assert_eq!(fallback.text.lock().unwrap().as_deref(), Some("Stew\nSimmer it for <2 hours"));
+    }
+
+    #[tokio::test]
+    async fn invalid_structured_data_goes_to_the_fallback() {
+        let fallback = Arc::new(RecordingParser::default());
+        let parser = JsonLdParser::new(fallback.clone());
+
+        let html = r#"<html><head><script type="application/ld+json">{"@type": "Recipe", "name": ""}</script></head><body><h1>Stew</h1></body></html>"#;
+        assert!(matches!(parser.parse(&request(html)).await, Err(ParseError::MissingContent)));
+        assert_eq!(fallback.text.lock().unwrap().as_deref(), Some("Stew"));
+    }
+}
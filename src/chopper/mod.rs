@@ -0,0 +1,265 @@
+//! Turns the text of a recipe page into a `ChopperRecipe` using a language model.
+//! Which model is used is configured through `ChopperSettings`.
+use std::sync::Arc;
+use async_trait::async_trait;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use crate::configuration::{ChopperProvider, ChopperSettings};
+use crate::constants;
+use crate::models::recipe::DuplicatePolicy;
+
+mod recipe;
+mod openai;
+mod ollama;
+mod fixture;
+mod json_ld;
+mod page;
+mod readability;
+mod worker;
+
+pub use recipe::{
+    ChopperRecipe,
+    ChopperRecipeComponent,
+    ChopperComponentIngredient,
+    ChopperRecipeInstruction,
+    ChopperInstructionStep,
+    ChopperError,
+};
+pub use openai::OpenAiParser;
+pub use ollama::OllamaParser;
+pub use fixture::FixtureParser;
+pub use json_ld::{JsonLdParser, recipe_from_html};
+pub use page::{Page, PageFetcher};
+pub use readability::readable_text;
+pub use worker::{process_next_job, run_job, run_worker};
+
+/// A recipe page as sent by the browser extension
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChopperRequest {
+    pub text: String,
+    #[serde(rename = "sourceUrl")]
+    pub source_url: String,
+    pub hostname: String,
+    pub pathname: String,
+    /// What to do when the user already has a recipe from this page
+    #[serde(default, rename = "onDuplicate")]
+    pub on_duplicate: DuplicatePolicy,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    #[error("The recipe parser is misconfigured: {0}")]
+    Config(String),
+
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    #[error("The provider answered with {status}: {body}")]
+    Provider { status: reqwest::StatusCode, body: String },
+
+    #[error("The provider's answer had no content")]
+    MissingContent,
+
+    #[error("The provider's answer did not match the recipe format: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("The provider's recipe has problems: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+
+    #[error("The page can't be imported: {0}")]
+    Page(String),
+
+    #[error("Failed to read fixture: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[async_trait]
+pub trait RecipeParser: Send + Sync {
+    async fn parse(&self, request: &ChopperRequest) -> Result<ChopperRecipe, ParseError>;
+}
+
+/// Builds the parser chosen in the settings. Pages with structured recipe data never reach it.
+/// Missing API keys are only reported when a recipe is parsed, so the rest of the app still runs without one.
+pub fn parser_from_settings(settings: &ChopperSettings) -> Result<Arc<dyn RecipeParser>, ParseError> {
+    let provider: Arc<dyn RecipeParser> = match settings.provider {
+        ChopperProvider::OpenAi => Arc::new(OpenAiParser::new(settings)?),
+        ChopperProvider::Ollama => Arc::new(OllamaParser::new(settings)?),
+        ChopperProvider::Fixture => {
+            let path = settings.fixture_path
+                .as_ref()
+                .ok_or_else(|| ParseError::Config("fixture_path is required for the fixture provider".to_string()))?;
+            Arc::new(FixtureParser::from_file(path)?)
+        },
+    };
+
+    Ok(Arc::new(JsonLdParser::new(provider)))
+}
+
+fn prompt(request: &ChopperRequest) -> String {
+    format!("{}: {}", constants::strings::RECIPE_PROOMPT, request.text)
+}
+
+/// Models like to wrap their JSON in a ```json block, so use what is inside it if there is one
+fn extract_json(content: &str) -> &str {
+    let re = Regex::new(r"```json\s*([\s\S]*?)\s*```").unwrap();
+    match re.captures(content) {
+        Some(captures) => captures.get(1).map_or(content, |m| m.as_str()),
+        None => content,
+    }
+}
+
+/// Reads the recipe out of a model's answer. The answer has to match `ChopperRecipe`
+/// and pass its validation.
+fn recipe_from_content(content: &str, source_url: &str) -> Result<ChopperRecipe, ParseError> {
+    let mut recipe: ChopperRecipe = serde_json::from_str(extract_json(content))?;
+    recipe.source_url = source_url.to_string();
+    recipe.validate().map_err(ParseError::Invalid)?;
+
+    Ok(recipe)
+}
+
+/// A model that answers a conversation. Messages are objects with a role and content.
+#[async_trait]
+trait ChatModel: Send + Sync {
+    async fn chat(&self, messages: &[Value]) -> Result<String, ParseError>;
+}
+
+/// Asks `model` for the recipe. When the answer can't be used and `reprompt` is set the model
+/// is told what was wrong with it and gets one more try.
+async fn parse_with_model(model: &dyn ChatModel, request: &ChopperRequest, reprompt: bool) -> Result<ChopperRecipe, ParseError> {
+    let mut messages = vec![json!({ "role": "user", "content": prompt(request) })];
+    let content = model.chat(&messages).await?;
+    let err = match recipe_from_content(&content, &request.source_url) {
+        Err(err @ (ParseError::Invalid(_) | ParseError::Json(_))) if reprompt => err,
+        result => return result,
+    };
+    tracing::debug!("Asking the model again after: {}", err);
+
+    messages.push(json!({ "role": "assistant", "content": content }));
+    messages.push(json!({ "role": "user", "content": repair_prompt(&err) }));
+    let content = model.chat(&messages).await?;
+
+    recipe_from_content(&content, &request.source_url)
+}
+
+fn repair_prompt(err: &ParseError) -> String {
+    let problems = match err {
+        ParseError::Invalid(problems) => problems.iter().map(|problem| format!("- {}", problem)).collect::<Vec<String>>().join("\n"),
+        err => format!("- {}", err),
+    };
+    format!("{}\n{}", constants::strings::RECIPE_REPAIR_PROMPT, problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use super::{extract_json, parse_with_model, recipe_from_content, ChatModel, ChopperRequest, DuplicatePolicy, ParseError};
+
+    #[test]
+    fn json_is_extracted_from_code_blocks() {
+        assert_eq!(extract_json("Sure!\n```json\n{\"name\": \"Stew\"}\n```"), "{\"name\": \"Stew\"}");
+        assert_eq!(extract_json("{\"name\": \"Stew\"}"), "{\"name\": \"Stew\"}");
+    }
+
+    #[test]
+    fn recipe_is_read_from_content() {
+        let content = r#"{
+            "name": "Stew",
+            "servings": 4,
+            "components": [{
+                "name": "Main",
+                "ingredients": [{ "name": "Potato", "unit": "cup", "quantity_numerator": 1, "quantity_denominator": 2 }]
+            }],
+            "instructions": [{ "order_idx": 1, "title": "Cook", "steps": [{ "step_number": 1, "content": "Boil" }] }]
+        }"#;
+        let recipe = recipe_from_content(content, "https://example.com/stew").unwrap();
+
+        assert_eq!(recipe.name, "Stew");
+        assert_eq!(recipe.servings, 4);
+        assert_eq!(recipe.source_url, "https://example.com/stew");
+        assert_eq!(recipe.components[0].ingredients[0].name, "Potato");
+        assert_eq!(recipe.instructions[0].steps[0].content, "Boil");
+    }
+
+    #[test]
+    fn invalid_json_is_an_error() {
+        assert!(recipe_from_content("I couldn't find a recipe", "").is_err());
+    }
+
+    #[test]
+    fn invalid_recipes_list_their_problems() {
+        let content = r#"{"name": "", "components": [], "instructions": []}"#;
+        match recipe_from_content(content, "") {
+            Err(ParseError::Invalid(problems)) => assert_eq!(problems, vec!["name is empty".to_string()]),
+            other => panic!("expected problems, got {:?}", other),
+        }
+    }
+
+    /// Answers with `answers` in order and remembers how long each conversation was
+    struct ScriptedModel {
+        answers: Mutex<Vec<&'static str>>,
+        conversation_lengths: Mutex<Vec<usize>>,
+    }
+
+    impl ScriptedModel {
+        fn new(answers: &[&'static str]) -> Self {
+            Self {
+                answers: Mutex::new(answers.iter().rev().copied().collect()),
+                conversation_lengths: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ChatModel for ScriptedModel {
+        async fn chat(&self, messages: &[Value]) -> Result<String, ParseError> {
+            self.conversation_lengths.lock().unwrap().push(messages.len());
+            let answer = self.answers.lock().unwrap().pop().ok_or(ParseError::MissingContent)?;
+            Ok(answer.to_string())
+        }
+    }
+
+    fn request() -> ChopperRequest {
+        ChopperRequest {
+            text: "A stew".to_string(),
+            source_url: "https://example.com/stew".to_string(),
+            hostname: "example.com".to_string(),
+            pathname: "/stew".to_string(),
+            on_duplicate: DuplicatePolicy::Skip,
+        }
+    }
+
+    const INVALID_ANSWER: &str = r#"{"name": "Stew", "components": [], "instructions": [
+        {"order_idx": 1, "title": "Cook", "steps": [{"step_number": 1, "content": "Boil"}, {"step_number": 1, "content": "Serve"}]}
+    ]}"#;
+    const VALID_ANSWER: &str = r#"{"name": "Stew", "components": [], "instructions": [
+        {"order_idx": 1, "title": "Cook", "steps": [{"step_number": 1, "content": "Boil"}, {"step_number": 2, "content": "Serve"}]}
+    ]}"#;
+
+    #[tokio::test]
+    async fn invalid_answers_are_corrected_once() {
+        let model = ScriptedModel::new(&[INVALID_ANSWER, VALID_ANSWER]);
+
+        let recipe = parse_with_model(&model, &request(), true).await.unwrap();
+        assert_eq!(recipe.instructions[0].steps[1].step_number, 2);
+        assert_eq!(*model.conversation_lengths.lock().unwrap(), vec![1, 3]);
+    }
+
+    #[tokio::test]
+    async fn a_second_invalid_answer_is_an_error() {
+        let model = ScriptedModel::new(&[INVALID_ANSWER, "not json"]);
+
+        assert!(matches!(parse_with_model(&model, &request(), true).await, Err(ParseError::Json(_))));
+    }
+
+    #[tokio::test]
+    async fn the_model_is_not_asked_again_when_reprompting_is_off() {
+        let model = ScriptedModel::new(&[INVALID_ANSWER, VALID_ANSWER]);
+
+        assert!(matches!(parse_with_model(&model, &request(), false).await, Err(ParseError::Invalid(_))));
+        assert_eq!(*model.conversation_lengths.lock().unwrap(), vec![1]);
+    }
+}
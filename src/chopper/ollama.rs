@@ -0,0 +1,73 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use crate::configuration::ChopperSettings;
+use super::{parse_with_model, ChatModel, ChopperRecipe, ChopperRequest, ParseError, RecipeParser};
+
+const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+const DEFAULT_MODEL: &str = "llama3.1";
+
+/// Talks to a local model through Ollama's chat endpoint
+pub struct OllamaParser {
+    client: Client,
+    base_url: String,
+    model: String,
+    temperature: f32,
+    reprompt_on_invalid: bool,
+}
+
+impl OllamaParser {
+    pub fn new(settings: &ChopperSettings) -> Result<Self, ParseError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(settings.timeout_seconds))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: settings.base_url.clone().unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            model: settings.model.clone().unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            temperature: settings.temperature,
+            reprompt_on_invalid: settings.reprompt_on_invalid,
+        })
+    }
+}
+
+#[async_trait]
+impl RecipeParser for OllamaParser {
+    async fn parse(&self, request: &ChopperRequest) -> Result<ChopperRecipe, ParseError> {
+        parse_with_model(self, request, self.reprompt_on_invalid).await
+    }
+}
+
+#[async_trait]
+impl ChatModel for OllamaParser {
+    async fn chat(&self, messages: &[Value]) -> Result<String, ParseError> {
+        // "format": "json" makes Ollama constrain the model to valid JSON
+        let request_body = json!({
+            "model": self.model,
+            "messages": messages,
+            "format": "json",
+            "stream": false,
+            "options": { "temperature": self.temperature },
+        });
+
+        let response = self.client
+            .post(format!("{}/api/chat", self.base_url.trim_end_matches('/')))
+            .json(&request_body)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ParseError::Provider { status, body: response.text().await? });
+        }
+
+        let response_body: Value = response.json().await?;
+        let content = response_body["message"]["content"]
+            .as_str()
+            .ok_or(ParseError::MissingContent)?;
+        tracing::debug!("Ollama answered: {}", content);
+
+        Ok(content.to_string())
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use serde_json::{json, Value};
+use crate::configuration::ChopperSettings;
+use super::{parse_with_model, ChatModel, ChopperRecipe, ChopperRequest, ParseError, RecipeParser};
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+
+/// Talks to anything that implements OpenAI's chat completions endpoint
+pub struct OpenAiParser {
+    client: Client,
+    base_url: String,
+    model: String,
+    api_key: Option<Secret<String>>,
+    temperature: f32,
+    reprompt_on_invalid: bool,
+}
+
+impl OpenAiParser {
+    /// The API key falls back to the OPENAI_API_KEY environment variable
+    pub fn new(settings: &ChopperSettings) -> Result<Self, ParseError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(settings.timeout_seconds))
+            .build()?;
+        let api_key = settings.api_key
+            .clone()
+            .or_else(|| std::env::var("OPENAI_API_KEY").ok().map(Secret::new));
+
+        Ok(Self {
+            client,
+            base_url: settings.base_url.clone().unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            model: settings.model.clone().unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            api_key,
+            temperature: settings.temperature,
+            reprompt_on_invalid: settings.reprompt_on_invalid,
+        })
+    }
+}
+
+#[async_trait]
+impl RecipeParser for OpenAiParser {
+    async fn parse(&self, request: &ChopperRequest) -> Result<ChopperRecipe, ParseError> {
+        parse_with_model(self, request, self.reprompt_on_invalid).await
+    }
+}
+
+#[async_trait]
+impl ChatModel for OpenAiParser {
+    async fn chat(&self, messages: &[Value]) -> Result<String, ParseError> {
+        let api_key = self.api_key
+            .as_ref()
+            .ok_or_else(|| ParseError::Config("no API key is set for the OpenAI provider".to_string()))?;
+        let request_body = json!({
+            "model": self.model,
+            "messages": messages,
+            "temperature": self.temperature,
+        });
+
+        let response = self.client
+            .post(format!("{}/chat/completions", self.base_url.trim_end_matches('/')))
+            .bearer_auth(api_key.expose_secret())
+            .json(&request_body)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ParseError::Provider { status, body: response.text().await? });
+        }
+
+        let response_body: Value = response.json().await?;
+        let content = response_body["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or(ParseError::MissingContent)?;
+        tracing::debug!("OpenAI answered: {}", content);
+
+        Ok(content.to_string())
+    }
+}
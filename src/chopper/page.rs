@@ -0,0 +1,208 @@
+use std::error::Error as StdError;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{header, redirect, Client, Url};
+use crate::configuration::ChopperSettings;
+use super::ParseError;
+
+/// Pages bigger than this are not recipes worth reading
+const MAX_PAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Some sites turn away requests that don't look like they come from a browser
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; ChoppingList/0.1; +https://choppinglist.app)";
+
+/// As many redirects as a browser would follow
+const MAX_REDIRECTS: usize = 10;
+
+/// A page fetched by the server, for clients that can't send the page themselves
+#[derive(Debug, Clone)]
+pub struct Page {
+    /// Where the page ended up after redirects
+    pub url: Url,
+    pub html: String,
+}
+
+/// Fetches recipe pages for imports by URL
+#[derive(Clone)]
+pub struct PageFetcher {
+    client: Client,
+    fetch_private_addresses: bool,
+}
+
+/// Why a page on an internal address wasn't fetched
+#[derive(Debug, thiserror::Error)]
+#[error("{0} is not a public address")]
+struct PrivateAddress(String);
+
+impl PageFetcher {
+    /// Unless `fetch_private_addresses` is set, only pages on public addresses are fetched.
+    /// Host names are checked when they are resolved, so redirects and DNS answers that
+    /// change between lookups can't reach internal services either.
+    pub fn new(settings: &ChopperSettings) -> Result<Self, ParseError> {
+        let mut builder = Client::builder()
+            .timeout(Duration::from_secs(settings.fetch_timeout_seconds))
+            .user_agent(USER_AGENT);
+        if !settings.fetch_private_addresses {
+            builder = builder
+                .dns_resolver(Arc::new(PublicResolver))
+                .redirect(redirect::Policy::custom(|attempt| {
+                    if attempt.previous().len() >= MAX_REDIRECTS {
+                        attempt.error("too many redirects")
+                    } else if let Err(err) = check_ip_host(attempt.url()) {
+                        attempt.error(err)
+                    } else {
+                        attempt.follow()
+                    }
+                }));
+        }
+
+        Ok(Self { client: builder.build()?, fetch_private_addresses: settings.fetch_private_addresses })
+    }
+
+    pub async fn fetch(&self, url: &Url) -> Result<Page, ParseError> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(ParseError::Page(format!("{} is not a web page", url)));
+        }
+        if !self.fetch_private_addresses {
+            check_ip_host(url).map_err(|err| ParseError::Page(err.to_string()))?;
+        }
+
+        let mut response = self.client
+            .get(url.clone())
+            .header(header::ACCEPT, "text/html,application/xhtml+xml")
+            .send()
+            .await
+            .map_err(|err| match private_address(&err) {
+                Some(private_address) => ParseError::Page(private_address.to_string()),
+                None => ParseError::Http(err),
+            })?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ParseError::Page(format!("the page answered with {}", status)));
+        }
+        let content_type = response.headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if !content_type.is_empty() && !content_type.contains("html") {
+            return Err(ParseError::Page(format!("the page is {}, not HTML", content_type)));
+        }
+        if response.content_length().is_some_and(|length| length as usize > MAX_PAGE_BYTES) {
+            return Err(ParseError::Page("the page is too big".to_string()));
+        }
+
+        // Chunked responses have no length, so the body is held to the limit as it arrives
+        let url = response.url().clone();
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > MAX_PAGE_BYTES {
+                return Err(ParseError::Page("the page is too big".to_string()));
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(Page { url, html: String::from_utf8_lossy(&body).into_owned() })
+    }
+}
+
+/// Resolves host names like the system does, leaving out addresses that aren't public
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if addrs.is_empty() || addrs.iter().any(|addr| !is_public(addr.ip())) {
+                return Err(Box::new(PrivateAddress(host)) as Box<dyn StdError + Send + Sync>);
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+
+            Ok(addrs)
+        })
+    }
+}
+
+/// Hosts written as an IP address are never resolved, so they are checked here instead
+fn check_ip_host(url: &Url) -> Result<(), PrivateAddress> {
+    // IPv6 hosts keep their brackets
+    let host = url.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+    let Ok(ip) = host.parse::<IpAddr>() else {
+        return Ok(());
+    };
+    if is_public(ip) { Ok(()) } else { Err(PrivateAddress(ip.to_string())) }
+}
+
+fn private_address(err: &reqwest::Error) -> Option<&PrivateAddress> {
+    let mut source = err.source();
+    while let Some(err) = source {
+        if let Some(private_address) = err.downcast_ref::<PrivateAddress>() {
+            return Some(private_address);
+        }
+        source = err.source();
+    }
+
+    None
+}
+
+/// False for loopback, private, link-local, shared and other addresses that aren't on the
+/// public internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Shared address space used by carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                // Reserved for future use, 240.0.0.0/4
+                || a >= 240)
+        },
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use reqwest::Url;
+    use super::{check_ip_host, is_public};
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public(ip.parse::<IpAddr>().unwrap()), "{} is public", ip);
+        }
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:4700::1111"] {
+            assert!(is_public(ip.parse::<IpAddr>().unwrap()), "{} is not public", ip);
+        }
+    }
+
+    #[test]
+    fn only_ip_hosts_are_checked_without_resolving() {
+        assert!(check_ip_host(&Url::parse("http://127.0.0.1:8000/admin").unwrap()).is_err());
+        assert!(check_ip_host(&Url::parse("http://[::1]/").unwrap()).is_err());
+        assert!(check_ip_host(&Url::parse("http://169.254.169.254/latest/meta-data").unwrap()).is_err());
+        assert!(check_ip_host(&Url::parse("https://example.com/stew").unwrap()).is_ok());
+    }
+}
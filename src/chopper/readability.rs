@@ -0,0 +1,177 @@
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+
+/// Elements that never hold any of the recipe
+const SKIPPED_TAGS: &[&str] = &[
+    "aside", "button", "dialog", "footer", "form", "iframe", "nav", "noscript", "script", "select", "style", "svg", "template",
+];
+
+/// Elements that start a new line of text
+const BLOCK_TAGS: &[&str] = &[
+    "address", "article", "blockquote", "br", "dd", "div", "dl", "dt", "figcaption", "h1", "h2", "h3", "h4", "h5", "h6",
+    "header", "hr", "li", "main", "ol", "p", "pre", "section", "table", "td", "th", "tr", "ul",
+];
+
+/// Landmark roles of the parts of a page around its content
+const SKIPPED_ROLES: &[&str] = &["banner", "complementary", "contentinfo", "dialog", "navigation"];
+
+/// Elements that may hold the page's content, in the order they are preferred
+const CONTENT_SELECTORS: &[&str] = &["article", "main", "[role=main]"];
+
+/// Class and id words used for ads, menus, comments and the like
+const BOILERPLATE_PATTERN: &str = r"(?i)(^|[\s_-])(ads?|advert\w*|banner|breadcrumbs?|comments?|cookies?|menu|modal|navbar|newsletter|popup|promo\w*|related|share|sharing|sidebar|social|sponsor\w*|subscribe)($|[\s_-])";
+
+/// Browser extensions send the page's text, other clients its HTML
+pub fn looks_like_html(text: &str) -> bool {
+    let start = text.trim_start().chars().take(15).collect::<String>().to_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html") || start.starts_with("<head") || start.starts_with("<body")
+}
+
+/// Reduces a page to the text of its main content, one block per line, leaving out the
+/// navigation, ads, comments and scripts around it. Keeps the model's prompt short and on topic.
+pub fn readable_text(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let boilerplate = Regex::new(BOILERPLATE_PATTERN).unwrap();
+
+    // The candidate with the most text wins, since pages often use articles for teasers too
+    let content = CONTENT_SELECTORS
+        .iter()
+        .flat_map(|selector| document.select(&Selector::parse(selector).unwrap()).collect::<Vec<ElementRef>>())
+        .map(|element| text_of(element, &boilerplate))
+        .max_by_key(|text| text.len())
+        .filter(|text| !text.is_empty());
+    let content = match content {
+        Some(text) => text,
+        None => match document.select(&Selector::parse("body").unwrap()).next() {
+            Some(body) => text_of(body, &boilerplate),
+            None => String::new(),
+        },
+    };
+
+    let title = document
+        .select(&Selector::parse("title").unwrap())
+        .next()
+        .map(|title| clean_line(&title.text().collect::<String>()))
+        .unwrap_or_default();
+    if title.is_empty() || content.contains(&title) {
+        content
+    } else {
+        format!("{}\n{}", title, content)
+    }
+}
+
+fn text_of(element: ElementRef, boilerplate: &Regex) -> String {
+    let mut text = String::new();
+    collect_text(element, boilerplate, &mut text);
+
+    text.lines()
+        .map(clean_line)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn collect_text(element: ElementRef, boilerplate: &Regex, text: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            // Line breaks in the source are only whitespace, blocks decide where lines end
+            Node::Text(node) => text.push_str(&node.replace(['\n', '\r'], " ")),
+            Node::Element(_) => {
+                let Some(child) = ElementRef::wrap(child) else { continue };
+                if is_boilerplate(child, boilerplate) {
+                    continue;
+                }
+                let is_block = BLOCK_TAGS.contains(&child.value().name());
+                if is_block {
+                    text.push('\n');
+                }
+                collect_text(child, boilerplate, text);
+                if is_block {
+                    text.push('\n');
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+fn is_boilerplate(element: ElementRef, boilerplate: &Regex) -> bool {
+    let value = element.value();
+    if SKIPPED_TAGS.contains(&value.name()) {
+        return true;
+    }
+    // A page's header holds the site's menu, but an article's header holds its title
+    if value.name() == "header" && element.select(&Selector::parse("h1").unwrap()).next().is_none() {
+        return true;
+    }
+    if value.attr("hidden").is_some() || value.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    if value.attr("role").is_some_and(|role| SKIPPED_ROLES.contains(&role)) {
+        return true;
+    }
+
+    [value.attr("class"), value.attr("id")]
+        .into_iter()
+        .flatten()
+        .any(|names| boilerplate.is_match(names))
+}
+
+fn clean_line(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{looks_like_html, readable_text};
+
+    const PAGE: &str = r#"<!DOCTYPE html>
+        <html>
+        <head><title>Beef Stew | Example Kitchen</title><script>window.ads = [];</script></head>
+        <body>
+            <header><nav><a href="/">Home</a> <a href="/recipes">Recipes</a></nav></header>
+            <div class="ad-slot">Buy a new oven today!</div>
+            <article class="recipe">
+                <header><h1>Beef Stew</h1></header>
+                <p>A hearty  stew for
+                   cold nights.</p>
+                <ul><li>500g beef</li><li>2 carrots</li></ul>
+                <div class="share-buttons">Share on Pinterest</div>
+                <ol><li>Brown the beef.</li><li>Simmer for two hours.</li></ol>
+            </article>
+            <aside><article><h2>You might also like</h2></article></aside>
+            <section id="comments"><p>Great recipe!</p></section>
+            <footer>Copyright Example Kitchen</footer>
+        </body>
+        </html>"#;
+
+    #[test]
+    fn only_the_recipe_is_kept() {
+        assert_eq!(
+            readable_text(PAGE),
+            "Beef Stew | Example Kitchen\nBeef Stew\nA hearty stew for cold nights.\n500g beef\n2 carrots\nBrown the beef.\nSimmer for two hours."
+        );
+    }
+
+    #[test]
+    fn the_longest_article_is_the_content() {
+        let html = "<html><body>
+            <article><h2>Quick pasta</h2></article>
+            <article><h1>Slow roasted lamb</h1><p>Roast the lamb for five hours.</p></article>
+        </body></html>";
+        assert_eq!(readable_text(html), "Slow roasted lamb\nRoast the lamb for five hours.");
+    }
+
+    #[test]
+    fn pages_without_an_article_use_the_body() {
+        let html = r#"<html><body><div role="navigation">Menu</div><p>Mix the flour</p><p hidden>Sign up</p></body></html>"#;
+        assert_eq!(readable_text(html), "Mix the flour");
+    }
+
+    #[test]
+    fn html_is_told_apart_from_page_text() {
+        assert!(looks_like_html("<!DOCTYPE html><html></html>"));
+        assert!(looks_like_html("\n  <HTML lang=\"en\">"));
+        assert!(!looks_like_html("Beef Stew\n<5 minutes to prepare"));
+    }
+}
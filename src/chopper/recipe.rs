@@ -0,0 +1,386 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use crate::domain::{IngredientLine, Quantity, QuantityError, UnitMatcher};
+use crate::models::ingredient::{Ingredient, CreateIngredientParams};
+use crate::models::recipe::{CreateRecipeParams, DuplicatePolicy, ImportOutcome, Recipe, MAX_STEP_MINUTES};
+use crate::models::recipe_component::{RecipeComponent, CreateRecipeComponentParams};
+use crate::models::recipe_component_ingredient::{RecipeComponentIngredient, CreateRecipeComponentIngredientParams};
+use crate::models::recipe_instruction::{RecipeInstruction, CreateRecipeInstructionParams};
+use crate::models::recipe_instruction_step::{RecipeInstructionStep, CreateRecipeInstructionStepParams};
+use crate::models::unit::{unit_matcher, Unit, CreateUnitParams};
+use crate::utils::ApiError;
+
+/// A recipe as read from a page. Descriptions, flags and times may be left out, everything
+/// else has to be there with the right type.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChopperRecipe {
+    #[serde(default)]
+    pub description: String,
+    pub name: String,
+    #[serde(default)]
+    pub prep_time: i32,
+    #[serde(default)]
+    pub rest_time: i32,
+    #[serde(default)]
+    pub cook_time: i32,
+    #[serde(default)]
+    pub servings: i32,
+    /// Comes from the request rather than the model
+    #[serde(default)]
+    pub source_url: String,
+    pub components: Vec<ChopperRecipeComponent>,
+    pub instructions: Vec<ChopperRecipeInstruction>,
+}
+
+/// Why a chopped recipe couldn't be saved. Nothing is written when this is returned.
+#[derive(Debug, thiserror::Error)]
+pub enum ChopperError {
+    #[error(transparent)]
+    Model(#[from] crate::models::Error),
+
+    #[error(transparent)]
+    Sqlx(#[from] sqlx::Error),
+
+    #[error(transparent)]
+    Quantity(#[from] QuantityError),
+
+    #[error("Failed to create {0}")]
+    NotCreated(&'static str),
+
+    #[error("The recipe has problems: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+}
+
+impl From<ChopperError> for ApiError {
+    fn from(err: ChopperError) -> Self {
+        match err {
+            ChopperError::Model(err) => err.into(),
+            ChopperError::Quantity(err) => ApiError::BadRequest(err.to_string()),
+            ChopperError::Invalid(problems) => ApiError::Validation(problems),
+            err => ApiError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl ChopperRecipe {
+    /// Lists everything that would stop the recipe from being saved as it is. Each problem
+    /// names the field it is about, so it can be sent back to the model to correct.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        if self.name.trim().is_empty() {
+            problems.push("name is empty".to_string());
+        }
+        for (field, value) in [("prep_time", self.prep_time), ("rest_time", self.rest_time), ("cook_time", self.cook_time), ("servings", self.servings)] {
+            if value < 0 {
+                problems.push(format!("{} is negative", field));
+            }
+        }
+        for (field, minutes) in [("prep_time", self.prep_time), ("rest_time", self.rest_time), ("cook_time", self.cook_time)] {
+            if minutes > MAX_STEP_MINUTES {
+                problems.push(format!("{} is more than {} minutes", field, MAX_STEP_MINUTES));
+            }
+        }
+
+        for (component_idx, component) in self.components.iter().enumerate() {
+            let path = format!("components[{}]", component_idx);
+            if component.name.trim().is_empty() {
+                problems.push(format!("{}.name is empty", path));
+            }
+            for (ingredient_idx, ingredient) in component.ingredients.iter().enumerate() {
+                let path = format!("{}.ingredients[{}]", path, ingredient_idx);
+                if ingredient.name.trim().is_empty() {
+                    problems.push(format!("{}.name is empty", path));
+                }
+                if ingredient.unit.trim().is_empty() {
+                    problems.push(format!("{}.unit is empty", path));
+                }
+                if ingredient.quantity_denominator == 0 {
+                    problems.push(format!("{}.quantity_denominator is 0", path));
+                }
+                if ingredient.quantity_numerator < 0 || ingredient.quantity_denominator < 0 {
+                    problems.push(format!("{} has a negative quantity", path));
+                }
+            }
+        }
+
+        let mut order_idxs = HashSet::new();
+        for (instruction_idx, instruction) in self.instructions.iter().enumerate() {
+            let path = format!("instructions[{}]", instruction_idx);
+            if !order_idxs.insert(instruction.order_idx) {
+                problems.push(format!("{}.order_idx {} is used more than once", path, instruction.order_idx));
+            }
+            let mut step_numbers = HashSet::new();
+            for (step_idx, step) in instruction.steps.iter().enumerate() {
+                let path = format!("{}.steps[{}]", path, step_idx);
+                if !step_numbers.insert(step.step_number) {
+                    problems.push(format!("{}.step_number {} is used more than once", path, step.step_number));
+                }
+                if step.content.trim().is_empty() {
+                    problems.push(format!("{}.content is empty", path));
+                }
+            }
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    /// Imports the recipe unless the user already has one from the same page, in which
+    /// case `on_duplicate` decides what happens
+    pub async fn save_for_user(
+        &self,
+        db: &PgPool,
+        user_id: &uuid::Uuid,
+        on_duplicate: DuplicatePolicy,
+    ) -> Result<(Recipe, ImportOutcome), ChopperError> {
+        match self.save_once(db, user_id, on_duplicate).await {
+            // Another import of the same page saved it in the meantime, so look again
+            Err(ChopperError::Model(err)) if err.is_duplicate_source_url() => self.save_once(db, user_id, on_duplicate).await,
+            saved => saved,
+        }
+    }
+
+    async fn save_once(
+        &self,
+        db: &PgPool,
+        user_id: &uuid::Uuid,
+        on_duplicate: DuplicatePolicy,
+    ) -> Result<(Recipe, ImportOutcome), ChopperError> {
+        let existing = Recipe::find_by_source_url(db, user_id, &self.source_url).await?;
+        let saved = match (existing, on_duplicate) {
+            (None, _) => (self.add_to_user(db, user_id).await?, ImportOutcome::Created),
+            (Some(recipe), DuplicatePolicy::Skip) => (recipe, ImportOutcome::Skipped),
+            (Some(recipe), DuplicatePolicy::Overwrite) => (self.overwrite(db, user_id, recipe.id).await?, ImportOutcome::Overwritten),
+            (Some(_), DuplicatePolicy::Copy) => (self.add_to_user(db, user_id).await?, ImportOutcome::Copied),
+        };
+
+        Ok(saved)
+    }
+
+    /// Writes the recipe and everything in it in a single transaction, creating any
+    /// ingredients and units that don't exist yet
+    pub async fn add_to_user(&self, db: &PgPool, user_id: &uuid::Uuid) -> Result<Recipe, ChopperError> {
+        self.validate().map_err(ChopperError::Invalid)?;
+        // Models answer "cups" or "Tbsp" as often as the unit's name
+        let units = unit_matcher(&Unit::list(db).await?);
+        let mut transaction = db.begin().await?;
+        let recipe = Recipe::create(&mut *transaction, &self.create_params(user_id)).await?
+            .ok_or(ChopperError::NotCreated("recipe"))?;
+        self.write_children(&mut transaction, recipe.id, &units).await?;
+        transaction.commit().await?;
+
+        Ok(recipe)
+    }
+
+    /// Replaces the contents of one of the user's recipes, keeping its id
+    async fn overwrite(&self, db: &PgPool, user_id: &uuid::Uuid, recipe_id: i32) -> Result<Recipe, ChopperError> {
+        self.validate().map_err(ChopperError::Invalid)?;
+        let units = unit_matcher(&Unit::list(db).await?);
+        let mut transaction = db.begin().await?;
+        let recipe = Recipe::overwrite(&mut transaction, recipe_id, &self.create_params(user_id)).await?
+            .ok_or(ChopperError::NotCreated("recipe"))?;
+        self.write_children(&mut transaction, recipe.id, &units).await?;
+        transaction.commit().await?;
+
+        Ok(recipe)
+    }
+
+    fn create_params(&self, user_id: &uuid::Uuid) -> CreateRecipeParams {
+        CreateRecipeParams {
+            user_id: *user_id,
+            description: self.description.clone(),
+            name: self.name.clone(),
+            is_public: false,
+            prep_time: Some(self.prep_time),
+            rest_time: Some(self.rest_time),
+            cook_time: Some(self.cook_time),
+            // Models and structured data without a yield answer 0
+            servings: Some(self.servings).filter(|servings| *servings > 0),
+            source_url: Some(self.source_url.clone()),
+        }
+    }
+
+    async fn write_children(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        recipe_id: i32,
+        units: &UnitMatcher,
+    ) -> Result<(), ChopperError> {
+        for component in &self.components {
+            let create_recipe_component_params = CreateRecipeComponentParams {
+                recipe_id,
+                name: component.name.clone(),
+                is_optional: component.is_optional,
+            };
+            let recipe_component = RecipeComponent::create(&mut **transaction, &create_recipe_component_params).await?
+                .ok_or(ChopperError::NotCreated("recipe component"))?;
+
+            for comp_ing in &component.ingredients {
+                let ingredient = match Ingredient::find_by_name(&mut **transaction, comp_ing.name.as_str()).await? {
+                    Some(ing) => ing,
+                    None => {
+                        let ing_params = CreateIngredientParams::new(comp_ing.name.to_string(), comp_ing.description.to_string());
+                        Ingredient::create(&mut **transaction, &ing_params).await?
+                    }
+                };
+
+                let unit_name = units.find(&comp_ing.unit).unwrap_or(comp_ing.unit.as_str());
+                let unit = match Unit::find_by_name(&mut **transaction, unit_name).await? {
+                    Some(unit) => unit,
+                    None => {
+                        let unit_params = CreateUnitParams::new(&unit_name.to_string());
+                        Unit::create(&mut **transaction, &unit_params).await?
+                    }
+                };
+
+                let create_component_ingredient_params = CreateRecipeComponentIngredientParams {
+                    recipe_component_id: recipe_component.id,
+                    ingredient_id: ingredient.id,
+                    unit_id: unit.id,
+                    quantity: comp_ing.quantity()?,
+                    is_optional: comp_ing.is_optional,
+                };
+                RecipeComponentIngredient::create(&mut **transaction, &create_component_ingredient_params).await?
+                    .ok_or(ChopperError::NotCreated("recipe component ingredient"))?;
+            }
+        }
+
+        for instruction in &self.instructions {
+            let create_recipe_instruction_params = CreateRecipeInstructionParams {
+                recipe_id,
+                order_idx: instruction.order_idx,
+                title: instruction.title.clone(),
+            };
+            let recipe_instruction = RecipeInstruction::create(&mut **transaction, &create_recipe_instruction_params).await?
+                .ok_or(ChopperError::NotCreated("recipe instruction"))?;
+
+            for inst_step in &instruction.steps {
+                let create_recipe_instruction_step_params = CreateRecipeInstructionStepParams {
+                    recipe_id,
+                    recipe_instruction_id: recipe_instruction.id,
+                    step_number: inst_step.step_number,
+                    content: inst_step.content.clone(),
+                };
+
+                RecipeInstructionStep::create(&mut **transaction, &create_recipe_instruction_step_params).await?
+                    .ok_or(ChopperError::NotCreated("recipe instruction step"))?;
+            }
+        }
+        Recipe::refresh_search(&mut **transaction, recipe_id).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChopperRecipeComponent {
+    pub name: String,
+    #[serde(default)]
+    pub is_optional: bool,
+    pub ingredients: Vec<ChopperComponentIngredient>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChopperComponentIngredient {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub is_optional: bool,
+    pub name: String,
+    pub quantity_denominator: i32,
+    pub quantity_numerator: i32,
+    pub unit: String,
+}
+
+impl ChopperComponentIngredient {
+    /// Splits "1 1/2 cups flour" into its amount, unit and name. Lines without a known unit
+    /// are counted in pieces.
+    pub fn from_line(line: &str, units: &UnitMatcher) -> Self {
+        let line = IngredientLine::parse(line, units);
+        let quantity = line.amount();
+
+        Self {
+            description: line.note.unwrap_or_default(),
+            is_optional: line.is_optional,
+            name: line.name,
+            quantity_numerator: quantity.numerator(),
+            quantity_denominator: quantity.denominator(),
+            unit: line.unit.unwrap_or_else(|| "piece".to_string()),
+        }
+    }
+
+    /// The LLM answers with separate numerator and denominator fields, which are only
+    /// trusted once they form a valid `Quantity`
+    pub fn quantity(&self) -> Result<Quantity, QuantityError> {
+        Quantity::new(self.quantity_numerator, self.quantity_denominator)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChopperRecipeInstruction {
+    pub order_idx: i32,
+    pub title: String,
+    pub steps: Vec<ChopperInstructionStep>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChopperInstructionStep {
+    pub step_number: i32,
+    pub content: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChopperRecipe;
+
+    fn recipe() -> ChopperRecipe {
+        serde_json::from_str(include_str!("../../tests/fixtures/chopper_recipe.json")).unwrap()
+    }
+
+    #[test]
+    fn fixture_recipe_is_valid() {
+        assert_eq!(recipe().validate(), Ok(()));
+    }
+
+    #[test]
+    fn every_problem_is_listed_with_its_field() {
+        let mut recipe = recipe();
+        recipe.name = " ".to_string();
+        recipe.components[0].ingredients[1].quantity_denominator = 0;
+        let first_step = recipe.instructions[0].steps[0].clone();
+        recipe.instructions[0].steps.push(first_step);
+
+        let problems = recipe.validate().unwrap_err();
+        assert_eq!(problems, vec![
+            "name is empty".to_string(),
+            "components[0].ingredients[1].quantity_denominator is 0".to_string(),
+            format!("instructions[0].steps[{}].step_number 1 is used more than once", recipe.instructions[0].steps.len() - 1),
+        ]);
+    }
+
+    #[test]
+    fn times_longer_than_a_week_are_listed() {
+        let mut recipe = recipe();
+        recipe.cook_time = i32::MAX;
+
+        assert_eq!(recipe.validate(), Err(vec!["cook_time is more than 10080 minutes".to_string()]));
+    }
+
+    #[test]
+    fn instruction_order_must_be_unique() {
+        let mut recipe = recipe();
+        let mut instruction = recipe.instructions[0].clone();
+        instruction.title = "Again".to_string();
+        recipe.instructions.push(instruction);
+
+        let problems = recipe.validate().unwrap_err();
+        assert_eq!(problems, vec!["instructions[1].order_idx 1 is used more than once".to_string()]);
+    }
+
+    #[test]
+    fn answers_with_the_wrong_types_are_rejected() {
+        let answer = r#"{"name": "Stew", "components": [{"name": "Main", "ingredients": [{"name": "Potato", "unit": "cup", "quantity_numerator": "1", "quantity_denominator": 2}]}], "instructions": []}"#;
+        assert!(serde_json::from_str::<ChopperRecipe>(answer).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+use futures::FutureExt;
+use sqlx::PgPool;
+use crate::models::chopper_job::ChopperJob;
+use crate::models::recipe::{ImportOutcome, Recipe};
+use super::{ChopperRequest, RecipeParser};
+
+impl From<&ChopperJob> for ChopperRequest {
+    fn from(job: &ChopperJob) -> Self {
+        Self {
+            text: job.text.clone(),
+            source_url: job.source_url.clone(),
+            hostname: job.hostname.clone(),
+            pathname: job.pathname.clone(),
+            on_duplicate: job.on_duplicate,
+        }
+    }
+}
+
+/// Works through queued chopper jobs one at a time, checking for new ones every
+/// `poll_interval` while the queue is empty
+pub async fn run_worker(db: PgPool, parser: Arc<dyn RecipeParser>, poll_interval: Duration) -> Result<(), anyhow::Error> {
+    loop {
+        match process_next_job(&db, parser.as_ref()).await {
+            Ok(true) => continue,
+            Ok(false) => {},
+            Err(err) => tracing::error!("Failed to process chopper job: {:?}", err),
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Parses and saves the oldest waiting job. Answers false when there was nothing to do.
+/// Failing to parse or save the recipe, or panicking while doing so, fails the job rather
+/// than the worker.
+pub async fn process_next_job(db: &PgPool, parser: &dyn RecipeParser) -> Result<bool, crate::models::Error> {
+    ChopperJob::fail_abandoned(db).await?;
+    let Some(job) = ChopperJob::claim_next(db).await? else {
+        return Ok(false);
+    };
+
+    match run_job(db, parser, &job).await {
+        Ok((recipe, outcome)) => ChopperJob::succeed(db, &job.id, recipe.id, outcome).await?,
+        Err(err) => {
+            tracing::warn!("Chopper job {} failed: {}", job.id, err);
+            ChopperJob::fail(db, &job.id, &err).await?
+        },
+    }
+
+    Ok(true)
+}
+
+/// Parses the job's page and saves the recipe, answering why it couldn't instead. A panic
+/// while doing either is caught and answered as the reason too.
+pub async fn run_job(db: &PgPool, parser: &dyn RecipeParser, job: &ChopperJob) -> Result<(Recipe, ImportOutcome), String> {
+    let request = ChopperRequest::from(job);
+    let parse_and_save = async {
+        match parser.parse(&request).await {
+            Ok(recipe) => recipe.save_for_user(db, &job.user_id, job.on_duplicate).await.map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        }
+    };
+
+    match AssertUnwindSafe(parse_and_save).catch_unwind().await {
+        Ok(result) => result,
+        Err(panic) => Err(format!("The chopper crashed: {}", panic_message(panic.as_ref()))),
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic.downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
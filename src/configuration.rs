@@ -42,6 +42,8 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email: EmailSettings,
     pub redis_uri: Secret<String>,
+    #[serde(default)]
+    pub chopper: ChopperSettings,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -76,6 +78,61 @@ pub struct EmailSettings {
     pub welcome_email: String,
 }
 
+/// Which model the chopper uses to read recipes
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChopperProvider {
+    /// OpenAI or any other server with an OpenAI compatible chat completions endpoint
+    OpenAi,
+    Ollama,
+    /// Always returns the recipe in `fixture_path`
+    Fixture,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ChopperSettings {
+    pub provider: ChopperProvider,
+    /// Defaults to the provider's public or local address
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    /// Falls back to the OPENAI_API_KEY environment variable
+    pub api_key: Option<Secret<String>>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_seconds: u64,
+    pub temperature: f32,
+    pub fixture_path: Option<String>,
+    /// Sends an unusable answer back to the model, with what is wrong with it, for one more try
+    pub reprompt_on_invalid: bool,
+    /// How long the import worker waits before looking for new jobs when the queue is empty
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub poll_interval_milliseconds: u64,
+    /// How long imports by URL wait for the recipe page
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub fetch_timeout_seconds: u64,
+    /// Lets imports by URL fetch pages from loopback, private and link-local addresses. Only
+    /// for tests and local development, anyone with an API key could reach internal services.
+    pub fetch_private_addresses: bool,
+}
+
+impl Default for ChopperSettings {
+    fn default() -> Self {
+        Self {
+            provider: ChopperProvider::OpenAi,
+            base_url: None,
+            model: None,
+            api_key: None,
+            timeout_seconds: 30,
+            temperature: 0.4,
+            fixture_path: None,
+            reprompt_on_invalid: true,
+            poll_interval_milliseconds: 1000,
+            fetch_timeout_seconds: 15,
+            fetch_private_addresses: false,
+        }
+    }
+}
+
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct TestSettings {
     pub secret_key: String
@@ -11,6 +11,17 @@ pub mod html_templates {
     pub const E500: &str = "500.html";
     pub const RECIPES_INDEX: &str = "recipes/index.html";
     pub const RECIPES_SHOW: &str = "recipes/show.html";
+    pub const RECIPES_NEW: &str = "recipes/new.html";
+    pub const RECIPES_EDIT: &str = "recipes/edit.html";
+    pub const RECIPES_IMPORT: &str = "recipes/import.html";
+    pub const RECIPES_PRINT: &str = "recipes/print.html";
+    pub const SHOPPING_LIST: &str = "shopping_list/show.html";
+    pub const PANTRY_INDEX: &str = "pantry/index.html";
+    pub const PANTRY_EDIT: &str = "pantry/edit.html";
+    pub const MEAL_PLANS_WEEK: &str = "meal_plans/week.html";
+    pub const MEAL_PLANS_MONTH: &str = "meal_plans/month.html";
+    pub const MEAL_PLANS_SHOPPING_LIST: &str = "meal_plans/shopping_list.html";
+    pub const SETTINGS: &str = "settings.html";
 }
 
 /// email templates
@@ -59,6 +70,7 @@ Please give those to me in JSON in this exact format.
     }]
   }]
 }";
+    pub const RECIPE_REPAIR_PROMPT: &str = "Your answer could not be used because of these problems. Fix them and answer again with the whole recipe as JSON in the same format, and nothing else.";
 }
 
 /// paths
@@ -71,6 +83,18 @@ pub mod route_paths {
     pub const PROTECTED: &str = "/protected";
     pub const RECIPES: &str = "/recipes";
     pub const API: &str = "/api";
+    pub const V1: &str = "/v1";
     pub const CHOPPER: &str = "/chopper";
+    pub const CHOPPER_JOBS: &str = "/chopper/jobs";
+    pub const IMPORT_URL: &str = "/import/url";
+    pub const IMPORT_LIBRARY: &str = "/import";
+    pub const EXPORT: &str = "/export";
+    pub const SEARCH: &str = "/search";
+    pub const COOKABLE: &str = "/cookable";
+    pub const SHOPPING_LIST: &str = "/shopping_list";
+    pub const PANTRY: &str = "/pantry";
+    pub const MEAL_PLANS: &str = "/meal_plans";
+    pub const CALENDAR: &str = "/calendar";
+    pub const SETTINGS: &str = "/settings";
 }
 
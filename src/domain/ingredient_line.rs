@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use regex::Regex;
+use super::{Quantity, QuantityError};
+
+/// Spellings that aren't a unit's name, abbreviation or their plural
+const UNIT_ALIASES: &[(&str, &str)] = &[
+    ("gr", "gram"),
+    ("kilo", "kilogram"),
+    ("millilitre", "milliliter"),
+    ("litre", "liter"),
+    ("l", "liter"),
+    ("tbs", "tablespoon"),
+    ("tbl", "tablespoon"),
+    ("c", "cup"),
+    ("pkg", "package"),
+];
+
+const UNICODE_FRACTIONS: &[(char, &str)] = &[
+    ('½', "1/2"),
+    ('⅓', "1/3"),
+    ('⅔', "2/3"),
+    ('¼', "1/4"),
+    ('¾', "3/4"),
+    ('⅕', "1/5"),
+    ('⅖', "2/5"),
+    ('⅗', "3/5"),
+    ('⅘', "4/5"),
+    ('⅙', "1/6"),
+    ('⅚', "5/6"),
+    ('⅐', "1/7"),
+    ('⅛', "1/8"),
+    ('⅜', "3/8"),
+    ('⅝', "5/8"),
+    ('⅞', "7/8"),
+    ('⅑', "1/9"),
+    ('⅒', "1/10"),
+];
+
+const NUMBER: &str = r"\d+\s+\d+/\d+|\d+/\d+|\d*\.\d+|\d+";
+
+/// Recognises the ways units are written in ingredient lists and answers the unit's name
+#[derive(Debug, Clone, Default)]
+pub struct UnitMatcher {
+    spellings: HashMap<String, String>,
+}
+
+impl UnitMatcher {
+    /// Builds a matcher from each unit's name and abbreviation
+    pub fn new<'a>(units: impl IntoIterator<Item = (&'a str, Option<&'a str>)>) -> Self {
+        let mut spellings = HashMap::new();
+        for (name, abbreviation) in units {
+            spellings.insert(name.to_lowercase(), name.to_string());
+            spellings.insert(name.replace('_', " ").to_lowercase(), name.to_string());
+            if let Some(abbreviation) = abbreviation {
+                spellings.insert(abbreviation.to_lowercase(), name.to_string());
+            }
+        }
+        for (alias, name) in UNIT_ALIASES {
+            if spellings.values().any(|unit| unit == name) {
+                spellings.entry(alias.to_string()).or_insert(name.to_string());
+            }
+        }
+
+        Self { spellings }
+    }
+
+    /// Also recognises `units`, for formats that use units we don't start out with
+    pub fn with_units<'a>(mut self, units: impl IntoIterator<Item = (&'a str, Option<&'a str>)>) -> Self {
+        for (spelling, name) in Self::new(units).spellings {
+            self.spellings.entry(spelling).or_insert(name);
+        }
+
+        self
+    }
+
+    /// Finds the unit written as `word`, ignoring case, a trailing period and plurals
+    pub fn find(&self, word: &str) -> Option<&str> {
+        let word = word.trim_end_matches('.').to_lowercase();
+        let spellings = [Some(word.as_str()), word.strip_suffix("es"), word.strip_suffix('s')];
+        let name = spellings.into_iter().flatten().find_map(|spelling| self.spellings.get(spelling))?;
+
+        Some(name.as_str())
+    }
+}
+
+/// One line of an ingredient list such as "1 1/2 cups finely chopped onion, divided"
+#[derive(Debug, Clone, PartialEq)]
+pub struct IngredientLine {
+    pub quantity: Option<Quantity>,
+    /// The upper end of ranges such as "2-3 cloves"
+    pub max_quantity: Option<Quantity>,
+    /// The name of the matched unit
+    pub unit: Option<String>,
+    pub name: String,
+    /// Parenthetical remarks and anything after the first comma
+    pub note: Option<String>,
+    pub is_optional: bool,
+}
+
+impl IngredientLine {
+    pub fn parse(line: &str, units: &UnitMatcher) -> Self {
+        let mut notes: Vec<String> = Vec::new();
+        let mut unit: Option<String> = None;
+        let mut is_optional = false;
+
+        let paren_re = Regex::new(r"\(([^()]*)\)").unwrap();
+        let line = normalize(line);
+        for captures in paren_re.captures_iter(&line) {
+            notes.push(captures[1].trim().to_string());
+        }
+        let line = paren_re.replace_all(&line, " ");
+
+        let to_taste_re = Regex::new(r"(?i),?\s*\bto taste\b").unwrap();
+        let has_to_taste = to_taste_re.is_match(&line);
+        let line = to_taste_re.replace_all(&line, "");
+        let mut rest = line.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+        let quantity_re = Regex::new(&format!(r"^({NUMBER})(?:\s*(?:-|–|—|to|or)\s*({NUMBER}))?\s*")).unwrap();
+        let (mut quantity, mut max_quantity) = (None, None);
+        if let Some(captures) = quantity_re.captures(&rest) {
+            if let Ok(low) = captures[1].parse::<Quantity>() {
+                quantity = Some(low);
+                max_quantity = captures.get(2).and_then(|high| high.as_str().parse::<Quantity>().ok());
+                rest = rest[captures[0].len()..].to_string();
+            }
+        }
+
+        let article_re = Regex::new(r"(?i)^an?\s+").unwrap();
+        let word_re = Regex::new(r"^([A-Za-z]+\.?)(?:\s+|$|[,;])").unwrap();
+        let after_article = article_re.find(&rest).map(|article| article.end());
+        let unit_start = if quantity.is_none() { after_article.unwrap_or(0) } else { 0 };
+        if let Some(captures) = word_re.captures(&rest[unit_start..]) {
+            if let Some(name) = units.find(&captures[1]) {
+                unit = Some(name.to_string());
+                if unit_start > 0 {
+                    quantity = Some(Quantity::whole(1));
+                }
+                rest = rest[unit_start + captures[1].len()..].trim_start().to_string();
+                if let Some(stripped) = rest.strip_prefix("of ") {
+                    rest = stripped.to_string();
+                }
+            }
+        }
+        if has_to_taste && unit.is_none() {
+            unit = units.find("to taste").map(str::to_string);
+        }
+
+        let (name, comma_note) = match rest.split_once(',') {
+            Some((name, note)) => (name.to_string(), Some(note.to_string())),
+            None => (rest, None),
+        };
+        notes.extend(comma_note);
+        let notes: Vec<String> = notes
+            .iter()
+            .flat_map(|note| note.split(','))
+            .map(str::trim)
+            .filter(|note| !note.is_empty())
+            .filter(|note| {
+                let optional = note.eq_ignore_ascii_case("optional");
+                is_optional |= optional;
+                !optional
+            })
+            .map(str::to_string)
+            .collect();
+
+        Self {
+            quantity,
+            max_quantity,
+            unit,
+            name: name.trim().trim_end_matches([',', ';']).trim().to_string(),
+            note: Some(notes.join(", ")).filter(|note| !note.is_empty()),
+            is_optional,
+        }
+    }
+
+    /// How much to use. Ranges use their upper end so the shopping list never comes up short.
+    pub fn amount(&self) -> Quantity {
+        self.max_quantity.or(self.quantity).unwrap_or_else(|| Quantity::whole(1))
+    }
+}
+
+/// Parses a single amount, also accepting unicode fractions such as "1½"
+pub fn parse_quantity(text: &str) -> Result<Quantity, QuantityError> {
+    normalize(text).parse()
+}
+
+/// Spells out unicode fractions ("1½" becomes "1 1/2") and collapses whitespace
+fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for c in text.chars() {
+        match UNICODE_FRACTIONS.iter().find(|(fraction, _)| *fraction == c) {
+            Some((_, spelled)) => {
+                normalized.push(' ');
+                normalized.push_str(spelled);
+                normalized.push(' ');
+            },
+            None if c == '⁄' => normalized.push('/'),
+            None => normalized.push(c),
+        }
+    }
+
+    normalized.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_quantity, IngredientLine, UnitMatcher};
+    use crate::domain::Quantity;
+    use quickcheck::{Arbitrary, Gen};
+
+    const UNITS: &[(&str, Option<&str>)] = &[
+        ("gram", Some("g")),
+        ("kilogram", Some("kg")),
+        ("liter", Some("L")),
+        ("teaspoon", Some("tsp")),
+        ("tablespoon", Some("tbsp")),
+        ("cup", Some("cup")),
+        ("ounce", Some("oz")),
+        ("pound", Some("lb")),
+        ("piece", Some("pc")),
+        ("pinch", None),
+        ("can", None),
+        ("to_taste", None),
+    ];
+
+    fn matcher() -> UnitMatcher {
+        UnitMatcher::new(UNITS.iter().copied())
+    }
+
+    fn parse(line: &str) -> IngredientLine {
+        IngredientLine::parse(line, &matcher())
+    }
+
+    #[test]
+    fn reads_quantity_unit_name_and_note() {
+        let line = parse("1 1/2 cups finely chopped onion, divided");
+        assert_eq!(line.quantity, Some(Quantity::new(3, 2).unwrap()));
+        assert_eq!(line.unit.as_deref(), Some("cup"));
+        assert_eq!(line.name, "finely chopped onion");
+        assert_eq!(line.note.as_deref(), Some("divided"));
+    }
+
+    #[test]
+    fn reads_unicode_fractions() {
+        assert_eq!(parse("1½ tsp salt").quantity, Some(Quantity::new(3, 2).unwrap()));
+        assert_eq!(parse("¾ cup sugar").quantity, Some(Quantity::new(3, 4).unwrap()));
+        assert_eq!(parse("1 1⁄4 cups milk").quantity, Some(Quantity::new(5, 4).unwrap()));
+    }
+
+    #[test]
+    fn reads_ranges() {
+        let line = parse("2-3 cloves garlic");
+        assert_eq!((line.quantity, line.max_quantity), (Some(Quantity::whole(2)), Some(Quantity::whole(3))));
+        assert_eq!(line.amount(), Quantity::whole(3));
+        assert_eq!(line.name, "cloves garlic");
+
+        let line = parse("1 to 2 tbsp olive oil");
+        assert_eq!(line.max_quantity, Some(Quantity::whole(2)));
+        assert_eq!(line.unit.as_deref(), Some("tablespoon"));
+    }
+
+    #[test]
+    fn reads_abbreviations_and_attached_units() {
+        assert_eq!(parse("200g flour").unit.as_deref(), Some("gram"));
+        assert_eq!(parse("2 Tbsp. butter").unit.as_deref(), Some("tablespoon"));
+        assert_eq!(parse("1 lbs ground beef").unit.as_deref(), Some("pound"));
+        assert_eq!(parse("1 l water").unit.as_deref(), Some("liter"));
+        assert_eq!(parse("2 pinches nutmeg").unit.as_deref(), Some("pinch"));
+    }
+
+    #[test]
+    fn parenthetical_notes_are_kept() {
+        let line = parse("1 (14 oz) can diced tomatoes (optional)");
+        assert_eq!(line.quantity, Some(Quantity::whole(1)));
+        assert_eq!(line.unit.as_deref(), Some("can"));
+        assert_eq!(line.name, "diced tomatoes");
+        assert_eq!(line.note.as_deref(), Some("14 oz"));
+        assert!(line.is_optional);
+    }
+
+    #[test]
+    fn lines_without_an_amount() {
+        let line = parse("Salt and pepper, to taste");
+        assert_eq!(line.quantity, None);
+        assert_eq!(line.unit.as_deref(), Some("to_taste"));
+        assert_eq!(line.name, "Salt and pepper");
+        assert_eq!(line.amount(), Quantity::whole(1));
+
+        let line = parse("a pinch of saffron");
+        assert_eq!(line.quantity, Some(Quantity::whole(1)));
+        assert_eq!(line.unit.as_deref(), Some("pinch"));
+        assert_eq!(line.name, "saffron");
+
+        let line = parse("2 eggs");
+        assert_eq!(line.unit, None);
+        assert_eq!(line.name, "eggs");
+    }
+
+    #[test]
+    fn single_quantities_accept_unicode_fractions() {
+        assert_eq!(parse_quantity("2½"), Ok(Quantity::new(5, 2).unwrap()));
+        assert!(parse_quantity("some").is_err());
+    }
+
+    #[derive(Debug, Clone)]
+    struct Name(String);
+
+    /// Lowercase words that can't be mistaken for a unit
+    impl Arbitrary for Name {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let letters: String = String::arbitrary(g).chars().filter(char::is_ascii_lowercase).take(8).collect();
+            Name(format!("{}berry", letters))
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn never_panics_and_trims_the_name(line: String) -> bool {
+        let parsed = parse(&line);
+        parsed.name == parsed.name.trim()
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn formatted_lines_round_trip(numerator: u16, denominator: u16, unit_idx: usize, name: Name) -> bool {
+        let Ok(quantity) = Quantity::new(numerator as i32, denominator.max(1) as i32) else {
+            return false;
+        };
+        let (unit, _) = UNITS[unit_idx % (UNITS.len() - 1)];
+        let parsed = parse(&format!("{} {} {}", quantity, unit, name.0));
+
+        parsed.quantity == Some(quantity) && parsed.unit.as_deref() == Some(unit) && parsed.name == name.0
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn ranges_keep_both_ends(low: u8, high: u8, name: Name) -> bool {
+        let parsed = parse(&format!("{}-{} g {}", low, high, name.0));
+
+        parsed.quantity == Some(Quantity::whole(low as i32))
+            && parsed.max_quantity == Some(Quantity::whole(high as i32))
+            && parsed.name == name.0
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn unicode_fractions_add_to_the_whole_number(whole: u8, fraction_idx: usize) -> bool {
+        let (fraction, spelled) = super::UNICODE_FRACTIONS[fraction_idx % super::UNICODE_FRACTIONS.len()];
+        let expected = Quantity::whole(whole as i32).checked_add(&spelled.parse().unwrap()).unwrap();
+
+        parse(&format!("{}{} cup oats", whole, fraction)).quantity == Some(expected)
+    }
+}
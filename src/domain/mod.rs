@@ -1,7 +1,15 @@
+mod ingredient_line;
 mod new_user;
+mod quantity;
+mod search_snippet;
+mod source_url;
 mod user_email;
 mod user_password;
 
+pub use ingredient_line::{parse_quantity, IngredientLine, UnitMatcher};
 pub use new_user::NewUser;
+pub use quantity::{Quantity, QuantityError};
+pub use search_snippet::{SearchSnippet, SnippetPart};
+pub use source_url::normalize_source_url;
 pub use user_email::UserEmail;
 pub use user_password::UserPassword;
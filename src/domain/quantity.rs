@@ -0,0 +1,316 @@
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+
+/// An exact ingredient amount stored as a reduced fraction.
+/// The denominator is always positive and never zero, so "3/6" is always kept as "1/2".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "RawQuantity")]
+pub struct Quantity {
+    numerator: i32,
+    denominator: i32,
+}
+
+/// The shape a quantity has on the wire before it is validated
+#[derive(Deserialize)]
+struct RawQuantity {
+    numerator: i32,
+    denominator: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum QuantityError {
+    #[error("Quantity denominator cannot be zero")]
+    ZeroDenominator,
+
+    #[error("Quantity is too large")]
+    Overflow,
+
+    #[error("{0} is not a valid quantity")]
+    Invalid(String),
+}
+
+impl TryFrom<RawQuantity> for Quantity {
+    type Error = QuantityError;
+
+    fn try_from(value: RawQuantity) -> Result<Self, Self::Error> {
+        Quantity::new(value.numerator, value.denominator)
+    }
+}
+
+impl Quantity {
+    pub fn new(numerator: i32, denominator: i32) -> Result<Self, QuantityError> {
+        Self::from_i64(numerator as i64, denominator as i64)
+    }
+
+    pub fn whole(value: i32) -> Self {
+        Self { numerator: value, denominator: 1 }
+    }
+
+    pub fn zero() -> Self {
+        Self::whole(0)
+    }
+
+    pub fn numerator(&self) -> i32 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> i32 {
+        self.denominator
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator == 0
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// Finds the fraction closest to `value` that uses one of `denominators`.
+    /// Unit conversions are rarely exact, so this keeps converted amounts readable.
+    pub fn approximate(value: f64, denominators: &[i32]) -> Result<Quantity, QuantityError> {
+        let mut closest: Option<(f64, Quantity)> = None;
+        for &denominator in denominators {
+            let numerator = (value * denominator as f64).round();
+            if !numerator.is_finite() || numerator.abs() > i32::MAX as f64 {
+                return Err(QuantityError::Overflow);
+            }
+            let candidate = Quantity::new(numerator as i32, denominator)?;
+            let error = (candidate.to_f64() - value).abs();
+            match closest {
+                Some((closest_error, _)) if closest_error <= error => {},
+                _ => closest = Some((error, candidate)),
+            }
+        }
+
+        closest
+            .map(|(_, quantity)| quantity)
+            .ok_or_else(|| QuantityError::Invalid(value.to_string()))
+    }
+
+    pub fn checked_add(&self, other: &Quantity) -> Result<Quantity, QuantityError> {
+        Self::from_i64(
+            self.numerator as i64 * other.denominator as i64 + other.numerator as i64 * self.denominator as i64,
+            self.denominator as i64 * other.denominator as i64,
+        )
+    }
+
+    pub fn checked_sub(&self, other: &Quantity) -> Result<Quantity, QuantityError> {
+        Self::from_i64(
+            self.numerator as i64 * other.denominator as i64 - other.numerator as i64 * self.denominator as i64,
+            self.denominator as i64 * other.denominator as i64,
+        )
+    }
+
+    pub fn checked_mul(&self, other: &Quantity) -> Result<Quantity, QuantityError> {
+        Self::from_i64(
+            self.numerator as i64 * other.numerator as i64,
+            self.denominator as i64 * other.denominator as i64,
+        )
+    }
+
+    /// Reduces the fraction and moves the sign onto the numerator. The arithmetic is done in
+    /// i64 so that only results which don't fit back into the database columns overflow.
+    fn from_i64(numerator: i64, denominator: i64) -> Result<Self, QuantityError> {
+        if denominator == 0 {
+            return Err(QuantityError::ZeroDenominator);
+        }
+        let sign = if denominator < 0 { -1 } else { 1 };
+        let divisor = gcd(numerator, denominator).max(1);
+        let numerator = i32::try_from(sign * numerator / divisor).map_err(|_| QuantityError::Overflow)?;
+        let denominator = i32::try_from(sign * denominator / divisor).map_err(|_| QuantityError::Overflow)?;
+
+        Ok(Self { numerator, denominator })
+    }
+}
+
+/// Compares by value. Denominators are always positive, so cross-multiplying keeps the order.
+impl Ord for Quantity {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.numerator as i64 * other.denominator as i64).cmp(&(other.numerator as i64 * self.denominator as i64))
+    }
+}
+
+impl PartialOrd for Quantity {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+/// Formats as a mixed number, e.g. "2", "1/2" or "1 1/2"
+impl std::fmt::Display for Quantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.numerator < 0 { "-" } else { "" };
+        let numerator = self.numerator.unsigned_abs();
+        let denominator = self.denominator.unsigned_abs();
+        let whole = numerator / denominator;
+        let remainder = numerator % denominator;
+
+        match (whole, remainder) {
+            (_, 0) => write!(f, "{}{}", sign, whole),
+            (0, _) => write!(f, "{}{}/{}", sign, remainder, denominator),
+            _ => write!(f, "{}{} {}/{}", sign, whole, remainder, denominator),
+        }
+    }
+}
+
+/// Parses whole numbers, fractions, mixed numbers and decimals: "2", "1/2", "1 1/2", "0.25"
+impl FromStr for Quantity {
+    type Err = QuantityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || QuantityError::Invalid(s.to_string());
+        let parts: Vec<&str> = s.split_whitespace().collect();
+
+        match parts.as_slice() {
+            [whole, fraction] if fraction.contains('/') => {
+                let negative = whole.starts_with('-');
+                let whole: i32 = whole.strip_prefix('-').unwrap_or(whole).parse().map_err(|_| invalid())?;
+                let fraction: Quantity = fraction.parse()?;
+                if whole < 0 || fraction.numerator < 0 {
+                    return Err(invalid());
+                }
+                let quantity = Quantity::whole(whole).checked_add(&fraction)?;
+                if negative { quantity.checked_mul(&Quantity::whole(-1)) } else { Ok(quantity) }
+            },
+            [single] => match single.split_once('/') {
+                Some((numerator, denominator)) => Quantity::new(
+                    numerator.parse().map_err(|_| invalid())?,
+                    denominator.parse().map_err(|_| invalid())?,
+                ),
+                None => match single.split_once('.') {
+                    Some((whole, decimals)) => {
+                        if decimals.is_empty() || decimals.len() > 6 || !decimals.chars().all(|c| c.is_ascii_digit()) {
+                            return Err(invalid());
+                        }
+                        let negative = whole.starts_with('-');
+                        let whole: i32 = if whole.is_empty() || whole == "-" { 0 } else { whole.parse().map_err(|_| invalid())? };
+                        let scale = 10_i32.pow(decimals.len() as u32);
+                        let fraction = Quantity::new(decimals.parse().map_err(|_| invalid())?, scale)?;
+                        let fraction = if negative { fraction.checked_mul(&Quantity::whole(-1))? } else { fraction };
+                        Quantity::whole(whole).checked_add(&fraction)
+                    },
+                    None => Ok(Quantity::whole(single.parse().map_err(|_| invalid())?)),
+                },
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Quantity, QuantityError};
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn zero_denominator_is_rejected() {
+        assert_eq!(Quantity::new(1, 0), Err(QuantityError::ZeroDenominator));
+    }
+
+    #[test]
+    fn fractions_are_reduced() {
+        let quantity = Quantity::new(3, 6).unwrap();
+        assert_eq!((quantity.numerator(), quantity.denominator()), (1, 2));
+    }
+
+    #[test]
+    fn sign_is_kept_on_the_numerator() {
+        let quantity = Quantity::new(1, -2).unwrap();
+        assert_eq!((quantity.numerator(), quantity.denominator()), (-1, 2));
+    }
+
+    #[test]
+    fn quantities_are_added() {
+        let sum = Quantity::new(1, 2).unwrap().checked_add(&Quantity::new(1, 3).unwrap()).unwrap();
+        assert_eq!(sum, Quantity::new(5, 6).unwrap());
+    }
+
+    #[test]
+    fn quantities_are_multiplied() {
+        let product = Quantity::new(3, 4).unwrap().checked_mul(&Quantity::new(2, 3).unwrap()).unwrap();
+        assert_eq!(product, Quantity::new(1, 2).unwrap());
+    }
+
+    #[test]
+    fn quantities_are_subtracted() {
+        let difference = Quantity::new(3, 4).unwrap().checked_sub(&Quantity::new(1, 4).unwrap()).unwrap();
+        assert_eq!(difference, Quantity::new(1, 2).unwrap());
+        assert_eq!(Quantity::whole(1).checked_sub(&Quantity::whole(2)), Ok(Quantity::whole(-1)));
+    }
+
+    #[test]
+    fn quantities_are_ordered_by_value() {
+        assert!(Quantity::new(1, 3).unwrap() < Quantity::new(1, 2).unwrap());
+        assert!(Quantity::new(-1, 2).unwrap() < Quantity::zero());
+        assert_eq!(Quantity::new(2, 3).unwrap().min(Quantity::new(3, 4).unwrap()), Quantity::new(2, 3).unwrap());
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let big = Quantity::whole(i32::MAX);
+        assert_eq!(big.checked_add(&big), Err(QuantityError::Overflow));
+    }
+
+    #[test]
+    fn approximates_to_the_closest_allowed_fraction() {
+        assert_eq!(Quantity::approximate(0.507, &[1, 2, 3, 4, 8]), Ok(Quantity::new(1, 2).unwrap()));
+        assert_eq!(Quantity::approximate(0.33, &[1, 2, 3, 4, 8]), Ok(Quantity::new(1, 3).unwrap()));
+        assert_eq!(Quantity::approximate(249.48, &[1]), Ok(Quantity::whole(249)));
+        assert_err!(Quantity::approximate(f64::NAN, &[1]));
+        assert_err!(Quantity::approximate(1.0, &[]));
+    }
+
+    #[test]
+    fn displays_as_mixed_number() {
+        assert_eq!(Quantity::new(3, 2).unwrap().to_string(), "1 1/2");
+        assert_eq!(Quantity::new(3, 6).unwrap().to_string(), "1/2");
+        assert_eq!(Quantity::new(4, 2).unwrap().to_string(), "2");
+        assert_eq!(Quantity::new(-3, 2).unwrap().to_string(), "-1 1/2");
+        assert_eq!(Quantity::zero().to_string(), "0");
+    }
+
+    #[test]
+    fn parses_common_formats() {
+        assert_eq!("2".parse::<Quantity>(), Ok(Quantity::whole(2)));
+        assert_eq!("1/2".parse::<Quantity>(), Ok(Quantity::new(1, 2).unwrap()));
+        assert_eq!("1 1/2".parse::<Quantity>(), Ok(Quantity::new(3, 2).unwrap()));
+        assert_eq!("0.25".parse::<Quantity>(), Ok(Quantity::new(1, 4).unwrap()));
+        assert_eq!("1.5".parse::<Quantity>(), Ok(Quantity::new(3, 2).unwrap()));
+    }
+
+    #[test]
+    fn invalid_strings_are_rejected() {
+        assert_err!("".parse::<Quantity>());
+        assert_err!("cup".parse::<Quantity>());
+        assert_err!("1/0".parse::<Quantity>());
+        assert_err!("1 2".parse::<Quantity>());
+        assert_err!("1 1/2 3".parse::<Quantity>());
+    }
+
+    #[test]
+    fn zero_denominator_is_rejected_when_deserializing() {
+        assert_err!(serde_json::from_str::<Quantity>(r#"{"numerator": 1, "denominator": 0}"#));
+        assert_ok!(serde_json::from_str::<Quantity>(r#"{"numerator": 3, "denominator": 6}"#));
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn display_round_trips_through_parse(numerator: i16, denominator: i16) -> bool {
+        match Quantity::new(numerator as i32, denominator as i32) {
+            Ok(quantity) => quantity.to_string().parse::<Quantity>() == Ok(quantity),
+            Err(_) => denominator == 0,
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn addition_is_commutative(a: (i16, i16), b: (i16, i16)) -> bool {
+        match (Quantity::new(a.0 as i32, a.1 as i32), Quantity::new(b.0 as i32, b.1 as i32)) {
+            (Ok(a), Ok(b)) => a.checked_add(&b) == b.checked_add(&a),
+            _ => true,
+        }
+    }
+}
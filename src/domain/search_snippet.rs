@@ -0,0 +1,76 @@
+use serde::{Serialize, Deserialize};
+
+/// Marks where Postgres' `ts_headline` starts a match. Control characters can't come from a
+/// form, so they can't be confused with the recipe's own text the way HTML tags could.
+pub const MATCH_START: char = '\u{2}';
+/// Marks where a match ends
+pub const MATCH_END: char = '\u{3}';
+
+/// A passage of a recipe that matched a search, split into the words that matched and the
+/// text around them, so that pages can highlight the matches without trusting the text
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SearchSnippet(pub Vec<SnippetPart>);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub matched: bool,
+}
+
+impl SearchSnippet {
+    /// The options for `ts_headline` that produce the headlines `from_headline` reads
+    pub fn headline_options() -> String {
+        format!(
+            "StartSel={}, StopSel={}, MaxFragments=2, MaxWords=20, MinWords=8, FragmentDelimiter=\" … \"",
+            MATCH_START, MATCH_END,
+        )
+    }
+
+    pub fn from_headline(headline: &str) -> Self {
+        let mut parts: Vec<SnippetPart> = Vec::new();
+        let mut text = String::new();
+        let mut matched = false;
+        for c in headline.chars() {
+            match c {
+                MATCH_START | MATCH_END => {
+                    if !text.is_empty() {
+                        parts.push(SnippetPart { text: std::mem::take(&mut text), matched });
+                    }
+                    matched = c == MATCH_START;
+                },
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(SnippetPart { text, matched });
+        }
+
+        Self(parts)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SearchSnippet, SnippetPart};
+
+    fn part(text: &str, matched: bool) -> SnippetPart {
+        SnippetPart { text: text.to_string(), matched }
+    }
+
+    #[test]
+    fn matches_are_split_from_the_text_around_them() {
+        let snippet = SearchSnippet::from_headline("Brown the \u{2}chicken\u{3} with <b>\u{2}garlic\u{3}");
+        assert_eq!(snippet.0, [part("Brown the ", false), part("chicken", true), part(" with <b>", false), part("garlic", true)]);
+    }
+
+    #[test]
+    fn headlines_without_matches_are_plain_text() {
+        assert_eq!(SearchSnippet::from_headline("Plain rice").0, [part("Plain rice", false)]);
+        assert!(SearchSnippet::from_headline("").is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use reqwest::Url;
+
+/// Query parameters that only track where a visitor came from
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "mc_cid", "mc_eid", "ref", "ref_src"];
+
+/// Reduces a recipe's source URL to the parts that identify the page, so the same page
+/// clipped from different links compares equal. The scheme, "www.", the fragment, trailing
+/// slashes and tracking parameters are dropped and the remaining parameters sorted.
+pub fn normalize_source_url(source_url: &str) -> String {
+    let source_url = source_url.trim();
+    let Ok(url) = Url::parse(source_url) else {
+        return source_url.to_lowercase();
+    };
+
+    let host = url.host_str().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let mut normalized = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    normalized.push_str(url.path().trim_end_matches('/'));
+
+    let mut params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if !params.is_empty() {
+        params.sort();
+        let query = params
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<String>>()
+            .join("&");
+        normalized.push('?');
+        normalized.push_str(&query);
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_source_url;
+
+    #[test]
+    fn links_to_the_same_page_are_equal() {
+        let expected = "example.com/recipes/stew";
+        assert_eq!(normalize_source_url("https://example.com/recipes/stew"), expected);
+        assert_eq!(normalize_source_url("http://www.Example.com/recipes/stew/"), expected);
+        assert_eq!(normalize_source_url("https://example.com/recipes/stew#comments"), expected);
+        assert_eq!(normalize_source_url(" https://example.com/recipes/stew?utm_source=pinterest&fbclid=abc "), expected);
+    }
+
+    #[test]
+    fn meaningful_parameters_are_kept_in_order() {
+        assert_eq!(normalize_source_url("https://example.com/r?p=2&id=7"), "example.com/r?id=7&p=2");
+        assert_ne!(normalize_source_url("https://example.com/r?id=7"), normalize_source_url("https://example.com/r?id=8"));
+    }
+
+    #[test]
+    fn ports_and_paths_still_differ() {
+        assert_ne!(normalize_source_url("http://localhost:8000/stew"), normalize_source_url("http://localhost:9000/stew"));
+        assert_ne!(normalize_source_url("https://example.com/stew"), normalize_source_url("https://example.com/soup"));
+    }
+
+    #[test]
+    fn text_that_is_not_a_url_is_only_trimmed_and_lowercased() {
+        assert_eq!(normalize_source_url(" Grandma's Cookbook "), "grandma's cookbook");
+    }
+}
@@ -0,0 +1,433 @@
+//! Our own export format, for backups and for moving recipes to another account or
+//! instance without losing anything. An archive is a zip holding:
+//!
+//! - `manifest.json`: `{"format": "chopping-list", "version": 1, "exported_at": <unix seconds>,
+//!   "recipes": [<paths of the recipe files>]}`
+//! - `units.json`: every unit the recipes use, as `{"name", "abbreviation", "system",
+//!   "dimension", "base_factor"}`, so that they can be matched to the importing instance's units
+//! - `recipes/*.json`: one `FullRecipeDetails` per file, with the recipe's `"tags"` as names
+//!   and its `"images"` as paths of files under `images/`
+//!
+//! Ids in recipe files are the exporting instance's and are ignored on import. Fields may be
+//! added to version 1 as long as they have defaults. Removing a field or changing what one
+//! means bumps the version, and archives newer than `ARCHIVE_VERSION` are refused.
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use zip::write::{SimpleFileOptions, ZipWriter};
+use crate::models::recipe::{DuplicatePolicy, FullRecipeDetails, ImportOutcome, Recipe, SaveRecipeParams};
+use crate::models::tag::Tag;
+use crate::models::unit::{Unit, UnitDimension, UnitSystem};
+use super::library::{is_zip, read_limited, MAX_ARCHIVE_FILES, MAX_FILE_BYTES, MAX_UNPACKED_BYTES};
+
+pub const ARCHIVE_FORMAT: &str = "chopping-list";
+pub const ARCHIVE_VERSION: u32 = 1;
+/// What downloaded archives are saved as
+pub const ARCHIVE_FILE_NAME: &str = "chopping-list-recipes.zip";
+
+const MANIFEST_PATH: &str = "manifest.json";
+const UNITS_PATH: &str = "units.json";
+const RECIPES_DIR: &str = "recipes";
+/// Recipe file names keep this much of the recipe's name, to be recognisable when unzipped
+const MAX_SLUG_LENGTH: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: i64,
+    pub recipes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveRecipe {
+    #[serde(flatten)]
+    pub recipe: FullRecipeDetails,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Recipes don't have images yet, so this is always empty for now
+    #[serde(default)]
+    pub images: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveUnit {
+    pub name: String,
+    pub abbreviation: Option<String>,
+    pub system: UnitSystem,
+    pub dimension: UnitDimension,
+    pub base_factor: Option<f64>,
+}
+
+/// One recipe file of an archive being read. Files that can't be read keep the reason, so
+/// the rest of the archive can still be imported.
+#[derive(Debug)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub recipe: Result<ArchiveRecipe, String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("The archive has no {0}")]
+    Missing(String),
+
+    #[error("The archive is {0}, not a Chopping List export")]
+    WrongFormat(String),
+
+    #[error("The archive is version {0}, which is newer than this server reads ({ARCHIVE_VERSION})")]
+    UnsupportedVersion(u32),
+
+    #[error("{}", .0.join(", "))]
+    Invalid(Vec<String>),
+
+    #[error("{path} can't be read: {source}")]
+    Json {
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[error("The archive holds {0} files, more than the {MAX_ARCHIVE_FILES} it may")]
+    TooManyFiles(usize),
+
+    #[error("The archive unpacks to more than {} MB", MAX_UNPACKED_BYTES / 1024 / 1024)]
+    TooBig,
+
+    #[error("The archive can't be read: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("The archive can't be read: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Model(#[from] crate::models::Error),
+}
+
+/// Archives are zips with a manifest, which sets them apart from other apps' exports
+pub fn is_archive(bytes: &[u8]) -> bool {
+    is_zip(bytes)
+        && zip::ZipArchive::new(Cursor::new(bytes)).is_ok_and(|mut archive| archive.by_name(MANIFEST_PATH).is_ok())
+}
+
+pub fn write_archive(units: &[ArchiveUnit], recipes: &[ArchiveRecipe], exported_at: i64) -> Result<Vec<u8>, ArchiveError> {
+    let paths: Vec<String> = recipes
+        .iter()
+        .enumerate()
+        .map(|(idx, recipe)| format!("{}/{:04}-{}.json", RECIPES_DIR, idx + 1, slug(&recipe.recipe.name)))
+        .collect();
+    let manifest = ArchiveManifest {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        exported_at,
+        recipes: paths.clone(),
+    };
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    write_json(&mut zip, MANIFEST_PATH, &manifest)?;
+    write_json(&mut zip, UNITS_PATH, units)?;
+    for (path, recipe) in paths.iter().zip(recipes) {
+        write_json(&mut zip, path, recipe)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Reads the units and every recipe listed in the manifest
+pub fn read_archive(bytes: &[u8]) -> Result<(Vec<ArchiveUnit>, Vec<ArchiveEntry>), ArchiveError> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes))?;
+    if zip.len() > MAX_ARCHIVE_FILES {
+        return Err(ArchiveError::TooManyFiles(zip.len()));
+    }
+    let mut unpacked_bytes = 0;
+    let manifest: ArchiveManifest = read_json(&mut zip, MANIFEST_PATH, &mut unpacked_bytes)?;
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(ArchiveError::WrongFormat(manifest.format));
+    }
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(ArchiveError::UnsupportedVersion(manifest.version));
+    }
+    // The manifest may list a file more than once, so it is held to the same count
+    if manifest.recipes.len() > MAX_ARCHIVE_FILES {
+        return Err(ArchiveError::TooManyFiles(manifest.recipes.len()));
+    }
+    let units: Vec<ArchiveUnit> = read_json(&mut zip, UNITS_PATH, &mut unpacked_bytes)?;
+
+    let mut entries = Vec::new();
+    for path in manifest.recipes {
+        let recipe = match read_json(&mut zip, &path, &mut unpacked_bytes) {
+            Err(ArchiveError::TooBig) => return Err(ArchiveError::TooBig),
+            recipe => recipe.map_err(|err| err.to_string()),
+        };
+        entries.push(ArchiveEntry { path, recipe });
+    }
+
+    Ok((units, entries))
+}
+
+fn write_json<T: Serialize + ?Sized>(zip: &mut ZipWriter<Cursor<Vec<u8>>>, path: &str, value: &T) -> Result<(), ArchiveError> {
+    let json = serde_json::to_vec_pretty(value).map_err(|source| ArchiveError::Json { path: path.to_string(), source })?;
+    zip.start_file(path, SimpleFileOptions::default())?;
+    zip.write_all(&json)?;
+
+    Ok(())
+}
+
+/// Reads one file, adding its size to what the archive has unpacked so far
+fn read_json<T: serde::de::DeserializeOwned>(
+    zip: &mut zip::ZipArchive<Cursor<&[u8]>>,
+    path: &str,
+    unpacked_bytes: &mut u64,
+) -> Result<T, ArchiveError> {
+    let file = match zip.by_name(path) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Err(ArchiveError::Missing(path.to_string())),
+        Err(err) => return Err(err.into()),
+    };
+    let json = read_limited(file, MAX_FILE_BYTES)
+        .map_err(|err| std::io::Error::new(err.kind(), format!("{} {}", path, err)))?;
+    *unpacked_bytes += json.len() as u64;
+    if *unpacked_bytes > MAX_UNPACKED_BYTES {
+        return Err(ArchiveError::TooBig);
+    }
+
+    serde_json::from_slice(&json).map_err(|source| ArchiveError::Json { path: path.to_string(), source })
+}
+
+/// "Kimchi Stew (Kimchi Jjigae)" becomes "kimchi-stew-kimchi-jjigae"
+fn slug(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let slug: String = words.join("-").chars().take(MAX_SLUG_LENGTH).collect();
+
+    match slug.trim_end_matches('-') {
+        "" => "recipe".to_string(),
+        slug => slug.to_string(),
+    }
+}
+
+impl From<&Unit> for ArchiveUnit {
+    fn from(unit: &Unit) -> Self {
+        Self {
+            name: unit.name.clone(),
+            abbreviation: unit.abbreviation.clone(),
+            system: unit.system.clone(),
+            dimension: unit.dimension.clone(),
+            base_factor: unit.base_factor,
+        }
+    }
+}
+
+/// Every recipe of the user with its tags, and the units they use
+pub async fn export_archive(db: &PgPool, user_id: &uuid::Uuid, exported_at: i64) -> Result<Vec<u8>, ArchiveError> {
+    let mut recipes = Vec::new();
+    for recipe in Recipe::list_for_user(db, user_id).await? {
+        let tags = recipe.get_tags(db).await?.into_iter().map(|tag| tag.name).collect();
+        let details = Recipe::get_full_recipe_details(db, user_id, recipe.id).await?;
+        recipes.push(ArchiveRecipe { recipe: details, tags, images: Vec::new() });
+    }
+
+    let units: Vec<ArchiveUnit> = Unit::list(db)
+        .await?
+        .iter()
+        .filter(|unit| {
+            recipes.iter().any(|recipe| {
+                recipe.recipe.recipe_components.iter().any(|component| {
+                    component.component_ingredients.iter().any(|ingredient| ingredient.unit == unit.name)
+                })
+            })
+        })
+        .map(ArchiveUnit::from)
+        .collect();
+
+    write_archive(&units, &recipes, exported_at)
+}
+
+/// Matches the archive's units to this instance's, by name or else by an abbreviation for
+/// the same dimension. Units are shared by every user, so ones this instance doesn't have
+/// are never created; their names are returned instead.
+pub async fn match_units(db: &PgPool, units: &[ArchiveUnit]) -> Result<(HashMap<String, String>, Vec<String>), ArchiveError> {
+    let known = Unit::list(db).await?;
+    let mut matched = HashMap::new();
+    let mut unknown = Vec::new();
+    for unit in units {
+        let local = known.iter().find(|known| known.name == unit.name).or_else(|| {
+            known.iter().find(|known| {
+                known.dimension == unit.dimension
+                    && unit.abbreviation.is_some()
+                    && known.abbreviation == unit.abbreviation
+            })
+        });
+        match local {
+            Some(local) => { matched.insert(unit.name.clone(), local.name.clone()); },
+            None => unknown.push(unit.name.clone()),
+        }
+    }
+
+    Ok((matched, unknown))
+}
+
+/// Saves an archived recipe for the user, with its units renamed to the ones `units` matched
+/// them to. A recipe the user already has from the same source URL, or for recipes without
+/// one the same name and content, is handled according to `on_duplicate`, as for other
+/// imports. The recipe and its tags are written together or not at all.
+pub async fn restore_recipe(
+    db: &PgPool,
+    user_id: &uuid::Uuid,
+    archived: &ArchiveRecipe,
+    units: &HashMap<String, String>,
+    on_duplicate: DuplicatePolicy,
+) -> Result<(Recipe, ImportOutcome), ArchiveError> {
+    let mut params = SaveRecipeParams::from(&archived.recipe);
+    for ingredient in params.components.iter_mut().flat_map(|component| component.ingredients.iter_mut()) {
+        if let Some(unit) = units.get(&ingredient.unit) {
+            ingredient.unit = unit.clone();
+        }
+    }
+    params.validate(&Unit::list(db).await?).map_err(ArchiveError::Invalid)?;
+
+    match save_restored(db, user_id, &params, &archived.tags, on_duplicate).await {
+        // Another import of the same page saved it in the meantime, so look again
+        Err(ArchiveError::Model(err)) if err.is_duplicate_source_url() => save_restored(db, user_id, &params, &archived.tags, on_duplicate).await,
+        saved => saved,
+    }
+}
+
+async fn save_restored(
+    db: &PgPool,
+    user_id: &uuid::Uuid,
+    params: &SaveRecipeParams,
+    tag_names: &[String],
+    on_duplicate: DuplicatePolicy,
+) -> Result<(Recipe, ImportOutcome), ArchiveError> {
+    let existing = match params.source_url.as_deref().filter(|url| !url.trim().is_empty()) {
+        Some(source_url) => Recipe::find_by_source_url(db, user_id, source_url).await?,
+        None => Recipe::find_same_without_source_url(db, user_id, params).await?,
+    };
+    let mut transaction = db.begin().await.map_err(crate::models::Error::from)?;
+    let (recipe, outcome) = match (existing, on_duplicate) {
+        (None, _) => (Recipe::insert_full(&mut transaction, user_id, params).await?, ImportOutcome::Created),
+        (Some(recipe), DuplicatePolicy::Skip) => return Ok((recipe, ImportOutcome::Skipped)),
+        (Some(recipe), DuplicatePolicy::Overwrite) => {
+            let recipe = Recipe::replace_full(&mut transaction, user_id, recipe.id, params).await?
+                .ok_or(crate::models::Error::NotFound)?;
+            (recipe, ImportOutcome::Overwritten)
+        },
+        (Some(_), DuplicatePolicy::Copy) => (Recipe::insert_full(&mut transaction, user_id, params).await?, ImportOutcome::Copied),
+    };
+
+    let mut tags: Vec<String> = Tag::find_by_recipe_id(&mut *transaction, recipe.id).await?
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect();
+    for tag in tag_names {
+        if !tags.contains(tag) {
+            recipe.tag_with(&mut transaction, tag).await?;
+            tags.push(tag.clone());
+        }
+    }
+    transaction.commit().await.map_err(crate::models::Error::from)?;
+
+    Ok((recipe, outcome))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::recipe::FullRecipeDetails;
+    use crate::models::unit::{UnitDimension, UnitSystem};
+    use crate::formats::library::MAX_ARCHIVE_FILES;
+    use super::{is_archive, read_archive, slug, write_archive, ArchiveError, ArchiveRecipe, ArchiveUnit};
+
+    fn stew() -> ArchiveRecipe {
+        let recipe: FullRecipeDetails = serde_json::from_value(serde_json::json!({
+            "recipe_id": 7,
+            "name": "Stew",
+            "description": "",
+            "is_public": true,
+            "prep_time": 10,
+            "cook_time": null,
+            "rest_time": null,
+            "servings": 4,
+            "source_url": null,
+            "recipe_components": [{
+                "name": "Main",
+                "is_optional": false,
+                "component_ingredients": [{
+                    "ingredient_id": 3,
+                    "name": "Potato",
+                    "description": "",
+                    "unit": "cup",
+                    "quantity": { "numerator": 3, "denominator": 2 },
+                    "is_optional": false,
+                }],
+            }],
+            "recipe_instructions": [{
+                "order_idx": 1,
+                "title": "Cook",
+                "instruction_steps": [{ "step_number": 1, "content": "Boil" }],
+            }],
+        })).unwrap();
+
+        ArchiveRecipe { recipe, tags: vec!["Winter".to_string()], images: Vec::new() }
+    }
+
+    #[test]
+    fn archives_read_back_what_was_written() {
+        let units = vec![ArchiveUnit {
+            name: "cup".to_string(),
+            abbreviation: Some("cup".to_string()),
+            system: UnitSystem::Imperial,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(236.588),
+        }];
+        let bytes = write_archive(&units, &[stew()], 1_700_000_000).unwrap();
+        assert!(is_archive(&bytes));
+
+        let (read_units, entries) = read_archive(&bytes).unwrap();
+        assert_eq!(read_units, units);
+        assert_eq!(entries[0].path, "recipes/0001-stew.json");
+        let recipe = entries[0].recipe.as_ref().unwrap();
+        assert_eq!(serde_json::to_value(recipe).unwrap(), serde_json::to_value(stew()).unwrap());
+    }
+
+    #[test]
+    fn newer_archives_are_refused() {
+        let bytes = write_archive(&[], &[], 0).unwrap();
+        let manifest = serde_json::json!({ "format": "chopping-list", "version": 2, "exported_at": 0, "recipes": [] });
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("manifest.json", zip::write::SimpleFileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut zip, manifest.to_string().as_bytes()).unwrap();
+        let newer = zip.finish().unwrap().into_inner();
+
+        assert!(read_archive(&bytes).is_ok());
+        assert!(matches!(read_archive(&newer), Err(ArchiveError::UnsupportedVersion(2))));
+    }
+
+    #[test]
+    fn manifests_listing_too_many_recipes_are_refused() {
+        let path = "recipes/0001-stew.json";
+        let manifest = serde_json::json!({
+            "format": "chopping-list",
+            "version": 1,
+            "exported_at": 0,
+            "recipes": vec![path; MAX_ARCHIVE_FILES + 1],
+        });
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, json) in [("manifest.json", manifest.to_string()), ("units.json", "[]".to_string()), (path, serde_json::to_string(&stew()).unwrap())] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            std::io::Write::write_all(&mut zip, json.as_bytes()).unwrap();
+        }
+        let bytes = zip.finish().unwrap().into_inner();
+
+        assert!(matches!(read_archive(&bytes), Err(ArchiveError::TooManyFiles(count)) if count == MAX_ARCHIVE_FILES + 1));
+    }
+
+    #[test]
+    fn recipe_files_are_named_after_the_recipe() {
+        assert_eq!(slug("Kimchi Stew (Kimchi Jjigae)"), "kimchi-stew-kimchi-jjigae");
+        assert_eq!(slug("!!!"), "recipe");
+    }
+}
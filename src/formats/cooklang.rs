@@ -0,0 +1,578 @@
+//! Reads and writes Cooklang (https://cooklang.org), where a recipe is plain text with its
+//! `@ingredients{1%cup}`, `#cookware{}` and `~timers{10%minutes}` marked up inside the steps.
+//!
+//! Steps are paragraphs and `== Name ==` starts a section. Our recipes keep their ingredients
+//! apart from the steps, so a step made of nothing but ingredients is read as an ingredient
+//! list. Written recipes put each component's ingredients in such a step under a section of
+//! their own, followed by a section per instruction.
+use regex::Regex;
+use crate::domain::{parse_quantity, Quantity, UnitMatcher};
+use super::{leading_number, minutes};
+use crate::models::recipe::{
+    FullRecipeDetails,
+    SaveRecipeComponentParams,
+    SaveRecipeIngredientParams,
+    SaveRecipeInstructionParams,
+    SaveRecipeParams,
+};
+
+/// Name for the ingredients of a recipe that has no sections
+const DEFAULT_COMPONENT_NAME: &str = "Ingredients";
+/// Ingredients with an amount but no unit
+const DEFAULT_UNIT: &str = "piece";
+/// Ingredients without an amount, like `@salt`
+const UNMEASURED_UNIT: &str = "to_taste";
+/// Marks sections holding an optional component
+const OPTIONAL_SUFFIX: &str = " (optional)";
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum CooklangError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/// Reads a Cooklang recipe. The name comes from the `title` metadata and is left empty
+/// without it, for the caller to fill in from the file name. Units are matched against
+/// `units` and kept as written when they are unknown, for validation to report.
+pub fn parse_cooklang(source: &str, units: &UnitMatcher) -> Result<SaveRecipeParams, CooklangError> {
+    let mut recipe = SaveRecipeParams {
+        name: String::new(),
+        description: String::new(),
+        is_public: false,
+        prep_time: None,
+        cook_time: None,
+        rest_time: None,
+        servings: None,
+        source_url: None,
+        components: Vec::new(),
+        instructions: Vec::new(),
+    };
+    let mut section = Section::default();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut paragraph_line = 0;
+
+    let source = strip_block_comments(source);
+    let mut lines = source.lines().enumerate().peekable();
+    // Newer files keep their metadata in YAML front matter
+    if lines.peek().is_some_and(|(_, line)| line.trim() == "---") {
+        lines.next();
+        for (_, line) in lines.by_ref() {
+            if line.trim() == "---" {
+                break;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                apply_metadata(&mut recipe, key, value);
+            }
+        }
+    }
+
+    for (idx, line) in lines {
+        // Metadata is read as written, so that descriptions and URLs can hold "--"
+        if let Some(metadata) = line.trim().strip_prefix(">>") {
+            if let Some((key, value)) = metadata.split_once(':') {
+                apply_metadata(&mut recipe, key, value);
+            }
+            continue;
+        }
+        let line = strip_line_comment(line);
+        let trimmed = line.trim();
+        if trimmed.starts_with('=') {
+            add_step(&mut recipe, &mut section, &paragraph, paragraph_line, units)?;
+            paragraph.clear();
+            section = Section::named(trimmed.trim_matches('=').trim());
+        } else if trimmed.is_empty() {
+            add_step(&mut recipe, &mut section, &paragraph, paragraph_line, units)?;
+            paragraph.clear();
+        } else {
+            if paragraph.is_empty() {
+                paragraph_line = idx + 1;
+            }
+            paragraph.push(trimmed);
+        }
+    }
+    add_step(&mut recipe, &mut section, &paragraph, paragraph_line, units)?;
+
+    Ok(recipe)
+}
+
+/// Writes the recipe as Cooklang that `parse_cooklang` reads back into the same recipe
+pub fn to_cooklang(recipe: &FullRecipeDetails) -> String {
+    let mut lines = vec![format!(">> title: {}", one_line(&recipe.name))];
+    if !recipe.description.trim().is_empty() {
+        lines.push(format!(">> description: {}", one_line(&recipe.description)));
+    }
+    if let Some(servings) = recipe.servings {
+        lines.push(format!(">> servings: {}", servings));
+    }
+    for (label, minutes) in [("prep time", recipe.prep_time), ("cook time", recipe.cook_time), ("rest time", recipe.rest_time)] {
+        if let Some(minutes) = minutes {
+            lines.push(format!(">> {}: {} minutes", label, minutes));
+        }
+    }
+    if let Some(source_url) = recipe.source_url.as_deref().filter(|url| !url.trim().is_empty()) {
+        lines.push(format!(">> source: {}", one_line(source_url)));
+    }
+
+    for component in &recipe.recipe_components {
+        let suffix = if component.is_optional { OPTIONAL_SUFFIX } else { "" };
+        lines.push(String::new());
+        lines.push(format!("== {}{} ==", one_line(&component.name), suffix));
+        lines.push(String::new());
+        for ingredient in &component.component_ingredients {
+            let marker = if ingredient.is_optional { "@?" } else { "@" };
+            let amount = match (ingredient.unit.as_str(), ingredient.quantity) {
+                (UNMEASURED_UNIT, quantity) if quantity == Quantity::whole(1) => String::new(),
+                (DEFAULT_UNIT, quantity) => format_quantity(&quantity),
+                (unit, quantity) => format!("{}%{}", format_quantity(&quantity), unit),
+            };
+            let note = match ingredient.description.trim() {
+                "" => String::new(),
+                description => format!("({})", one_line(description)),
+            };
+            lines.push(format!("{}{}{{{}}}{}", marker, one_line(&ingredient.name), amount, note));
+        }
+    }
+
+    for instruction in &recipe.recipe_instructions {
+        lines.push(String::new());
+        lines.push(format!("== {} ==", one_line(&instruction.title)));
+        for step in &instruction.instruction_steps {
+            lines.push(String::new());
+            lines.push(escape(&one_line(&step.content)));
+        }
+    }
+    lines.push(String::new());
+
+    lines.join("\n")
+}
+
+/// Where steps go until the next `==` line. Components and instructions are only added
+/// once the section has ingredients or steps for them.
+#[derive(Default)]
+struct Section {
+    name: Option<String>,
+    component_idx: Option<usize>,
+    instruction_idx: Option<usize>,
+}
+
+impl Section {
+    fn named(name: &str) -> Self {
+        Self { name: Some(name.to_string()), ..Self::default() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Text(String),
+    Ingredient { name: String, amount: Option<String>, unit: Option<String>, note: Option<String>, is_optional: bool },
+    Cookware(String),
+    Timer { name: String, amount: Option<String>, unit: Option<String> },
+}
+
+fn add_step(
+    recipe: &mut SaveRecipeParams,
+    section: &mut Section,
+    paragraph: &[&str],
+    line: usize,
+    units: &UnitMatcher,
+) -> Result<(), CooklangError> {
+    if paragraph.is_empty() {
+        return Ok(());
+    }
+    let tokens = tokenize(&paragraph.join("\n"), line)?;
+
+    let mut ingredients = Vec::new();
+    for token in &tokens {
+        if let Token::Ingredient { name, amount, unit, note, is_optional } = token {
+            ingredients.push(ingredient(name, amount.as_deref(), unit.as_deref(), note.as_deref(), *is_optional, line, units)?);
+        }
+    }
+    if !ingredients.is_empty() {
+        let component_idx = *section.component_idx.get_or_insert_with(|| {
+            let name = section.name.as_deref().filter(|name| !name.is_empty()).unwrap_or(DEFAULT_COMPONENT_NAME);
+            let (name, is_optional) = match name.strip_suffix(OPTIONAL_SUFFIX) {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+            recipe.components.push(SaveRecipeComponentParams { name: name.to_string(), is_optional, ingredients: Vec::new() });
+            recipe.components.len() - 1
+        });
+        recipe.components[component_idx].ingredients.extend(ingredients);
+    }
+
+    if is_ingredient_list(&tokens) {
+        return Ok(());
+    }
+    let instruction_idx = *section.instruction_idx.get_or_insert_with(|| {
+        let title = section.name.clone().unwrap_or_default();
+        recipe.instructions.push(SaveRecipeInstructionParams { title, steps: Vec::new() });
+        recipe.instructions.len() - 1
+    });
+    recipe.instructions[instruction_idx].steps.push(render(&tokens));
+
+    Ok(())
+}
+
+/// A step with ingredients and nothing else to do
+fn is_ingredient_list(tokens: &[Token]) -> bool {
+    let has_ingredients = tokens.iter().any(|token| matches!(token, Token::Ingredient { .. }));
+    let only_ingredients = tokens.iter().all(|token| match token {
+        Token::Ingredient { .. } => true,
+        Token::Text(text) => text.chars().all(|c| c.is_whitespace() || c == ',' || c == ';'),
+        Token::Cookware(_) | Token::Timer { .. } => false,
+    });
+
+    has_ingredients && only_ingredients
+}
+
+/// The step as it reads without the markup
+fn render(tokens: &[Token]) -> String {
+    let text: String = tokens
+        .iter()
+        .map(|token| match token {
+            Token::Text(text) => text.clone(),
+            Token::Ingredient { name, .. } | Token::Cookware(name) => name.clone(),
+            Token::Timer { name, amount, unit } => {
+                let duration = [amount.as_deref(), unit.as_deref()].into_iter().flatten().collect::<Vec<&str>>().join(" ");
+                if duration.is_empty() { name.clone() } else { duration }
+            },
+        })
+        .collect();
+
+    one_line(&text)
+}
+
+fn ingredient(
+    name: &str,
+    amount: Option<&str>,
+    unit: Option<&str>,
+    note: Option<&str>,
+    is_optional: bool,
+    line: usize,
+    units: &UnitMatcher,
+) -> Result<SaveRecipeIngredientParams, CooklangError> {
+    // A trailing * stops an amount from scaling, which doesn't apply here
+    let amount = amount.map(|amount| amount.trim_end_matches('*').trim()).filter(|amount| !amount.is_empty());
+    let quantity = match amount {
+        Some(amount) => parse_quantity(amount).map_err(|err| CooklangError::Syntax {
+            line,
+            message: format!("{} has an amount that isn't a number: {}", name, err),
+        })?,
+        None => Quantity::whole(1),
+    };
+    let unit = match (amount, unit) {
+        (_, Some(unit)) => units.find(unit).unwrap_or(unit).to_string(),
+        (Some(_), None) => DEFAULT_UNIT.to_string(),
+        (None, None) => UNMEASURED_UNIT.to_string(),
+    };
+
+    Ok(SaveRecipeIngredientParams {
+        name: name.to_string(),
+        description: note.unwrap_or_default().to_string(),
+        unit,
+        quantity,
+        is_optional,
+    })
+}
+
+fn tokenize(step: &str, line: usize) -> Result<Vec<Token>, CooklangError> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = step;
+
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        match c {
+            '\\' => {
+                let escaped = after.chars().next().unwrap_or('\\');
+                text.push(escaped);
+                rest = after.get(escaped.len_utf8()..).unwrap_or_default();
+            },
+            '@' | '#' | '~' => match read_component(c, after, line)? {
+                Some((token, remaining)) => {
+                    if !text.is_empty() {
+                        tokens.push(Token::Text(std::mem::take(&mut text)));
+                    }
+                    tokens.push(token);
+                    rest = remaining;
+                },
+                None => {
+                    text.push(c);
+                    rest = after;
+                },
+            },
+            c => {
+                text.push(c);
+                rest = after;
+            },
+        }
+    }
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+
+    Ok(tokens)
+}
+
+/// Reads what follows a `@`, `#` or `~`. Names run up to the `{` when there is one before the
+/// next marker and are a single word otherwise. Answers None when the marker is plain text.
+fn read_component(marker: char, text: &str, line: usize) -> Result<Option<(Token, &str)>, CooklangError> {
+    let (is_optional, text) = match text.strip_prefix('?') {
+        Some(text) if marker == '@' => (true, text),
+        _ => (false, text),
+    };
+
+    let multiword_name = text
+        .find('{')
+        .map(|brace| &text[..brace])
+        .filter(|name| !name.contains(['@', '#', '~', '}', '\n']));
+    let (name, rest) = match multiword_name {
+        Some(name) => (name.trim(), &text[name.len()..]),
+        None => {
+            let end = text.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-')).unwrap_or(text.len());
+            (&text[..end], &text[end..])
+        },
+    };
+    if name.is_empty() && !(marker == '~' && rest.starts_with('{')) {
+        return Ok(None);
+    }
+
+    let (amount, unit, rest) = match rest.strip_prefix('{') {
+        Some(rest) => {
+            let close = rest.find('}').ok_or_else(|| CooklangError::Syntax {
+                line,
+                message: format!("{}{} is missing its closing }}", marker, name),
+            })?;
+            let (amount, unit) = match rest[..close].split_once('%') {
+                Some((amount, unit)) => (amount.trim(), Some(unit.trim().to_string()).filter(|unit| !unit.is_empty())),
+                None => (rest[..close].trim(), None),
+            };
+            (Some(amount.to_string()).filter(|amount| !amount.is_empty()), unit, &rest[close + 1..])
+        },
+        None => (None, None, rest),
+    };
+
+    let token = match marker {
+        '@' => {
+            let (note, rest) = match rest.strip_prefix('(').and_then(|rest| rest.split_once(')')) {
+                Some((note, rest)) => (Some(note.trim().to_string()), rest),
+                None => (None, rest),
+            };
+            return Ok(Some((Token::Ingredient { name: name.to_string(), amount, unit, note, is_optional }, rest)));
+        },
+        '#' => Token::Cookware(name.to_string()),
+        _ => Token::Timer { name: name.to_string(), amount, unit },
+    };
+
+    Ok(Some((token, rest)))
+}
+
+fn apply_metadata(recipe: &mut SaveRecipeParams, key: &str, value: &str) {
+    let key = key.trim().to_lowercase().replace(['_', '-'], " ");
+    let value = value.trim().trim_matches('"');
+    match key.as_str() {
+        "title" | "name" => recipe.name = value.to_string(),
+        "description" | "introduction" => recipe.description = value.to_string(),
+        "servings" | "serves" | "yield" => recipe.servings = leading_number(value),
+        "prep time" | "preptime" => recipe.prep_time = minutes(value),
+        "cook time" | "cooktime" | "time required" | "time" | "duration" => recipe.cook_time = recipe.cook_time.or(minutes(value)),
+        "rest time" | "resttime" => recipe.rest_time = minutes(value),
+        "source" | "source url" | "source.url" | "url" => recipe.source_url = Some(value.to_string()).filter(|url| !url.is_empty()),
+        _ => {},
+    }
+}
+
+fn strip_block_comments(source: &str) -> String {
+    let re = Regex::new(r"(?s)\[-.*?-\]").unwrap();
+    // Keep the line breaks so errors point at the right line
+    re.replace_all(source, |captures: &regex::Captures| "\n".repeat(captures[0].matches('\n').count()))
+        .into_owned()
+}
+
+fn strip_line_comment(line: &str) -> &str {
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            '\\' => escaped = !escaped,
+            '-' if !escaped && line[idx + 1..].starts_with('-') => return &line[..idx],
+            _ => escaped = false,
+        }
+    }
+
+    line
+}
+
+/// Keeps step text from being read as markup
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut previous = None;
+    for c in text.chars() {
+        let needs_escape = matches!(c, '\\' | '@' | '#' | '~')
+            || (c == '-' && matches!(previous, Some('-') | Some('[')))
+            || (previous.is_none() && matches!(c, '=' | '>'));
+        if needs_escape {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        previous = Some(c);
+    }
+
+    escaped
+}
+
+fn format_quantity(quantity: &Quantity) -> String {
+    match quantity.denominator() {
+        1 => quantity.numerator().to_string(),
+        denominator => format!("{}/{}", quantity.numerator(), denominator),
+    }
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cooklang, to_cooklang, CooklangError};
+    use crate::domain::Quantity;
+    use crate::models::recipe::{
+        FullRecipeComponent,
+        FullRecipeComponentIngredient,
+        FullRecipeDetails,
+        FullRecipeInstruction,
+        FullRecipeInstructionStep,
+        SaveRecipeParams,
+    };
+    use crate::models::unit::default_unit_matcher;
+    use crate::seeds::seed_recipes;
+
+    /// The recipe as it would come back from the database
+    fn full_details(recipe: &SaveRecipeParams) -> FullRecipeDetails {
+        FullRecipeDetails {
+            recipe_id: 1,
+            name: recipe.name.clone(),
+            description: recipe.description.clone(),
+            is_public: recipe.is_public,
+            prep_time: recipe.prep_time,
+            cook_time: recipe.cook_time,
+            rest_time: recipe.rest_time,
+            servings: recipe.servings,
+            source_url: recipe.source_url.clone(),
+            recipe_components: recipe.components.iter().map(|component| FullRecipeComponent {
+                name: component.name.clone(),
+                is_optional: component.is_optional,
+                component_ingredients: component.ingredients.iter().enumerate().map(|(idx, ingredient)| FullRecipeComponentIngredient {
+                    ingredient_id: idx as i32 + 1,
+                    name: ingredient.name.clone(),
+                    description: ingredient.description.clone(),
+                    unit: ingredient.unit.clone(),
+                    quantity: ingredient.quantity,
+                    is_optional: ingredient.is_optional,
+                }).collect(),
+            }).collect(),
+            recipe_instructions: recipe.instructions.iter().enumerate().map(|(idx, instruction)| FullRecipeInstruction {
+                order_idx: idx as i32 + 1,
+                title: instruction.title.clone(),
+                instruction_steps: instruction.steps.iter().enumerate().map(|(idx, step)| FullRecipeInstructionStep {
+                    step_number: idx as i32 + 1,
+                    content: step.clone(),
+                }).collect(),
+            }).collect(),
+        }
+    }
+
+    #[test]
+    fn seeded_recipes_survive_a_round_trip() {
+        for seed in seed_recipes() {
+            let cooklang = to_cooklang(&full_details(&seed.recipe));
+            let parsed = parse_cooklang(&cooklang, &default_unit_matcher()).unwrap();
+
+            // Visibility is a setting of ours rather than part of the recipe
+            assert_eq!(parsed, SaveRecipeParams { is_public: false, ..seed.recipe }, "{}", cooklang);
+        }
+    }
+
+    #[test]
+    fn optional_parts_notes_and_markup_in_steps_survive_a_round_trip() {
+        let mut recipe = seed_recipes().remove(1).recipe;
+        recipe.is_public = false;
+        recipe.components[1].is_optional = true;
+        recipe.components[0].ingredients[0].description = "well fermented".to_string();
+        recipe.components[0].ingredients[1].quantity = Quantity::new(3, 2).unwrap();
+        recipe.instructions[0].steps[0] = "Email @chef about dish #2 -- and ~5 more".to_string();
+        recipe.instructions[1].steps[0] = "== Not a section ==".to_string();
+        recipe.description = "Quick -- and easy".to_string();
+        recipe.source_url = Some("https://example.com/kimchi--jjigae".to_string());
+
+        let cooklang = to_cooklang(&full_details(&recipe));
+        assert_eq!(parse_cooklang(&cooklang, &default_unit_matcher()).unwrap(), recipe, "{}", cooklang);
+    }
+
+    #[test]
+    fn ingredients_cookware_and_timers_are_read_from_steps() {
+        let source = "\
+>> title: Fried Eggs
+>> servings: 2 people
+>> cook time: 1 hour 5 min
+-- a comment about the pan
+Heat @olive oil{2%tbsp} in a #frying pan{}.
+
+Crack @eggs{3} into the pan [- not too fast -] and fry for ~{4%minutes}.
+Season with @salt and @?chives{1%Tablespoons}(chopped).
+";
+        let recipe = parse_cooklang(source, &default_unit_matcher()).unwrap();
+
+        assert_eq!(recipe.name, "Fried Eggs");
+        assert_eq!((recipe.servings, recipe.cook_time), (Some(2), Some(65)));
+        assert_eq!(recipe.instructions.len(), 1);
+        assert_eq!(recipe.instructions[0].steps, vec![
+            "Heat olive oil in a frying pan.",
+            "Crack eggs into the pan and fry for 4 minutes. Season with salt and chives.",
+        ]);
+
+        assert_eq!(recipe.components.len(), 1);
+        assert_eq!(recipe.components[0].name, "Ingredients");
+        let ingredients: Vec<(&str, &str, String, bool, &str)> = recipe.components[0].ingredients
+            .iter()
+            .map(|i| (i.name.as_str(), i.unit.as_str(), i.quantity.to_string(), i.is_optional, i.description.as_str()))
+            .collect();
+        assert_eq!(ingredients, vec![
+            ("olive oil", "tablespoon", "2".to_string(), false, ""),
+            ("eggs", "piece", "3".to_string(), false, ""),
+            ("salt", "to_taste", "1".to_string(), false, ""),
+            ("chives", "tablespoon", "1".to_string(), true, "chopped"),
+        ]);
+    }
+
+    #[test]
+    fn sections_group_ingredients_and_steps() {
+        let source = "\
+---
+title: Pancakes
+prep_time: 10
+---
+== Batter ==
+Whisk @flour{1 1/2%cups} with @milk{300%ml}.
+
+== Topping (optional) ==
+@maple syrup{2%tablespoons}
+";
+        let recipe = parse_cooklang(source, &default_unit_matcher()).unwrap();
+
+        assert_eq!((recipe.name.as_str(), recipe.prep_time), ("Pancakes", Some(10)));
+        let components: Vec<(&str, bool, usize)> = recipe.components.iter().map(|c| (c.name.as_str(), c.is_optional, c.ingredients.len())).collect();
+        assert_eq!(components, vec![("Batter", false, 2), ("Topping", true, 1)]);
+        assert_eq!(recipe.components[0].ingredients[0].quantity, Quantity::new(3, 2).unwrap());
+        assert_eq!(recipe.instructions.len(), 1);
+        assert_eq!(recipe.instructions[0].title, "Batter");
+    }
+
+    #[test]
+    fn broken_markup_is_reported_with_its_line() {
+        let err = parse_cooklang(">> title: Soup\n\nAdd @water{2%cups", &default_unit_matcher()).unwrap_err();
+        assert_eq!(err, CooklangError::Syntax { line: 3, message: "@water is missing its closing }".to_string() });
+
+        let err = parse_cooklang("Add @water{lots%cups}", &default_unit_matcher()).unwrap_err();
+        assert!(matches!(err, CooklangError::Syntax { line: 1, .. }));
+    }
+}
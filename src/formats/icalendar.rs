@@ -0,0 +1,206 @@
+//! Writes the meal plan as an iCalendar (RFC 5545) feed that phone calendars can subscribe
+//! to. Each planned meal is an event that starts when prep has to start, from the recipe's
+//! prep, cook and rest times, and ends when the meal is served. Times are floating, so a
+//! dinner is at 19:00 in whatever time zone the calendar is in.
+use time::{Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use crate::formats::markdown::format_minutes;
+use crate::models::meal_plan::MealPlanEvent;
+
+const PRODUCT_ID: &str = "-//Chopping List//Meal Plan//EN";
+/// How long a meal shows in the calendar when its recipe has no times
+const MEAL_MINUTES: i64 = 30;
+/// Longest line allowed before it has to be folded, in bytes
+const LINE_LENGTH: usize = 75;
+
+/// `base_url` is where the recipe pages are, e.g. `https://example.com`
+pub fn to_icalendar(events: &[MealPlanEvent], base_url: &str) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODUCT_ID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Meal plan".to_string(),
+    ];
+    for event in events {
+        // Meals with times too long to fit in the calendar's dates are left out
+        if let Some(event_lines) = event_lines(event, base_url.trim_end_matches('/')) {
+            lines.extend(event_lines);
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+fn event_lines(event: &MealPlanEvent, base_url: &str) -> Option<Vec<String>> {
+    let served_at = PrimitiveDateTime::new(event.planned_on, event.meal.served_at());
+    let steps = [("Prep", event.prep_time), ("Cook", event.cook_time), ("Rest", event.rest_time)];
+    let total_minutes: i64 = steps.iter().filter_map(|(_, minutes)| minutes.filter(|minutes| *minutes > 0)).map(i64::from).sum();
+    let starts_at = served_at.checked_sub(Duration::minutes(total_minutes))?;
+    let ends_at = if total_minutes > 0 { served_at } else { served_at.checked_add(Duration::minutes(MEAL_MINUTES))? };
+    let url = format!("{}/recipes/{}", base_url, event.recipe_id);
+
+    // The schedule, one step after the other, ending with the meal
+    let mut description = Vec::new();
+    if let Some(servings) = event.servings {
+        description.push(format!("For {} servings", servings));
+    }
+    let mut step_at = starts_at;
+    for (step, minutes) in steps {
+        if let Some(minutes) = minutes.filter(|minutes| *minutes > 0) {
+            description.push(format!("{} from {} ({})", step, clock_time(step_at), format_minutes(minutes)));
+            step_at += Duration::minutes(i64::from(minutes));
+        }
+    }
+    description.push(format!("Serve at {}", clock_time(served_at)));
+    description.push(url.clone());
+
+    Some(vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:meal-plan-{}@chopping-list", event.id),
+        format!("DTSTAMP:{}", utc_date_time(event.updated_at)),
+        format!("LAST-MODIFIED:{}", utc_date_time(event.updated_at)),
+        format!("DTSTART:{}", floating_date_time(starts_at)),
+        format!("DTEND:{}", floating_date_time(ends_at)),
+        format!("SUMMARY:{}", escape(&format!("{}: {}", capitalize(&event.meal.to_string()), event.recipe_name))),
+        format!("DESCRIPTION:{}", escape(&description.join("\n"))),
+        format!("URL:{}", url),
+        "END:VEVENT".to_string(),
+    ])
+}
+
+fn floating_date_time(date_time: PrimitiveDateTime) -> String {
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}",
+        date_time.year(), u8::from(date_time.month()), date_time.day(),
+        date_time.hour(), date_time.minute(), date_time.second(),
+    )
+}
+
+fn utc_date_time(date_time: OffsetDateTime) -> String {
+    let date_time = date_time.to_offset(UtcOffset::UTC);
+    format!("{}Z", floating_date_time(PrimitiveDateTime::new(date_time.date(), date_time.time())))
+}
+
+fn clock_time(date_time: PrimitiveDateTime) -> String {
+    format!("{:02}:{:02}", date_time.hour(), date_time.minute())
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Escapes a TEXT value, where backslashes, semicolons, commas and newlines are special
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Ends the line with CRLF, breaking it onto continuation lines that start with a space when
+/// it is too long. Breaks never split a character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut line_length = 0;
+    for c in line.chars() {
+        if line_length + c.len_utf8() > LINE_LENGTH {
+            folded.push_str("\r\n ");
+            line_length = 1;
+        }
+        folded.push(c);
+        line_length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, fold, to_icalendar};
+    use crate::models::meal_plan::{MealPlanEvent, MealSlot};
+    use time::{Date, Month, OffsetDateTime};
+
+    fn event(meal: MealSlot, prep_time: Option<i32>, cook_time: Option<i32>, rest_time: Option<i32>) -> MealPlanEvent {
+        MealPlanEvent {
+            id: 7,
+            recipe_id: 3,
+            recipe_name: "Soup, with bread".to_string(),
+            planned_on: Date::from_calendar_date(2025, Month::August, 6).unwrap(),
+            meal,
+            servings: Some(6),
+            prep_time,
+            cook_time,
+            rest_time,
+            updated_at: OffsetDateTime::from_unix_timestamp(1_754_000_000).unwrap(),
+        }
+    }
+
+    fn unfolded(calendar: &str) -> Vec<String> {
+        calendar.replace("\r\n ", "").split("\r\n").map(str::to_string).collect()
+    }
+
+    #[test]
+    fn events_start_when_prep_starts() {
+        let calendar = to_icalendar(&[event(MealSlot::Dinner, Some(20), Some(90), Some(10))], "https://example.com/");
+        let lines = unfolded(&calendar);
+        assert_eq!(lines.first().unwrap(), "BEGIN:VCALENDAR");
+        assert!(lines.contains(&"UID:meal-plan-7@chopping-list".to_string()));
+        assert!(lines.contains(&"DTSTAMP:20250731T221320Z".to_string()));
+        assert!(lines.contains(&"DTSTART:20250806T170000".to_string()));
+        assert!(lines.contains(&"DTEND:20250806T190000".to_string()));
+        assert!(lines.contains(&"SUMMARY:Dinner: Soup\\, with bread".to_string()));
+        assert!(lines.contains(&"DESCRIPTION:For 6 servings\\nPrep from 17:00 (20 minutes)\\nCook from 17:20 (1 hour 30 minutes)\\nRest from 18:50 (10 minutes)\\nServe at 19:00\\nhttps://example.com/recipes/3".to_string()));
+        assert!(lines.contains(&"URL:https://example.com/recipes/3".to_string()));
+        assert!(calendar.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn meals_without_times_are_at_the_meal() {
+        let lines = unfolded(&to_icalendar(&[event(MealSlot::Breakfast, None, Some(0), None)], "https://example.com"));
+        assert!(lines.contains(&"DTSTART:20250806T080000".to_string()));
+        assert!(lines.contains(&"DTEND:20250806T083000".to_string()));
+    }
+
+    #[test]
+    fn prep_can_start_the_day_before() {
+        let lines = unfolded(&to_icalendar(&[event(MealSlot::Breakfast, Some(600), None, None)], "https://example.com"));
+        assert!(lines.contains(&"DTSTART:20250805T220000".to_string()));
+    }
+
+    #[test]
+    fn meals_too_long_to_date_are_left_out() {
+        let endless = event(MealSlot::Dinner, Some(i32::MAX), Some(i32::MAX), Some(i32::MAX));
+        let calendar = to_icalendar(&[endless, event(MealSlot::Lunch, None, None, None)], "https://example.com");
+        assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 1);
+        assert!(unfolded(&calendar).contains(&"DTSTART:20250806T123000".to_string()));
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape("a;b,c\\d\r\ne"), "a\\;b\\,c\\\\d\\ne");
+    }
+
+    #[test]
+    fn long_lines_are_folded_without_splitting_characters() {
+        let line = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+}
@@ -0,0 +1,358 @@
+//! Reads the whole-library exports of other recipe apps and our own archives, so a
+//! collection can be moved over in one upload. Every recipe in a file is imported on its own
+//! and reported on its own: one that can't be read doesn't stop the rest.
+use std::io::{Cursor, Read};
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use sqlx::PgPool;
+use crate::chopper::ChopperRecipe;
+use crate::models::recipe::{DuplicatePolicy, ImportOutcome};
+use super::archive::{is_archive, match_units, read_archive, restore_recipe, ArchiveError};
+use super::{mealmaster, mela, paprika};
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// No recipe, even with its photo, unpacks to more than this. Anything bigger is refused
+/// rather than read into memory.
+pub(crate) const MAX_FILE_BYTES: u64 = 16 * 1024 * 1024;
+/// More files than any recipe library has
+pub(crate) const MAX_ARCHIVE_FILES: usize = 10_000;
+/// Everything unpacked from one upload together
+pub(crate) const MAX_UNPACKED_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LibraryFormat {
+    Paprika,
+    MealMaster,
+    Mela,
+}
+
+impl LibraryFormat {
+    /// Goes by the file's extension, and by its contents for text files, which MealMaster
+    /// exports are saved as under many names
+    pub fn detect(file_name: &str, bytes: &[u8]) -> Option<Self> {
+        let extension = file_name.rsplit_once('.').map(|(_, extension)| extension.to_lowercase());
+        match extension.as_deref() {
+            Some("paprikarecipes" | "paprikarecipe") => Some(Self::Paprika),
+            Some("melarecipes" | "melarecipe") => Some(Self::Mela),
+            _ if mealmaster::looks_like_mealmaster(&decode_text(bytes)) => Some(Self::MealMaster),
+            _ => None,
+        }
+    }
+}
+
+/// One recipe found in an uploaded file. Recipes that can't be read keep the reason, so
+/// they show up in the report.
+#[derive(Debug)]
+pub struct LibraryEntry {
+    pub name: String,
+    pub recipe: Result<ChopperRecipe, String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LibraryError {
+    #[error("{0} is not a Chopping List, Paprika, MealMaster or Mela export")]
+    Unrecognized(String),
+
+    #[error("The archive can't be read: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("The file can't be read: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("The archive holds {0} files, more than the {MAX_ARCHIVE_FILES} it may")]
+    TooManyFiles(usize),
+
+    #[error("The archive unpacks to more than {} MB", MAX_UNPACKED_BYTES / 1024 / 1024)]
+    TooBig,
+}
+
+/// Reads every recipe in an uploaded export
+pub fn read_library(file_name: &str, bytes: &[u8]) -> Result<Vec<LibraryEntry>, LibraryError> {
+    match LibraryFormat::detect(file_name, bytes) {
+        Some(LibraryFormat::Paprika) => paprika::read_paprika(bytes),
+        Some(LibraryFormat::MealMaster) => Ok(mealmaster::read_mealmaster(&decode_text(bytes))),
+        Some(LibraryFormat::Mela) => mela::read_mela(bytes),
+        None => Err(LibraryError::Unrecognized(file_name.to_string())),
+    }
+}
+
+pub(crate) fn is_zip(bytes: &[u8]) -> bool {
+    bytes.starts_with(ZIP_MAGIC)
+}
+
+pub(crate) fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(GZIP_MAGIC)
+}
+
+/// A file's path in a zip archive and its contents, or why they can't be read
+pub(crate) type ArchiveFile = (String, Result<Vec<u8>, String>);
+
+/// The name and contents of every file in a zip archive, leaving out folders and the
+/// metadata macOS adds. Files that are too big are answered with the reason instead.
+pub(crate) fn archive_files(bytes: &[u8]) -> Result<Vec<ArchiveFile>, LibraryError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    if archive.len() > MAX_ARCHIVE_FILES {
+        return Err(LibraryError::TooManyFiles(archive.len()));
+    }
+    let mut files = Vec::new();
+    let mut unpacked_bytes = 0;
+    for idx in 0..archive.len() {
+        let file = archive.by_index(idx)?;
+        if file.is_dir() || file.name().starts_with("__MACOSX/") {
+            continue;
+        }
+        let name = file.name().to_string();
+        let contents = read_limited(file, MAX_FILE_BYTES).map_err(|err| format!("The file can't be read: {}", err));
+        if let Ok(contents) = &contents {
+            unpacked_bytes += contents.len() as u64;
+            if unpacked_bytes > MAX_UNPACKED_BYTES {
+                return Err(LibraryError::TooBig);
+            }
+        }
+        files.push((name, contents));
+    }
+
+    Ok(files)
+}
+
+pub(crate) fn gunzip(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    read_limited(GzDecoder::new(bytes), MAX_FILE_BYTES)
+}
+
+/// Reads everything unless there is more than `limit` bytes of it, which compressed files
+/// can hold however small they are
+pub(crate) fn read_limited(reader: impl Read, limit: u64) -> std::io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut contents)?;
+    if contents.len() as u64 > limit {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("it unpacks to more than {} MB", limit / 1024 / 1024),
+        ));
+    }
+
+    Ok(contents)
+}
+
+/// Older exports are usually Windows-1252 rather than UTF-8. Reading those bytes as
+/// Latin-1 keeps the accented letters that matter in recipe names.
+pub(crate) fn decode_text(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+/// The file name without its folders or extension, for entries whose recipe can't be read
+pub(crate) fn entry_name(path: &str) -> String {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let name = file_name.rsplit_once('.').map(|(name, _)| name).unwrap_or(file_name);
+    name.to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Imported,
+    Skipped,
+    Failed,
+}
+
+/// How one recipe from an upload fared
+#[derive(Debug, Serialize)]
+pub struct ImportReportItem {
+    pub file: String,
+    pub name: String,
+    pub status: ImportStatus,
+    /// The recipe that was created, or that was already there for skipped duplicates
+    pub recipe_id: Option<i32>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub recipes: Vec<ImportReportItem>,
+}
+
+impl ImportReport {
+    /// Saves every readable recipe in the file for the user, treating recipes the user
+    /// already has from the same source URL according to `on_duplicate`
+    pub async fn import_file(
+        &mut self,
+        db: &PgPool,
+        user_id: &uuid::Uuid,
+        file_name: &str,
+        bytes: &[u8],
+        on_duplicate: DuplicatePolicy,
+    ) {
+        if is_archive(bytes) {
+            return self.import_archive(db, user_id, file_name, bytes, on_duplicate).await;
+        }
+
+        let entries = match read_library(file_name, bytes) {
+            Ok(entries) if entries.is_empty() => {
+                return self.fail(file_name, &entry_name(file_name), vec!["No recipes were found in the file".to_string()]);
+            },
+            Ok(entries) => entries,
+            Err(err) => return self.fail(file_name, &entry_name(file_name), vec![err.to_string()]),
+        };
+
+        for entry in entries {
+            let recipe = match entry.recipe {
+                Ok(recipe) => recipe,
+                Err(err) => {
+                    self.fail(file_name, &entry.name, vec![err]);
+                    continue;
+                },
+            };
+            if let Err(problems) = recipe.validate() {
+                self.fail(file_name, &entry.name, problems);
+                continue;
+            }
+
+            match recipe.save_for_user(db, user_id, on_duplicate).await {
+                Ok((saved, outcome)) => self.saved(file_name, &recipe.name, saved.id, outcome),
+                Err(err) => {
+                    tracing::error!("Failed to import {} from {}: {:?}", entry.name, file_name, err);
+                    self.fail(file_name, &entry.name, vec![err.to_string()]);
+                },
+            }
+        }
+    }
+
+    /// Restores an archive exported from this or another instance. Units this instance
+    /// doesn't have are reported as failed, as are the recipes that use them.
+    async fn import_archive(
+        &mut self,
+        db: &PgPool,
+        user_id: &uuid::Uuid,
+        file_name: &str,
+        bytes: &[u8],
+        on_duplicate: DuplicatePolicy,
+    ) {
+        let (units, entries) = match read_archive(bytes) {
+            Ok(contents) => contents,
+            Err(err) => return self.fail(file_name, &entry_name(file_name), vec![err.to_string()]),
+        };
+        let units = match match_units(db, &units).await {
+            Ok((units, unknown)) => {
+                for unit in unknown {
+                    self.fail(file_name, &unit, vec![format!("{} is not a unit on this server", unit)]);
+                }
+                units
+            },
+            Err(err) => {
+                tracing::error!("Failed to match the units of {}: {:?}", file_name, err);
+                return self.fail(file_name, &entry_name(file_name), vec![err.to_string()]);
+            },
+        };
+
+        for entry in entries {
+            let archived = match entry.recipe {
+                Ok(archived) => archived,
+                Err(err) => {
+                    self.fail(file_name, &entry_name(&entry.path), vec![err]);
+                    continue;
+                },
+            };
+            let name = archived.recipe.name.clone();
+            match restore_recipe(db, user_id, &archived, &units, on_duplicate).await {
+                Ok((saved, outcome)) => self.saved(file_name, &name, saved.id, outcome),
+                Err(ArchiveError::Invalid(problems)) => self.fail(file_name, &name, problems),
+                Err(err) => {
+                    tracing::error!("Failed to restore {} from {}: {:?}", name, file_name, err);
+                    self.fail(file_name, &name, vec![err.to_string()]);
+                },
+            }
+        }
+    }
+
+    fn saved(&mut self, file_name: &str, name: &str, recipe_id: i32, outcome: ImportOutcome) {
+        let status = match outcome {
+            ImportOutcome::Skipped => ImportStatus::Skipped,
+            _ => ImportStatus::Imported,
+        };
+        self.push(ImportReportItem {
+            file: file_name.to_string(),
+            name: name.to_string(),
+            status,
+            recipe_id: Some(recipe_id),
+            errors: Vec::new(),
+        });
+    }
+
+    fn fail(&mut self, file_name: &str, name: &str, errors: Vec<String>) {
+        self.push(ImportReportItem {
+            file: file_name.to_string(),
+            name: name.to_string(),
+            status: ImportStatus::Failed,
+            recipe_id: None,
+            errors,
+        });
+    }
+
+    fn push(&mut self, item: ImportReportItem) {
+        match item.status {
+            ImportStatus::Imported => self.imported += 1,
+            ImportStatus::Skipped => self.skipped += 1,
+            ImportStatus::Failed => self.failed += 1,
+        }
+        self.recipes.push(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use flate2::{write::GzEncoder, Compression};
+    use zip::write::{SimpleFileOptions, ZipWriter};
+    use super::{archive_files, entry_name, gunzip, LibraryError, LibraryFormat, MAX_ARCHIVE_FILES, MAX_FILE_BYTES};
+
+    #[test]
+    fn formats_are_told_apart_by_extension_then_contents() {
+        assert_eq!(LibraryFormat::detect("Export.paprikarecipes", b"PK"), Some(LibraryFormat::Paprika));
+        assert_eq!(LibraryFormat::detect("soup.MELARECIPE", b"{}"), Some(LibraryFormat::Mela));
+        let mealmaster = b"MMMMM----- Recipe via Meal-Master (tm) v8.05\n\n      Title: Soup\n";
+        assert_eq!(LibraryFormat::detect("SOUPS.TXT", mealmaster), Some(LibraryFormat::MealMaster));
+        assert_eq!(LibraryFormat::detect("notes.txt", b"Buy milk"), None);
+    }
+
+    #[test]
+    fn entry_names_drop_folders_and_extensions() {
+        assert_eq!(entry_name("Recipes/Pho.paprikarecipe"), "Pho");
+    }
+
+    #[test]
+    fn files_that_unpack_too_big_are_failed_on_their_own() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("bomb.melarecipe", SimpleFileOptions::default()).unwrap();
+        zip.write_all(&vec![0; MAX_FILE_BYTES as usize + 1]).unwrap();
+        zip.start_file("soup.melarecipe", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"{}").unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let files = archive_files(&bytes).unwrap();
+        assert_eq!(files[0].0, "bomb.melarecipe");
+        assert_eq!(files[0].1.as_ref().unwrap_err(), "The file can't be read: it unpacks to more than 16 MB");
+        assert_eq!(files[1].1.as_deref(), Ok(&b"{}"[..]));
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&vec![0; MAX_FILE_BYTES as usize + 1]).unwrap();
+        assert!(gunzip(&encoder.finish().unwrap()).is_err());
+    }
+
+    #[test]
+    fn archives_with_too_many_files_are_refused() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for idx in 0..=MAX_ARCHIVE_FILES {
+            zip.start_file(format!("{}.melarecipe", idx), SimpleFileOptions::default()).unwrap();
+        }
+        let bytes = zip.finish().unwrap().into_inner();
+
+        assert!(matches!(archive_files(&bytes), Err(LibraryError::TooManyFiles(count)) if count == MAX_ARCHIVE_FILES + 1));
+    }
+}
@@ -0,0 +1,209 @@
+//! Writes recipes as Markdown for printing or pasting into notes: the details as a list,
+//! then a section for the ingredients and one for the instructions, with a heading for
+//! each component and each instruction. Amounts are written as fractions, like "1 1/2 cup".
+use crate::models::recipe::{FullRecipeComponentIngredient, FullRecipeDetails};
+
+/// Ingredients counted rather than measured, which are written without a unit
+const COUNT_UNIT: &str = "piece";
+/// Ingredients without an amount
+const UNMEASURED_UNIT: &str = "to_taste";
+
+pub fn to_markdown(recipe: &FullRecipeDetails) -> String {
+    let mut lines = vec![format!("# {}", escape(&one_line(&recipe.name)))];
+    if !recipe.description.trim().is_empty() {
+        lines.push(String::new());
+        lines.push(escape(&one_line(&recipe.description)));
+    }
+
+    let mut details = Vec::new();
+    if let Some(servings) = recipe.servings {
+        details.push(format!("- **Servings:** {}", servings));
+    }
+    for (label, minutes) in [("Prep time", recipe.prep_time), ("Cook time", recipe.cook_time), ("Rest time", recipe.rest_time)] {
+        if let Some(minutes) = minutes.filter(|minutes| *minutes > 0) {
+            details.push(format!("- **{}:** {}", label, format_minutes(minutes)));
+        }
+    }
+    if let Some(source_url) = recipe.source_url.as_deref().filter(|url| !url.trim().is_empty()) {
+        details.push(format!("- **Source:** <{}>", source_url.trim()));
+    }
+    if !details.is_empty() {
+        lines.push(String::new());
+        lines.extend(details);
+    }
+
+    if !recipe.recipe_components.is_empty() {
+        lines.push(String::new());
+        lines.push("## Ingredients".to_string());
+    }
+    for component in &recipe.recipe_components {
+        let suffix = if component.is_optional { " (optional)" } else { "" };
+        lines.push(String::new());
+        lines.push(format!("### {}{}", escape(&one_line(&component.name)), suffix));
+        lines.push(String::new());
+        for ingredient in &component.component_ingredients {
+            lines.push(format!("- {}", escape(&ingredient_text(ingredient))));
+        }
+    }
+
+    if !recipe.recipe_instructions.is_empty() {
+        lines.push(String::new());
+        lines.push("## Instructions".to_string());
+    }
+    for instruction in &recipe.recipe_instructions {
+        lines.push(String::new());
+        lines.push(format!("### {}", escape(&one_line(&instruction.title))));
+        lines.push(String::new());
+        for step in &instruction.instruction_steps {
+            lines.push(format!("{}. {}", step.step_number, escape(&one_line(&step.content))));
+        }
+    }
+    lines.push(String::new());
+
+    lines.join("\n")
+}
+
+/// How an ingredient reads in a cookbook: "1 1/2 cup flour, sifted", "2 eggs" or
+/// "salt, to taste"
+pub fn ingredient_text(ingredient: &FullRecipeComponentIngredient) -> String {
+    let name = one_line(&ingredient.name);
+    let mut text = match ingredient.unit.as_str() {
+        UNMEASURED_UNIT => name,
+        COUNT_UNIT => format!("{} {}", ingredient.quantity, name),
+        unit => format!("{} {} {}", ingredient.quantity, unit.replace('_', " "), name),
+    };
+    if !ingredient.description.trim().is_empty() {
+        text.push_str(", ");
+        text.push_str(&one_line(&ingredient.description));
+    }
+    if ingredient.unit == UNMEASURED_UNIT {
+        text.push_str(", to taste");
+    }
+    if ingredient.is_optional {
+        text.push_str(" (optional)");
+    }
+
+    text
+}
+
+/// "45 minutes", "1 hour" or "1 hour 30 minutes"
+pub(crate) fn format_minutes(minutes: i32) -> String {
+    let plural = |count: i32, unit: &str| format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" });
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => plural(minutes, "minute"),
+        (hours, 0) => plural(hours, "hour"),
+        (hours, minutes) => format!("{} {}", plural(hours, "hour"), plural(minutes, "minute")),
+    }
+}
+
+/// Keeps text that happens to contain Markdown from being formatted
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_minutes, ingredient_text, to_markdown};
+    use crate::domain::Quantity;
+    use crate::models::recipe::{
+        FullRecipeComponent,
+        FullRecipeComponentIngredient,
+        FullRecipeDetails,
+        FullRecipeInstruction,
+        FullRecipeInstructionStep,
+    };
+
+    fn ingredient(name: &str, unit: &str, quantity: Quantity, description: &str, is_optional: bool) -> FullRecipeComponentIngredient {
+        FullRecipeComponentIngredient {
+            ingredient_id: 1,
+            name: name.to_string(),
+            description: description.to_string(),
+            unit: unit.to_string(),
+            quantity,
+            is_optional,
+        }
+    }
+
+    #[test]
+    fn ingredients_read_like_a_cookbook() {
+        let flour = ingredient("flour", "cup", Quantity::new(3, 2).unwrap(), "sifted", false);
+        assert_eq!(ingredient_text(&flour), "1 1/2 cup flour, sifted");
+        let eggs = ingredient("eggs", "piece", Quantity::whole(2), "", false);
+        assert_eq!(ingredient_text(&eggs), "2 eggs");
+        let salt = ingredient("salt", "to_taste", Quantity::whole(1), "flaky", true);
+        assert_eq!(ingredient_text(&salt), "salt, flaky, to taste (optional)");
+        let milk = ingredient("milk", "fluid_ounce", Quantity::new(1, 2).unwrap(), "", false);
+        assert_eq!(ingredient_text(&milk), "1/2 fluid ounce milk");
+    }
+
+    #[test]
+    fn recipes_are_written_as_sections() {
+        let recipe = FullRecipeDetails {
+            recipe_id: 1,
+            name: "Pancakes".to_string(),
+            description: "Fluffy *and* quick".to_string(),
+            is_public: false,
+            prep_time: Some(10),
+            cook_time: Some(90),
+            rest_time: None,
+            servings: Some(4),
+            source_url: Some("https://example.com/pancakes".to_string()),
+            recipe_components: vec![FullRecipeComponent {
+                name: "Batter".to_string(),
+                is_optional: false,
+                component_ingredients: vec![ingredient("flour", "cup", Quantity::new(3, 2).unwrap(), "", false)],
+            }],
+            recipe_instructions: vec![FullRecipeInstruction {
+                order_idx: 0,
+                title: "Cook".to_string(),
+                instruction_steps: vec![
+                    FullRecipeInstructionStep { step_number: 1, content: "Mix.".to_string() },
+                    FullRecipeInstructionStep { step_number: 2, content: "Fry in\nbatches.".to_string() },
+                ],
+            }],
+        };
+
+        assert_eq!(to_markdown(&recipe), "\
+# Pancakes
+
+Fluffy \\*and\\* quick
+
+- **Servings:** 4
+- **Prep time:** 10 minutes
+- **Cook time:** 1 hour 30 minutes
+- **Source:** <https://example.com/pancakes>
+
+## Ingredients
+
+### Batter
+
+- 1 1/2 cup flour
+
+## Instructions
+
+### Cook
+
+1. Mix.
+2. Fry in batches.
+");
+    }
+
+    #[test]
+    fn durations_are_written_in_hours_and_minutes() {
+        assert_eq!(format_minutes(1), "1 minute");
+        assert_eq!(format_minutes(120), "2 hours");
+        assert_eq!(format_minutes(61), "1 hour 1 minute");
+    }
+}
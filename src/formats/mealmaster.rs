@@ -0,0 +1,320 @@
+//! Reads MealMaster text exports, which hold any number of recipes between a
+//! "Recipe via Meal-Master" header and an `MMMMM` (or `-----`) line.
+//!
+//! After the title, categories and yield come the ingredients in fixed columns: the amount
+//! in the first seven characters, a two letter unit code, then the ingredient. Lines like
+//! `MMMMM-----SAUCE-----` start a new section and ingredients continue on lines starting
+//! with `-`. The directions follow as paragraphs wrapped to fit the screen.
+use regex::Regex;
+use crate::chopper::ChopperRecipe;
+use crate::domain::UnitMatcher;
+use crate::models::unit::default_unit_matcher;
+use super::library::LibraryEntry;
+use super::{components_from_lines, first_number, instructions_from_lines, ListLine};
+
+/// MealMaster's unit codes and how we write them. Sizes are kept as part of the name.
+const UNIT_CODES: &[(&str, &str)] = &[
+    ("x", ""),
+    ("ea", ""),
+    ("sm", "small"),
+    ("md", "medium"),
+    ("lg", "large"),
+    ("t", "teaspoon"),
+    ("ts", "teaspoon"),
+    ("tb", "tablespoon"),
+    ("T", "tablespoon"),
+    ("c", "cup"),
+    ("fl", "floz"),
+    ("pt", "pint"),
+    ("qt", "quart"),
+    ("ga", "gallon"),
+    ("oz", "ounce"),
+    ("lb", "pound"),
+    ("ml", "milliliter"),
+    ("cl", "centiliter"),
+    ("dl", "deciliter"),
+    ("l", "liter"),
+    ("mg", "milligram"),
+    ("g", "gram"),
+    ("kg", "kilogram"),
+    ("cn", "can"),
+    ("pk", "package"),
+    ("pn", "pinch"),
+    ("dr", "drop"),
+    ("ds", "dash"),
+    ("sl", "slice"),
+    ("bn", "bunch"),
+    ("cb", "cube"),
+    ("ct", "carton"),
+];
+
+/// Units MealMaster has that we don't start out with
+const EXTRA_UNITS: &[(&str, Option<&str>)] = &[
+    ("fluid_ounce", Some("floz")),
+    ("pint", None),
+    ("quart", None),
+    ("gallon", None),
+    ("centiliter", None),
+    ("deciliter", None),
+    ("milligram", None),
+    ("drop", None),
+    ("dash", None),
+    ("slice", None),
+    ("bunch", None),
+    ("cube", None),
+    ("carton", None),
+];
+
+/// Where the second column starts in two column ingredient lists
+const SECOND_COLUMN: usize = 41;
+
+pub fn looks_like_mealmaster(text: &str) -> bool {
+    text.lines().any(is_recipe_header)
+}
+
+/// Reads every recipe in the file. Text outside of recipes is ignored.
+pub fn read_mealmaster(text: &str) -> Vec<LibraryEntry> {
+    let units = default_unit_matcher().with_units(EXTRA_UNITS.iter().copied());
+    let mut entries = Vec::new();
+    let mut lines = text.lines();
+    while lines.by_ref().any(is_recipe_header) {
+        let body: Vec<&str> = lines.by_ref().take_while(|line| !is_recipe_end(line)).collect();
+        let recipe = read_recipe(&body, &units);
+        let name = match recipe.name.is_empty() {
+            true => format!("Recipe {}", entries.len() + 1),
+            false => recipe.name.clone(),
+        };
+        entries.push(LibraryEntry { name, recipe: Ok(recipe) });
+    }
+
+    entries
+}
+
+fn is_recipe_header(line: &str) -> bool {
+    let line = line.trim();
+    (line.starts_with("MMMMM") || line.starts_with("-----")) && line.to_lowercase().contains("meal-master")
+}
+
+fn is_recipe_end(line: &str) -> bool {
+    matches!(line.trim(), "MMMMM" | "-----")
+}
+
+enum Part {
+    Header,
+    Ingredients,
+    Directions,
+}
+
+fn read_recipe(lines: &[&str], units: &UnitMatcher) -> ChopperRecipe {
+    let header_re = Regex::new(r"^\s*(Title|Categories|Yield|Servings):\s*(.*)$").unwrap();
+    let (mut name, mut servings) = (String::new(), 0);
+    let mut ingredients: Vec<ListLine> = Vec::new();
+    let mut directions: Vec<ListLine> = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut part = Part::Header;
+
+    for line in lines {
+        if let Part::Header = part {
+            if let Some(captures) = header_re.captures(line) {
+                match &captures[1] {
+                    "Title" => name = captures[2].trim().to_string(),
+                    "Yield" | "Servings" => servings = first_number(&captures[2]).unwrap_or(0),
+                    _ => {},
+                }
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            part = Part::Ingredients;
+        }
+
+        if let Part::Ingredients = part {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(heading) = section_heading(line) {
+                ingredients.push(ListLine::Heading(heading));
+                continue;
+            }
+            if let Some(columns) = ingredient_columns(line) {
+                for (amount, unit, text) in columns {
+                    add_ingredient(&mut ingredients, &amount, &unit, &text);
+                }
+                continue;
+            }
+            part = Part::Directions;
+        }
+
+        if line.trim().is_empty() {
+            end_paragraph(&mut directions, &mut paragraph);
+        } else if let Some(heading) = section_heading(line) {
+            end_paragraph(&mut directions, &mut paragraph);
+            directions.push(ListLine::Heading(heading));
+        } else {
+            paragraph.push(line.trim());
+        }
+    }
+    end_paragraph(&mut directions, &mut paragraph);
+
+    ChopperRecipe {
+        description: String::new(),
+        name,
+        prep_time: 0,
+        rest_time: 0,
+        cook_time: 0,
+        servings,
+        source_url: String::new(),
+        components: components_from_lines(ingredients, units),
+        instructions: instructions_from_lines(directions),
+    }
+}
+
+/// "MMMMM-----FOR THE SAUCE-----" reads as "For the sauce"
+fn section_heading(line: &str) -> Option<String> {
+    let line = line.trim();
+    if !(line.starts_with("MMMMM-") || line.starts_with("-----")) || is_recipe_header(line) {
+        return None;
+    }
+    let heading = line.trim_start_matches('M').trim_matches('-').trim();
+    if heading.is_empty() {
+        return None;
+    }
+    if heading.chars().any(|c| c.is_lowercase()) {
+        return Some(heading.to_string());
+    }
+
+    let heading = heading.to_lowercase();
+    let mut chars = heading.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect())
+}
+
+/// Splits an ingredient line into (amount, unit code, text) for each column it has, or
+/// answers None if it isn't laid out like one
+fn ingredient_columns(line: &str) -> Option<Vec<(String, String, String)>> {
+    let chars: Vec<char> = line.trim_end().chars().collect();
+    if chars.len() > SECOND_COLUMN + 11 {
+        if let (Some(left), Some(right)) = (ingredient_column(&chars[..SECOND_COLUMN]), ingredient_column(&chars[SECOND_COLUMN..])) {
+            return Some(vec![left, right]);
+        }
+    }
+
+    ingredient_column(&chars).map(|column| vec![column])
+}
+
+fn ingredient_column(chars: &[char]) -> Option<(String, String, String)> {
+    if chars.len() < 12 || chars[7] != ' ' || chars[10] != ' ' {
+        return None;
+    }
+    let amount: String = chars[..7].iter().collect::<String>().trim().to_string();
+    let unit: String = chars[8..10].iter().collect::<String>().trim().to_string();
+    let text: String = chars[11..].iter().collect::<String>().trim().to_string();
+
+    let is_amount = amount.chars().all(|c| c.is_ascii_digit() || matches!(c, '/' | '.' | ' ' | '-'));
+    let is_unit = unit.is_empty() || UNIT_CODES.iter().any(|(code, _)| *code == unit);
+    if !is_amount || !is_unit || text.is_empty() {
+        return None;
+    }
+
+    Some((amount, unit, text))
+}
+
+fn add_ingredient(ingredients: &mut Vec<ListLine>, amount: &str, unit: &str, text: &str) {
+    // "-finely chopped" carries on the ingredient above
+    if amount.is_empty() && unit.is_empty() {
+        if let (Some(rest), Some(ListLine::Item(previous))) = (text.strip_prefix('-'), ingredients.last_mut()) {
+            previous.push_str(", ");
+            previous.push_str(rest.trim());
+            return;
+        }
+    }
+
+    let unit = UNIT_CODES
+        .iter()
+        .find(|(code, _)| *code == unit)
+        .map(|(_, spelling)| *spelling)
+        .unwrap_or_default();
+    let line = [amount, unit, text]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    ingredients.push(ListLine::Item(line));
+}
+
+fn end_paragraph(directions: &mut Vec<ListLine>, paragraph: &mut Vec<&str>) {
+    if !paragraph.is_empty() {
+        directions.push(ListLine::Item(paragraph.join(" ")));
+        paragraph.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{looks_like_mealmaster, read_mealmaster};
+
+    const CHILI: &str = "\
+MMMMM----- Recipe via Meal-Master (tm) v8.05
+
+      Title: Chili Con Carne
+ Categories: Main dish, Beef
+      Yield: 6 servings
+
+      1 lb Ground beef
+      1 md Onion
+  1 1/2 c  Kidney beans
+      2 tb Chili powder
+           -finely ground
+      1 pt Stock
+
+MMMMM-----------------------------SAUCE------------------------------
+      1 cn Tomato sauce
+
+  Brown the beef with the onion. Add the beans
+  and the chili powder.
+
+  Simmer for an hour.
+
+MMMMM
+";
+
+    #[test]
+    fn recipes_are_read_column_by_column() {
+        assert!(looks_like_mealmaster(CHILI));
+        let entries = read_mealmaster(CHILI);
+        assert_eq!(entries.len(), 1);
+
+        let recipe = entries[0].recipe.as_ref().unwrap();
+        assert_eq!((recipe.name.as_str(), recipe.servings), ("Chili Con Carne", 6));
+        assert_eq!(recipe.components.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["Ingredients", "Sauce"]);
+
+        let ingredients = &recipe.components[0].ingredients;
+        assert_eq!((ingredients[0].unit.as_str(), ingredients[0].name.as_str()), ("pound", "Ground beef"));
+        assert_eq!((ingredients[1].unit.as_str(), ingredients[1].name.as_str()), ("piece", "medium Onion"));
+        assert_eq!((ingredients[2].quantity_numerator, ingredients[2].quantity_denominator, ingredients[2].unit.as_str()), (3, 2, "cup"));
+        assert_eq!((ingredients[3].name.as_str(), ingredients[3].description.as_str()), ("Chili powder", "finely ground"));
+        assert_eq!(ingredients[4].unit, "pint");
+        assert_eq!(recipe.components[1].ingredients[0].unit, "can");
+
+        let steps = &recipe.instructions[0].steps;
+        assert_eq!(steps.iter().map(|s| s.content.as_str()).collect::<Vec<_>>(), [
+            "Brown the beef with the onion. Add the beans and the chili powder.",
+            "Simmer for an hour.",
+        ]);
+    }
+
+    #[test]
+    fn files_hold_many_recipes_and_two_column_lists() {
+        let text = format!(
+            "{}\n---------- Recipe via Meal-Master (tm) v8.02\n\n      Title: Salad\n   Servings: 2\n\n{:<41}{}\n\n  Toss.\n\n-----\n",
+            CHILI,
+            "      1    Lettuce",
+            "      2 tb Olive oil",
+        );
+
+        let entries = read_mealmaster(&text);
+        assert_eq!(entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), ["Chili Con Carne", "Salad"]);
+        let salad = entries[1].recipe.as_ref().unwrap();
+        assert_eq!(salad.components[0].ingredients.iter().map(|i| i.name.as_str()).collect::<Vec<_>>(), ["Lettuce", "Olive oil"]);
+    }
+}
@@ -0,0 +1,134 @@
+//! Reads Mela exports. A `.melarecipe` file is a single JSON recipe and a `.melarecipes` file
+//! is a zip archive of them. Ingredients and instructions are text with one item per line,
+//! where lines starting with `#` are section headings.
+use serde::Deserialize;
+use crate::chopper::ChopperRecipe;
+use crate::models::unit::default_unit_matcher;
+use super::library::{archive_files, entry_name, is_zip, LibraryEntry, LibraryError};
+use super::{components_from_lines, first_number, instructions_from_lines, minutes, rest_minutes, ListLine};
+
+/// The fields of a Mela recipe that have a place in ours
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct MelaRecipe {
+    title: Option<String>,
+    text: Option<String>,
+    ingredients: Option<String>,
+    instructions: Option<String>,
+    notes: Option<String>,
+    #[serde(rename = "yield")]
+    recipe_yield: Option<String>,
+    prep_time: Option<String>,
+    cook_time: Option<String>,
+    total_time: Option<String>,
+    link: Option<String>,
+}
+
+pub fn read_mela(bytes: &[u8]) -> Result<Vec<LibraryEntry>, LibraryError> {
+    if !is_zip(bytes) {
+        return Ok(vec![read_recipe_file("recipe", bytes)]);
+    }
+
+    Ok(archive_files(bytes)?
+        .into_iter()
+        .map(|(path, contents)| match contents {
+            Ok(contents) => read_recipe_file(&path, &contents),
+            Err(err) => LibraryEntry { name: entry_name(&path), recipe: Err(err) },
+        })
+        .collect())
+}
+
+fn read_recipe_file(path: &str, bytes: &[u8]) -> LibraryEntry {
+    match serde_json::from_slice::<MelaRecipe>(bytes) {
+        Ok(recipe) => LibraryEntry {
+            name: recipe.title.clone().unwrap_or_else(|| entry_name(path)),
+            recipe: Ok(to_chopper_recipe(recipe)),
+        },
+        Err(err) => LibraryEntry {
+            name: entry_name(path),
+            recipe: Err(format!("The recipe can't be read: {}", err)),
+        },
+    }
+}
+
+fn to_chopper_recipe(recipe: MelaRecipe) -> ChopperRecipe {
+    let text = |field: &Option<String>| field.as_deref().unwrap_or_default().trim().to_string();
+    let prep_time = recipe.prep_time.as_deref().and_then(minutes).unwrap_or(0);
+    let cook_time = recipe.cook_time.as_deref().and_then(minutes).unwrap_or(0);
+    let total_time = recipe.total_time.as_deref().and_then(minutes);
+
+    let description = [text(&recipe.text), text(&recipe.notes)]
+        .into_iter()
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    ChopperRecipe {
+        description,
+        name: text(&recipe.title),
+        prep_time,
+        rest_time: rest_minutes(total_time, prep_time, cook_time),
+        cook_time,
+        servings: recipe.recipe_yield.as_deref().and_then(first_number).unwrap_or(0),
+        source_url: text(&recipe.link),
+        components: components_from_lines(list_lines(&text(&recipe.ingredients)), &default_unit_matcher()),
+        instructions: instructions_from_lines(list_lines(&text(&recipe.instructions))),
+    }
+}
+
+fn list_lines(text: &str) -> Vec<ListLine> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.strip_prefix('#') {
+            Some(heading) => ListLine::Heading(heading.trim_start_matches('#').trim().to_string()),
+            None => ListLine::Item(line.to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use serde_json::json;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+    use super::read_mela;
+
+    #[test]
+    fn sections_are_marked_with_hashes() {
+        let recipe = json!({
+            "id": "4C1B",
+            "title": "Tacos",
+            "text": "Weeknight tacos.",
+            "yield": "4 servings",
+            "prepTime": "15 min",
+            "cookTime": "10 min",
+            "totalTime": "",
+            "ingredients": "# Filling\n500 g beef\n# Salsa\n2 tomatoes",
+            "instructions": "Brown the beef.\n\nChop the tomatoes.",
+            "link": "https://example.com/tacos",
+            "favorite": true,
+        });
+
+        let entries = read_mela(recipe.to_string().as_bytes()).unwrap();
+        let recipe = entries[0].recipe.as_ref().unwrap();
+        assert_eq!((recipe.name.as_str(), recipe.servings, recipe.prep_time, recipe.rest_time), ("Tacos", 4, 15, 0));
+        assert_eq!(recipe.components.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["Filling", "Salsa"]);
+        assert_eq!((recipe.components[0].ingredients[0].quantity_numerator, recipe.components[0].ingredients[0].unit.as_str()), (500, "gram"));
+        assert_eq!(recipe.instructions[0].steps.len(), 2);
+        assert_eq!(recipe.source_url, "https://example.com/tacos");
+    }
+
+    #[test]
+    fn archives_keep_going_past_unreadable_recipes() {
+        let mut archive = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        archive.start_file("Tacos.melarecipe", SimpleFileOptions::default()).unwrap();
+        archive.write_all(json!({ "title": "Tacos", "ingredients": "2 tortillas" }).to_string().as_bytes()).unwrap();
+        archive.start_file("Broken.melarecipe", SimpleFileOptions::default()).unwrap();
+        archive.write_all(b"{").unwrap();
+        let bytes = archive.finish().unwrap().into_inner();
+
+        let entries = read_mela(&bytes).unwrap();
+        assert_eq!(entries.iter().map(|e| (e.name.as_str(), e.recipe.is_ok())).collect::<Vec<_>>(), [("Tacos", true), ("Broken", false)]);
+    }
+}
@@ -0,0 +1,190 @@
+//! Recipe file formats used by other apps, for importing and exporting recipes
+use regex::Regex;
+use crate::chopper::{
+    ChopperComponentIngredient,
+    ChopperInstructionStep,
+    ChopperRecipeComponent,
+    ChopperRecipeInstruction,
+};
+use crate::domain::UnitMatcher;
+
+pub mod archive;
+pub mod cooklang;
+pub mod icalendar;
+pub mod library;
+pub mod markdown;
+pub mod mealmaster;
+pub mod mela;
+pub mod paprika;
+
+/// Name for the ingredients listed before any heading
+const DEFAULT_COMPONENT_NAME: &str = "Ingredients";
+/// Title for the steps written before any heading
+const DEFAULT_INSTRUCTION_TITLE: &str = "Instructions";
+
+/// A line of an ingredient list or of the directions, once the format's own way of marking
+/// headings has been read
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ListLine {
+    Heading(String),
+    Item(String),
+}
+
+/// Groups ingredient lines into components, starting a new one at each heading
+pub(crate) fn components_from_lines(lines: Vec<ListLine>, units: &UnitMatcher) -> Vec<ChopperRecipeComponent> {
+    let mut components = vec![component(DEFAULT_COMPONENT_NAME)];
+    for line in lines {
+        match line {
+            ListLine::Heading(name) => components.push(component(&name)),
+            ListLine::Item(text) => {
+                let text = strip_list_marker(&text);
+                if !text.is_empty() {
+                    components
+                        .last_mut()
+                        .expect("There is always a component")
+                        .ingredients
+                        .push(ChopperComponentIngredient::from_line(text, units));
+                }
+            },
+        }
+    }
+    components.retain(|component| !component.ingredients.is_empty());
+
+    components
+}
+
+/// Groups steps into instructions, starting a new one at each heading
+pub(crate) fn instructions_from_lines(lines: Vec<ListLine>) -> Vec<ChopperRecipeInstruction> {
+    let mut instructions = vec![(DEFAULT_INSTRUCTION_TITLE.to_string(), Vec::new())];
+    for line in lines {
+        match line {
+            ListLine::Heading(title) => instructions.push((title, Vec::new())),
+            ListLine::Item(text) => {
+                let text = strip_list_marker(&text);
+                if !text.is_empty() {
+                    instructions.last_mut().expect("There is always an instruction").1.push(text.to_string());
+                }
+            },
+        }
+    }
+
+    instructions
+        .into_iter()
+        .filter(|(_, steps)| !steps.is_empty())
+        .enumerate()
+        .map(|(idx, (title, steps))| ChopperRecipeInstruction {
+            order_idx: idx as i32 + 1,
+            title,
+            steps: steps
+                .into_iter()
+                .enumerate()
+                .map(|(idx, content)| ChopperInstructionStep { step_number: idx as i32 + 1, content })
+                .collect(),
+        })
+        .collect()
+}
+
+fn component(name: &str) -> ChopperRecipeComponent {
+    ChopperRecipeComponent {
+        name: name.trim().trim_end_matches(':').trim().to_string(),
+        is_optional: false,
+        ingredients: Vec::new(),
+    }
+}
+
+/// Drops the "1." or "-" that lists are often written with
+fn strip_list_marker(line: &str) -> &str {
+    let re = Regex::new(r"^(\d+[.)]|[-*•])\s+").unwrap();
+    let line = line.trim();
+    match re.find(line) {
+        Some(marker) => line[marker.end()..].trim(),
+        None => line,
+    }
+}
+
+pub(crate) fn leading_number(value: &str) -> Option<i32> {
+    let digits: String = value.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// The first whole number anywhere in the text, for yields like "Serves 4"
+pub(crate) fn first_number(value: &str) -> Option<i32> {
+    let re = Regex::new(r"\d+").unwrap();
+    re.find(value).and_then(|number| number.as_str().parse().ok())
+}
+
+/// Reads durations like "20 minutes", "1 hour 30 minutes", "1h30m" or "45". Ranges like
+/// "10-15 minutes" count as their upper bound.
+pub(crate) fn minutes(value: &str) -> Option<i32> {
+    let lower_bound = Regex::new(r"(?i)\d+(?:\.\d+)?\s*(?:hours|hour|hrs|hr|h|minutes|minute|mins|min|m)?\s*(?:-|–|—|to)\s*(\d)").unwrap();
+    let value = lower_bound.replace_all(value, "${1}");
+    let re = Regex::new(r"(?i)(\d+(?:\.\d+)?)\s*(hours|hour|hrs|hr|h|minutes|minute|mins|min|m)?").unwrap();
+    let mut total = None;
+    for captures in re.captures_iter(&value) {
+        let amount: f64 = captures[1].parse().ok()?;
+        let factor = match captures.get(2).map(|unit| unit.as_str().to_lowercase()) {
+            Some(unit) if unit.starts_with('h') => 60.0,
+            _ => 1.0,
+        };
+        total = Some(total.unwrap_or(0.0) + amount * factor);
+    }
+
+    total.map(|total: f64| total.round() as i32)
+}
+
+/// Apps only record the total time besides prep and cook, so whatever is left over is
+/// counted as resting
+pub(crate) fn rest_minutes(total: Option<i32>, prep: i32, cook: i32) -> i32 {
+    total.map(|total| total.saturating_sub(prep).saturating_sub(cook).max(0)).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::unit::default_unit_matcher;
+    use super::{components_from_lines, instructions_from_lines, minutes, rest_minutes, ListLine};
+
+    #[test]
+    fn headings_start_new_components_and_instructions() {
+        let lines = vec![
+            ListLine::Item("2 cups rice".to_string()),
+            ListLine::Heading("For the sauce:".to_string()),
+            ListLine::Item("- 1 tbsp soy sauce".to_string()),
+        ];
+        let components = components_from_lines(lines, &default_unit_matcher());
+        assert_eq!(components.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["Ingredients", "For the sauce"]);
+        assert_eq!(components[1].ingredients[0].name, "soy sauce");
+
+        let lines = vec![
+            ListLine::Heading("Sauce".to_string()),
+            ListLine::Item("1. Whisk.".to_string()),
+            ListLine::Item("2) Simmer.".to_string()),
+        ];
+        let instructions = instructions_from_lines(lines);
+        assert_eq!(instructions.len(), 1);
+        assert_eq!((instructions[0].order_idx, instructions[0].title.as_str()), (1, "Sauce"));
+        assert_eq!(instructions[0].steps.iter().map(|s| (s.step_number, s.content.as_str())).collect::<Vec<_>>(), [(1, "Whisk."), (2, "Simmer.")]);
+    }
+
+    #[test]
+    fn durations_add_up_hours_and_minutes() {
+        assert_eq!(minutes("1 hour 30 minutes"), Some(90));
+        assert_eq!(minutes("1h30m"), Some(90));
+        assert_eq!(minutes("soon"), None);
+    }
+
+    #[test]
+    fn ranges_count_as_their_upper_bound() {
+        assert_eq!(minutes("10-15 minutes"), Some(15));
+        assert_eq!(minutes("10 to 15 mins"), Some(15));
+        assert_eq!(minutes("1–2 hours"), Some(120));
+        assert_eq!(minutes("1 hour - 1 hour 30 minutes"), Some(90));
+    }
+
+    #[test]
+    fn rest_is_what_the_total_leaves_over() {
+        assert_eq!(rest_minutes(Some(90), 20, 40), 30);
+        assert_eq!(rest_minutes(Some(30), 20, 40), 0);
+        assert_eq!(rest_minutes(None, 20, 40), 0);
+        assert_eq!(rest_minutes(Some(0), i32::MAX, i32::MAX), 0);
+    }
+}
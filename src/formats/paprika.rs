@@ -0,0 +1,176 @@
+//! Reads Paprika exports. A `.paprikarecipes` file is a zip archive holding one gzipped JSON
+//! `.paprikarecipe` per recipe, and a single `.paprikarecipe` is one of those on its own.
+//! Ingredients and directions are plain text with one item per line.
+use serde::Deserialize;
+use serde_json::Value;
+use crate::chopper::ChopperRecipe;
+use crate::models::unit::default_unit_matcher;
+use super::library::{archive_files, entry_name, gunzip, is_gzip, is_zip, LibraryEntry, LibraryError};
+use super::{components_from_lines, first_number, instructions_from_lines, minutes, rest_minutes, ListLine};
+
+/// Headings are the short lines ending in a colon, like "For the sauce:"
+const MAX_HEADING_LENGTH: usize = 50;
+
+/// The fields of a Paprika recipe that have a place in ours. Paprika writes `null` for
+/// anything left blank.
+#[derive(Debug, Deserialize)]
+struct PaprikaRecipe {
+    name: Option<String>,
+    ingredients: Option<String>,
+    directions: Option<String>,
+    description: Option<String>,
+    notes: Option<String>,
+    servings: Option<String>,
+    prep_time: Option<String>,
+    cook_time: Option<String>,
+    total_time: Option<String>,
+    source_url: Option<String>,
+}
+
+pub fn read_paprika(bytes: &[u8]) -> Result<Vec<LibraryEntry>, LibraryError> {
+    if !is_zip(bytes) {
+        return Ok(read_recipe_file("recipe", bytes));
+    }
+
+    Ok(archive_files(bytes)?
+        .into_iter()
+        .flat_map(|(path, contents)| match contents {
+            Ok(contents) => read_recipe_file(&path, &contents),
+            Err(err) => vec![LibraryEntry { name: entry_name(&path), recipe: Err(err) }],
+        })
+        .collect())
+}
+
+/// A file holds one recipe, though a list of them is read too
+fn read_recipe_file(path: &str, bytes: &[u8]) -> Vec<LibraryEntry> {
+    let failed = |err: String| vec![LibraryEntry { name: entry_name(path), recipe: Err(err) }];
+
+    let json = if is_gzip(bytes) {
+        match gunzip(bytes) {
+            Ok(json) => json,
+            Err(err) => return failed(format!("The recipe can't be unzipped: {}", err)),
+        }
+    } else {
+        bytes.to_vec()
+    };
+    let recipes = match serde_json::from_slice::<Value>(&json) {
+        Ok(Value::Array(recipes)) => recipes,
+        Ok(recipe) => vec![recipe],
+        Err(err) => return failed(format!("The recipe is not valid JSON: {}", err)),
+    };
+
+    recipes
+        .into_iter()
+        .map(|recipe| match serde_json::from_value::<PaprikaRecipe>(recipe) {
+            Ok(recipe) => LibraryEntry {
+                name: recipe.name.clone().unwrap_or_else(|| entry_name(path)),
+                recipe: Ok(to_chopper_recipe(recipe)),
+            },
+            Err(err) => LibraryEntry {
+                name: entry_name(path),
+                recipe: Err(format!("The recipe can't be read: {}", err)),
+            },
+        })
+        .collect()
+}
+
+fn to_chopper_recipe(recipe: PaprikaRecipe) -> ChopperRecipe {
+    let text = |field: &Option<String>| field.as_deref().unwrap_or_default().trim().to_string();
+    let prep_time = recipe.prep_time.as_deref().and_then(minutes).unwrap_or(0);
+    let cook_time = recipe.cook_time.as_deref().and_then(minutes).unwrap_or(0);
+    let total_time = recipe.total_time.as_deref().and_then(minutes);
+
+    let description = [text(&recipe.description), text(&recipe.notes)]
+        .into_iter()
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    ChopperRecipe {
+        description,
+        name: text(&recipe.name),
+        prep_time,
+        rest_time: rest_minutes(total_time, prep_time, cook_time),
+        cook_time,
+        servings: recipe.servings.as_deref().and_then(first_number).unwrap_or(0),
+        source_url: text(&recipe.source_url),
+        components: components_from_lines(list_lines(&text(&recipe.ingredients)), &default_unit_matcher()),
+        instructions: instructions_from_lines(list_lines(&text(&recipe.directions))),
+    }
+}
+
+fn list_lines(text: &str) -> Vec<ListLine> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let is_heading = line.ends_with(':')
+                && line.len() <= MAX_HEADING_LENGTH
+                && !line.chars().any(|c| c.is_ascii_digit());
+            if is_heading {
+                ListLine::Heading(line.to_string())
+            } else {
+                ListLine::Item(line.to_string())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use flate2::{write::GzEncoder, Compression};
+    use serde_json::json;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+    use super::read_paprika;
+
+    fn gzip(json: &serde_json::Value) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(json.to_string().as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn archives_are_read_recipe_by_recipe() {
+        let soup = json!({
+            "name": "Lentil Soup",
+            "ingredients": "1 cup lentils\n\nFor the topping:\n2 tbsp yogurt",
+            "directions": "Simmer the lentils.\nTop with yogurt.",
+            "description": null,
+            "notes": "Freezes well.",
+            "servings": "Serves 4",
+            "prep_time": "10 mins",
+            "cook_time": "30 mins",
+            "total_time": "1 hr",
+            "source_url": "https://example.com/lentil-soup",
+            "photo_data": null,
+        });
+
+        let mut archive = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        archive.start_file("Lentil Soup.paprikarecipe", SimpleFileOptions::default()).unwrap();
+        archive.write_all(&gzip(&soup)).unwrap();
+        archive.start_file("Broken.paprikarecipe", SimpleFileOptions::default()).unwrap();
+        archive.write_all(b"not gzip").unwrap();
+        let bytes = archive.finish().unwrap().into_inner();
+
+        let entries = read_paprika(&bytes).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let recipe = entries[0].recipe.as_ref().unwrap();
+        assert_eq!(recipe.name, "Lentil Soup");
+        assert_eq!(recipe.description, "Freezes well.");
+        assert_eq!((recipe.prep_time, recipe.cook_time, recipe.rest_time, recipe.servings), (10, 30, 20, 4));
+        assert_eq!(recipe.components.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["Ingredients", "For the topping"]);
+        assert_eq!(recipe.components[1].ingredients[0].unit, "tablespoon");
+        assert_eq!(recipe.instructions[0].steps.len(), 2);
+
+        assert_eq!(entries[1].name, "Broken");
+        assert!(entries[1].recipe.is_err());
+    }
+
+    #[test]
+    fn a_single_gzipped_recipe_is_read() {
+        let entries = read_paprika(&gzip(&json!({ "name": "Toast", "directions": "Toast it." }))).unwrap();
+        assert_eq!(entries[0].recipe.as_ref().unwrap().instructions[0].steps[0].content, "Toast it.");
+    }
+}
@@ -12,3 +12,6 @@ pub mod emailer;
 pub mod constants;
 pub mod models;
 pub mod middleware;
+pub mod chopper;
+pub mod formats;
+pub mod seeds;
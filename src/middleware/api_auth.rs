@@ -1,5 +1,5 @@
 use axum::{
-    http::{Request, StatusCode},
+    http::Request,
     middleware::Next,
     response::Response,
     body::Body,
@@ -7,12 +7,13 @@ use axum::{
 };
 use crate::startup::AppState;
 use crate::models::user::User;
+use crate::utils::ApiError;
 
 pub async fn api_key_auth(
     State(state): State<AppState>,
     mut req: Request<Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, ApiError> {
     let auth_header = req
         .headers()
         .get("Authorization")
@@ -22,16 +23,16 @@ pub async fn api_key_auth(
     match auth_header {
         Some(auth) if auth.starts_with("Bearer ") => {
             let api_key = &auth[7..];
-            if let Some(user) = User::find_by_api_key(&state.db, api_key).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+            if let Some(user) = User::find_by_api_key(&state.db, api_key).await? {
                 req.extensions_mut().insert(user);
                 Ok(next.run(req).await)
             } else {
-                Err(StatusCode::UNAUTHORIZED)
+                Err(ApiError::Unauthorized)
             }
         }
         _ => {
             println!("\n\n\nNO AUTHORIZATION TOKEN FOUND\n\n\n");
-            Err(StatusCode::UNAUTHORIZED)
+            Err(ApiError::Unauthorized)
         },
     }
 }
@@ -0,0 +1,139 @@
+use serde::{Serialize, Deserialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+use crate::models::recipe::{DuplicatePolicy, ImportOutcome};
+
+/// Jobs left running this long belong to a worker that stopped and are picked up again
+const STALE_AFTER_MINUTES: i32 = 10;
+/// Jobs that are still unfinished after this many tries are failed rather than picked up again
+pub const MAX_ATTEMPTS: i32 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ChopperJob {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub text: String,
+    pub source_url: String,
+    pub hostname: String,
+    pub pathname: String,
+    pub status: ChopperJobStatus,
+    pub on_duplicate: DuplicatePolicy,
+    /// Set once the job has succeeded
+    pub recipe_id: Option<i32>,
+    /// Set once the job has succeeded
+    pub outcome: Option<ImportOutcome>,
+    /// Why the job failed
+    pub error: Option<String>,
+    /// How many times a worker has picked the job up
+    pub attempts: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ChopperJobStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+pub struct CreateChopperJobParams {
+    pub user_id: Uuid,
+    pub text: String,
+    pub source_url: String,
+    pub hostname: String,
+    pub pathname: String,
+    pub on_duplicate: DuplicatePolicy,
+}
+
+impl ChopperJob {
+    pub async fn create(db: &PgPool, params: &CreateChopperJobParams) -> Result<Self, crate::models::Error> {
+        let job = sqlx::query_as(
+            "INSERT INTO chopper_jobs (id, user_id, text, source_url, hostname, pathname, on_duplicate)
+            VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *"
+        )
+        .bind(Uuid::new_v4())
+        .bind(params.user_id)
+        .bind(&params.text)
+        .bind(&params.source_url)
+        .bind(&params.hostname)
+        .bind(&params.pathname)
+        .bind(params.on_duplicate)
+        .fetch_one(db)
+        .await?;
+
+        Ok(job)
+    }
+
+    pub async fn find_for_user(db: &PgPool, user_id: &Uuid, id: &Uuid) -> Result<Option<Self>, crate::models::Error> {
+        let job = sqlx::query_as("SELECT * FROM chopper_jobs WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(job)
+    }
+
+    /// Marks the oldest waiting job as running and returns it. Jobs locked by another
+    /// worker are skipped, so several workers never take the same job.
+    pub async fn claim_next(db: &PgPool) -> Result<Option<Self>, crate::models::Error> {
+        let job = sqlx::query_as(
+            "UPDATE chopper_jobs SET status = 'running', attempts = attempts + 1
+            WHERE id = (
+                SELECT id FROM chopper_jobs
+                WHERE status = 'pending'
+                    OR (status = 'running' AND updated_at < NOW() - make_interval(mins => $1) AND attempts < $2)
+                ORDER BY created_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *"
+        )
+        .bind(STALE_AFTER_MINUTES)
+        .bind(MAX_ATTEMPTS)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(job)
+    }
+
+    /// Fails the jobs that stopped a worker on every one of their attempts, which
+    /// `claim_next` no longer picks up
+    pub async fn fail_abandoned(db: &PgPool) -> Result<u64, crate::models::Error> {
+        let result = sqlx::query(
+            "UPDATE chopper_jobs SET status = 'failed', error = $3
+            WHERE status = 'running' AND updated_at < NOW() - make_interval(mins => $1) AND attempts >= $2"
+        )
+        .bind(STALE_AFTER_MINUTES)
+        .bind(MAX_ATTEMPTS)
+        .bind(format!("Gave up after {} attempts", MAX_ATTEMPTS))
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn succeed(db: &PgPool, id: &Uuid, recipe_id: i32, outcome: ImportOutcome) -> Result<(), crate::models::Error> {
+        sqlx::query("UPDATE chopper_jobs SET status = 'succeeded', recipe_id = $2, outcome = $3, error = NULL WHERE id = $1")
+            .bind(id)
+            .bind(recipe_id)
+            .bind(outcome)
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn fail(db: &PgPool, id: &Uuid, error: &str) -> Result<(), crate::models::Error> {
+        sqlx::query("UPDATE chopper_jobs SET status = 'failed', error = $2 WHERE id = $1")
+            .bind(id)
+            .bind(error)
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+}
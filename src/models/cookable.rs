@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+use sqlx::{FromRow, PgPool};
+use crate::models::ingredient::Ingredient;
+
+/// An ingredient on hand, given by its id or by its name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IngredientRef {
+    Id(i32),
+    Name(String),
+}
+
+/// The user's recipes ranked by how much of what they need is on hand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CookableRecipes {
+    /// Names given that aren't the name of any ingredient
+    pub unknown_ingredients: Vec<String>,
+    pub recipes: Vec<CookableRecipe>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CookableRecipe {
+    pub recipe_id: i32,
+    pub name: String,
+    /// How many different ingredients the recipe needs
+    pub required: usize,
+    /// How many of those are on hand
+    pub covered: usize,
+    /// `covered` out of `required`, from 0 to 1. Recipes that need nothing are fully covered.
+    pub coverage: f64,
+    pub missing: Vec<MissingIngredient>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MissingIngredient {
+    pub ingredient_id: i32,
+    pub name: String,
+    /// The component that needs it first
+    pub component: String,
+}
+
+/// One row per required ingredient of each of the user's recipes, and a row without an
+/// ingredient for recipes that need nothing
+#[derive(Debug, Clone, FromRow)]
+struct CookableRow {
+    recipe_id: i32,
+    recipe_name: String,
+    ingredient_id: Option<i32>,
+    ingredient_name: Option<String>,
+    component: Option<String>,
+}
+
+impl CookableRecipes {
+    pub async fn from_ingredients(db: &PgPool, user_id: &uuid::Uuid, on_hand: &[IngredientRef]) -> Result<Self, crate::models::Error> {
+        let mut ingredient_ids: HashSet<i32> = HashSet::new();
+        let mut names: Vec<String> = Vec::new();
+        for ingredient in on_hand {
+            match ingredient {
+                IngredientRef::Id(id) => { ingredient_ids.insert(*id); },
+                IngredientRef::Name(name) => names.push(name.trim().to_string()),
+            }
+        }
+        let found = Ingredient::find_by_names(db, &names).await?;
+        let unknown_ingredients = names
+            .into_iter()
+            .filter(|name| !found.iter().any(|ingredient| ingredient.name.to_lowercase() == name.to_lowercase()))
+            .collect();
+        ingredient_ids.extend(found.iter().map(|ingredient| ingredient.id));
+
+        // Optional ingredients and everything in optional components can be left out, so
+        // only the rest are required
+        let rows: Vec<CookableRow> = sqlx::query_as(
+            "SELECT
+                 r.id AS recipe_id,
+                 r.name AS recipe_name,
+                 i.id AS ingredient_id,
+                 i.name AS ingredient_name,
+                 rc.name AS component
+             FROM recipes r
+             LEFT JOIN recipe_components rc ON rc.recipe_id = r.id AND NOT rc.is_optional
+             LEFT JOIN recipe_component_ingredients rci ON rci.recipe_component_id = rc.id AND NOT rci.is_optional
+             LEFT JOIN ingredients i ON i.id = rci.ingredient_id
+             WHERE r.user_id = $1
+             ORDER BY r.id, rc.id, rci.id"
+        )
+        .bind(user_id)
+        .fetch_all(db)
+        .await?;
+
+        Ok(Self {
+            unknown_ingredients,
+            recipes: rank_rows(rows, &ingredient_ids),
+        })
+    }
+}
+
+/// Works out what each recipe is missing and puts the best covered recipes first, then
+/// those missing the fewest ingredients, then sorts by name
+fn rank_rows(rows: Vec<CookableRow>, on_hand: &HashSet<i32>) -> Vec<CookableRecipe> {
+    let mut recipes: Vec<(CookableRecipe, HashSet<i32>)> = Vec::new();
+    for row in rows {
+        if recipes.last().map(|(recipe, _)| recipe.recipe_id) != Some(row.recipe_id) {
+            recipes.push((CookableRecipe {
+                recipe_id: row.recipe_id,
+                name: row.recipe_name,
+                required: 0,
+                covered: 0,
+                coverage: 1.0,
+                missing: Vec::new(),
+            }, HashSet::new()));
+        }
+        let (recipe, required) = recipes.last_mut().expect("A recipe was just added");
+        let Some(ingredient_id) = row.ingredient_id else {
+            continue;
+        };
+        // Ingredients used by several components are only needed once
+        if !required.insert(ingredient_id) {
+            continue;
+        }
+        recipe.required += 1;
+        if on_hand.contains(&ingredient_id) {
+            recipe.covered += 1;
+        } else {
+            recipe.missing.push(MissingIngredient {
+                ingredient_id,
+                name: row.ingredient_name.unwrap_or_default(),
+                component: row.component.unwrap_or_default(),
+            });
+        }
+    }
+
+    let mut recipes: Vec<CookableRecipe> = recipes
+        .into_iter()
+        .map(|(mut recipe, _)| {
+            if recipe.required > 0 {
+                recipe.coverage = recipe.covered as f64 / recipe.required as f64;
+            }
+            recipe
+        })
+        .collect();
+    recipes.sort_by(|a, b| {
+        b.coverage
+            .total_cmp(&a.coverage)
+            .then(a.missing.len().cmp(&b.missing.len()))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    recipes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::{rank_rows, CookableRow, IngredientRef};
+
+    fn row(recipe_id: i32, ingredient_id: Option<i32>) -> CookableRow {
+        CookableRow {
+            recipe_id,
+            recipe_name: format!("recipe {}", recipe_id),
+            ingredient_id,
+            ingredient_name: ingredient_id.map(|id| format!("ingredient {}", id)),
+            component: Some("Main".to_string()),
+        }
+    }
+
+    #[test]
+    fn best_covered_recipes_come_first() {
+        let rows = vec![row(1, Some(1)), row(1, Some(2)), row(2, Some(1)), row(3, Some(3)), row(3, Some(4))];
+        let recipes = rank_rows(rows, &HashSet::from([1]));
+        assert_eq!(recipes.iter().map(|r| (r.recipe_id, r.covered, r.required)).collect::<Vec<_>>(), [(2, 1, 1), (1, 1, 2), (3, 0, 2)]);
+        assert_eq!(recipes[1].coverage, 0.5);
+        assert_eq!(recipes[1].missing.iter().map(|m| m.ingredient_id).collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn fewer_missing_ingredients_break_ties() {
+        let rows = vec![row(1, Some(1)), row(1, Some(2)), row(1, Some(3)), row(2, Some(4))];
+        let recipes = rank_rows(rows, &HashSet::new());
+        assert_eq!(recipes.iter().map(|r| r.recipe_id).collect::<Vec<_>>(), [2, 1]);
+    }
+
+    #[test]
+    fn ingredients_are_counted_once_and_recipes_needing_nothing_are_covered() {
+        let rows = vec![row(1, Some(1)), row(1, Some(1)), row(2, None)];
+        let recipes = rank_rows(rows, &HashSet::new());
+        assert_eq!((recipes[0].recipe_id, recipes[0].coverage, recipes[0].required), (2, 1.0, 0));
+        assert_eq!((recipes[1].required, recipes[1].missing.len()), (1, 1));
+    }
+
+    #[test]
+    fn ingredients_are_given_by_id_or_name() {
+        let on_hand: Vec<IngredientRef> = serde_json::from_str(r#"[3, "garlic"]"#).unwrap();
+        assert_eq!(on_hand, [IngredientRef::Id(3), IngredientRef::Name("garlic".to_string())]);
+    }
+}
@@ -23,7 +23,10 @@ impl CreateIngredientParams {
 }
 
 impl Ingredient {
-    pub async fn create(db: &PgPool, params: &CreateIngredientParams) -> Result<Self, crate::models::Error> {
+    pub async fn create<'e, E>(db: E, params: &CreateIngredientParams) -> Result<Self, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let ingredient = sqlx::query_as(
             "INSERT INTO ingredients (name, description) VALUES ($1, $2) RETURNING *"
         )
@@ -46,7 +49,10 @@ impl Ingredient {
         Ok(ingredient)
     }
 
-    pub async fn find_by_name(db: &PgPool, name: &str) -> Result<Option<Self>, crate::models::Error> {
+    pub async fn find_by_name<'e, E>(db: E, name: &str) -> Result<Option<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let ingredient = sqlx::query_as(
             "SELECT * FROM ingredients WHERE name = $1"
         )
@@ -102,6 +108,19 @@ impl Ingredient {
         Ok(ingredients)
     }
 
+    /// The ingredients with any of these names, ignoring case
+    pub async fn find_by_names(db: &PgPool, names: &[String]) -> Result<Vec<Self>, crate::models::Error> {
+        let names: Vec<String> = names.iter().map(|name| name.trim().to_lowercase()).collect();
+        let ingredients = sqlx::query_as(
+            "SELECT * FROM ingredients WHERE LOWER(name) = ANY($1) ORDER BY id"
+        )
+        .bind(&names)
+        .fetch_all(db)
+        .await?;
+
+        Ok(ingredients)
+    }
+
     pub async fn search(db: &PgPool, query: &str, limit: i32) -> Result<Vec<Self>, crate::models::Error> {
         let ingredients = sqlx::query_as(
             "SELECT * FROM ingredients WHERE name ILIKE $1 ORDER BY name LIMIT $2"
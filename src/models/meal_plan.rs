@@ -0,0 +1,298 @@
+use serde::{Serialize, Deserialize};
+use sqlx::{FromRow, PgPool};
+use std::str::FromStr;
+use time::{Date, Duration, OffsetDateTime, Time};
+
+/// The meals of a day that recipes can be planned for, in the order they are eaten
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MealSlot {
+    Breakfast,
+    Lunch,
+    Dinner,
+}
+
+impl MealSlot {
+    pub const ALL: [MealSlot; 3] = [MealSlot::Breakfast, MealSlot::Lunch, MealSlot::Dinner];
+
+    /// When the meal is on the table, in the eater's own time zone
+    pub fn served_at(&self) -> Time {
+        let (hour, minute) = match self {
+            MealSlot::Breakfast => (8, 0),
+            MealSlot::Lunch => (12, 30),
+            MealSlot::Dinner => (19, 0),
+        };
+
+        Time::from_hms(hour, minute, 0).expect("Meal times are valid times")
+    }
+}
+
+impl FromStr for MealSlot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "breakfast" => Ok(MealSlot::Breakfast),
+            "lunch" => Ok(MealSlot::Lunch),
+            "dinner" => Ok(MealSlot::Dinner),
+            _ => Err(format!("Invalid meal: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for MealSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MealSlot::Breakfast => write!(f, "breakfast"),
+            MealSlot::Lunch => write!(f, "lunch"),
+            MealSlot::Dinner => write!(f, "dinner"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MealPlan {
+    pub id: i32,
+    pub user_id: uuid::Uuid,
+    pub recipe_id: i32,
+    pub planned_on: Date,
+    pub meal: MealSlot,
+    /// Overrides the recipe's own serving count
+    pub servings: Option<i32>,
+}
+
+pub struct CreateMealPlanParams {
+    pub user_id: uuid::Uuid,
+    pub recipe_id: i32,
+    pub planned_on: Date,
+    pub meal: MealSlot,
+    pub servings: Option<i32>,
+}
+
+impl CreateMealPlanParams {
+    pub fn new(user_id: uuid::Uuid, recipe_id: i32, planned_on: Date, meal: MealSlot) -> Self {
+        Self {
+            user_id,
+            recipe_id,
+            planned_on,
+            meal,
+            servings: None,
+        }
+    }
+
+    pub fn with_servings(mut self, servings: i32) -> Self {
+        self.servings = Some(servings);
+        self
+    }
+}
+
+/// A planned meal with the name of its recipe, as the calendar shows it
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MealPlanEntry {
+    pub id: i32,
+    pub recipe_id: i32,
+    pub recipe_name: String,
+    pub planned_on: Date,
+    pub meal: MealSlot,
+    pub servings: Option<i32>,
+}
+
+/// A planned meal with the recipe's times, as the calendar feed shows it
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MealPlanEvent {
+    pub id: i32,
+    pub recipe_id: i32,
+    pub recipe_name: String,
+    pub planned_on: Date,
+    pub meal: MealSlot,
+    pub servings: Option<i32>,
+    pub prep_time: Option<i32>,
+    pub cook_time: Option<i32>,
+    pub rest_time: Option<i32>,
+    pub updated_at: OffsetDateTime,
+}
+
+/// One day of the calendar with what is planned for each of its meals
+#[derive(Debug, Clone, Serialize)]
+pub struct MealPlanDay {
+    pub date: Date,
+    pub weekday: String,
+    pub meals: Vec<PlannedMeal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedMeal {
+    pub meal: MealSlot,
+    pub entries: Vec<MealPlanEntry>,
+}
+
+impl MealPlan {
+    /// Returns None if the user has no such recipe
+    pub async fn create(db: &PgPool, params: &CreateMealPlanParams) -> Result<Option<Self>, crate::models::Error> {
+        let meal_plan = sqlx::query_as(
+            "INSERT INTO meal_plans (user_id, recipe_id, planned_on, meal, servings)
+             SELECT $1, r.id, $3, $4, $5 FROM recipes r WHERE r.id = $2 AND r.user_id = $1
+             RETURNING *"
+        )
+        .bind(params.user_id)
+        .bind(params.recipe_id)
+        .bind(params.planned_on)
+        .bind(params.meal)
+        .bind(params.servings)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(meal_plan)
+    }
+
+    /// Answers the removed meal, or None if the user has no such meal planned
+    pub async fn delete(db: &PgPool, user_id: &uuid::Uuid, meal_plan_id: i32) -> Result<Option<Self>, crate::models::Error> {
+        let meal_plan = sqlx::query_as("DELETE FROM meal_plans WHERE id = $1 AND user_id = $2 RETURNING *")
+            .bind(meal_plan_id)
+            .bind(user_id)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(meal_plan)
+    }
+
+    /// What the user has planned from `from` to `to`, both included
+    pub async fn entries_between(db: &PgPool, user_id: &uuid::Uuid, from: Date, to: Date) -> Result<Vec<MealPlanEntry>, crate::models::Error> {
+        let entries = sqlx::query_as(
+            "SELECT mp.id, mp.recipe_id, r.name AS recipe_name, mp.planned_on, mp.meal, mp.servings
+             FROM meal_plans mp
+             JOIN recipes r ON r.id = mp.recipe_id
+             WHERE mp.user_id = $1 AND mp.planned_on BETWEEN $2 AND $3
+             ORDER BY mp.planned_on, mp.id"
+        )
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(db)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Every meal the user planned on or after `since`
+    pub async fn events_since(db: &PgPool, user_id: &uuid::Uuid, since: Date) -> Result<Vec<MealPlanEvent>, crate::models::Error> {
+        let events = sqlx::query_as(
+            "SELECT mp.id, mp.recipe_id, r.name AS recipe_name, mp.planned_on, mp.meal, mp.servings,
+                    r.prep_time, r.cook_time, r.rest_time, mp.updated_at
+             FROM meal_plans mp
+             JOIN recipes r ON r.id = mp.recipe_id
+             WHERE mp.user_id = $1 AND mp.planned_on >= $2
+             ORDER BY mp.planned_on, mp.id"
+        )
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(db)
+        .await?;
+
+        Ok(events)
+    }
+}
+
+/// The Monday of the week `date` is in
+pub fn week_start(date: Date) -> Date {
+    date - Duration::days(date.weekday().number_days_from_monday() as i64)
+}
+
+/// The first and last day of the month `date` is in
+pub fn month_bounds(date: Date) -> (Date, Date) {
+    let last_day = time::util::days_in_year_month(date.year(), date.month());
+
+    (
+        date.replace_day(1).expect("Every month has a first day"),
+        date.replace_day(last_day).expect("Every month has its last day"),
+    )
+}
+
+/// Lays out every day from `from` to `to` with the entries planned for each of its meals
+pub fn calendar_days(from: Date, to: Date, entries: &[MealPlanEntry]) -> Vec<MealPlanDay> {
+    let mut days = Vec::new();
+    let mut date = from;
+    while date <= to {
+        days.push(MealPlanDay {
+            date,
+            weekday: date.weekday().to_string(),
+            meals: MealSlot::ALL
+                .iter()
+                .map(|meal| PlannedMeal {
+                    meal: *meal,
+                    entries: entries
+                        .iter()
+                        .filter(|entry| entry.planned_on == date && entry.meal == *meal)
+                        .cloned()
+                        .collect(),
+                })
+                .collect(),
+        });
+        date = match date.next_day() {
+            Some(next_day) => next_day,
+            None => break,
+        };
+    }
+
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{calendar_days, month_bounds, week_start, MealPlanEntry, MealSlot};
+    use time::{Date, Month};
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    fn entry(id: i32, planned_on: Date, meal: MealSlot) -> MealPlanEntry {
+        MealPlanEntry {
+            id,
+            recipe_id: id,
+            recipe_name: format!("recipe {}", id),
+            planned_on,
+            meal,
+            servings: None,
+        }
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        assert_eq!(week_start(date(2025, Month::August, 7)), date(2025, Month::August, 4));
+        assert_eq!(week_start(date(2025, Month::August, 4)), date(2025, Month::August, 4));
+        assert_eq!(week_start(date(2025, Month::August, 3)), date(2025, Month::July, 28));
+    }
+
+    #[test]
+    fn months_run_from_their_first_to_last_day() {
+        assert_eq!(month_bounds(date(2024, Month::February, 10)), (date(2024, Month::February, 1), date(2024, Month::February, 29)));
+        assert_eq!(month_bounds(date(2025, Month::December, 31)), (date(2025, Month::December, 1), date(2025, Month::December, 31)));
+    }
+
+    #[test]
+    fn entries_are_placed_on_their_day_and_meal() {
+        let monday = date(2025, Month::August, 4);
+        let entries = vec![
+            entry(1, date(2025, Month::August, 5), MealSlot::Dinner),
+            entry(2, date(2025, Month::August, 5), MealSlot::Breakfast),
+            entry(3, date(2025, Month::August, 11), MealSlot::Lunch),
+        ];
+        let days = calendar_days(monday, date(2025, Month::August, 10), &entries);
+        assert_eq!(days.len(), 7);
+        assert_eq!(days[1].weekday, "Tuesday");
+        assert_eq!(days[1].meals.iter().map(|planned| planned.meal).collect::<Vec<_>>(), MealSlot::ALL);
+        assert_eq!(days[1].meals[0].entries.iter().map(|entry| entry.id).collect::<Vec<_>>(), [2]);
+        assert!(days[1].meals[1].entries.is_empty());
+        assert_eq!(days[1].meals[2].entries.iter().map(|entry| entry.id).collect::<Vec<_>>(), [1]);
+        assert!(days.iter().all(|day| day.meals.iter().all(|planned| planned.entries.iter().all(|entry| entry.id != 3))));
+    }
+
+    #[test]
+    fn meals_are_read_case_insensitively() {
+        assert_eq!("Dinner".parse::<MealSlot>(), Ok(MealSlot::Dinner));
+        assert!("brunch".parse::<MealSlot>().is_err());
+    }
+}
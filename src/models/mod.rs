@@ -1,4 +1,9 @@
+pub mod chopper_job;
+pub mod cookable;
 pub mod ingredient;
+pub mod meal_plan;
+pub mod nutrition;
+pub mod pantry_item;
 pub mod recipe;
 pub mod recipe_component;
 pub mod recipe_component_ingredient;
@@ -9,6 +14,7 @@ pub mod unit;
 pub mod recipe_instruction;
 pub mod recipe_instruction_step;
 pub mod recipe_tag;
+pub mod shopping_list;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -20,4 +26,21 @@ pub enum Error {
 
     #[error("Resource not found")]
     Serde(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Quantity(#[from] crate::domain::QuantityError),
+
+    #[error("Unknown unit {0}")]
+    UnknownUnit(String),
+}
+
+impl Error {
+    /// Whether the write lost a race with another one saving the user's first recipe from
+    /// the same page
+    pub fn is_duplicate_source_url(&self) -> bool {
+        matches!(
+            self,
+            Error::Sqlx(sqlx::Error::Database(err)) if err.constraint() == Some("idx_recipes_user_id_normalized_source_url")
+        )
+    }
 }
@@ -0,0 +1,553 @@
+use serde::{Serialize, Deserialize};
+use sqlx::{FromRow, PgPool};
+use crate::domain::Quantity;
+use crate::models::recipe::FullRecipeDetails;
+use crate::models::unit::{UnitConverter, UnitDimension};
+
+/// Nutrition facts for common foods, based on USDA FoodData Central (SR Legacy), per 100 g
+const DEFAULT_NUTRITION_FACTS: &str = include_str!("../../data/nutrition.csv");
+/// The columns of the nutrition facts CSV, in order
+const CSV_COLUMNS: [&str; 17] = [
+    "name", "aliases", "calories", "protein_g", "fat_g", "saturated_fat_g", "carbohydrate_g", "fiber_g",
+    "sugar_g", "sodium_mg", "calcium_mg", "iron_mg", "potassium_mg", "vitamin_c_mg", "grams_per_cup",
+    "grams_per_piece", "grams_per_can",
+];
+const CUP_MILLILITERS: f64 = 236.5882365;
+/// A sixteenth of a teaspoon
+const PINCH_MILLILITERS: f64 = 0.30805759960937;
+
+/// Amounts of each nutrient, per 100 g for a food and in total for a recipe
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct Nutrients {
+    pub calories: f64,
+    pub protein_grams: f64,
+    pub fat_grams: f64,
+    pub saturated_fat_grams: f64,
+    pub carbohydrate_grams: f64,
+    pub fiber_grams: f64,
+    pub sugar_grams: f64,
+    pub sodium_milligrams: f64,
+    pub calcium_milligrams: f64,
+    pub iron_milligrams: f64,
+    pub potassium_milligrams: f64,
+    pub vitamin_c_milligrams: f64,
+}
+
+impl Nutrients {
+    fn values(&self) -> [f64; 12] {
+        [
+            self.calories,
+            self.protein_grams,
+            self.fat_grams,
+            self.saturated_fat_grams,
+            self.carbohydrate_grams,
+            self.fiber_grams,
+            self.sugar_grams,
+            self.sodium_milligrams,
+            self.calcium_milligrams,
+            self.iron_milligrams,
+            self.potassium_milligrams,
+            self.vitamin_c_milligrams,
+        ]
+    }
+
+    fn values_mut(&mut self) -> [&mut f64; 12] {
+        [
+            &mut self.calories,
+            &mut self.protein_grams,
+            &mut self.fat_grams,
+            &mut self.saturated_fat_grams,
+            &mut self.carbohydrate_grams,
+            &mut self.fiber_grams,
+            &mut self.sugar_grams,
+            &mut self.sodium_milligrams,
+            &mut self.calcium_milligrams,
+            &mut self.iron_milligrams,
+            &mut self.potassium_milligrams,
+            &mut self.vitamin_c_milligrams,
+        ]
+    }
+
+    /// Adds `factor` times `other`
+    fn add_scaled(&mut self, other: &Nutrients, factor: f64) {
+        for (value, other_value) in self.values_mut().into_iter().zip(other.values()) {
+            *value += other_value * factor;
+        }
+    }
+
+    fn scaled(&self, factor: f64) -> Nutrients {
+        let mut scaled = Nutrients::default();
+        scaled.add_scaled(self, factor);
+
+        scaled
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct NutritionFact {
+    pub id: i32,
+    pub name: String,
+    /// Other names the food goes by in recipes
+    pub aliases: Vec<String>,
+    /// Per 100 g
+    #[sqlx(flatten)]
+    pub nutrients: Nutrients,
+    pub grams_per_cup: Option<f64>,
+    pub grams_per_piece: Option<f64>,
+    pub grams_per_can: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveNutritionFactParams {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub nutrients: Nutrients,
+    pub grams_per_cup: Option<f64>,
+    pub grams_per_piece: Option<f64>,
+    pub grams_per_can: Option<f64>,
+}
+
+/// An ingredient that has nutrition facts, but in a unit they don't give a weight for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnconvertedIngredient {
+    pub name: String,
+    pub unit: String,
+}
+
+/// What a recipe adds up to, and which of its ingredients couldn't be counted.
+/// Optional ingredients are left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecipeNutrition {
+    /// What the total is divided by for `per_serving`. None when the recipe doesn't say how
+    /// many it serves, in which case a serving is the whole recipe.
+    pub servings: Option<i32>,
+    pub total: Nutrients,
+    pub per_serving: Nutrients,
+    /// Ingredients without nutrition facts
+    pub unmatched: Vec<String>,
+    pub unconverted: Vec<UnconvertedIngredient>,
+}
+
+impl NutritionFact {
+    /// Adds the food, or replaces what is known about it
+    pub async fn save(db: &PgPool, params: &SaveNutritionFactParams) -> Result<Self, crate::models::Error> {
+        let nutrients = &params.nutrients;
+        let nutrition_fact = sqlx::query_as(
+            "INSERT INTO nutrition_facts (
+                 name, aliases, calories, protein_grams, fat_grams, saturated_fat_grams, carbohydrate_grams,
+                 fiber_grams, sugar_grams, sodium_milligrams, calcium_milligrams, iron_milligrams,
+                 potassium_milligrams, vitamin_c_milligrams, grams_per_cup, grams_per_piece, grams_per_can
+             )
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+             ON CONFLICT (name) DO UPDATE SET
+                 aliases = EXCLUDED.aliases,
+                 calories = EXCLUDED.calories,
+                 protein_grams = EXCLUDED.protein_grams,
+                 fat_grams = EXCLUDED.fat_grams,
+                 saturated_fat_grams = EXCLUDED.saturated_fat_grams,
+                 carbohydrate_grams = EXCLUDED.carbohydrate_grams,
+                 fiber_grams = EXCLUDED.fiber_grams,
+                 sugar_grams = EXCLUDED.sugar_grams,
+                 sodium_milligrams = EXCLUDED.sodium_milligrams,
+                 calcium_milligrams = EXCLUDED.calcium_milligrams,
+                 iron_milligrams = EXCLUDED.iron_milligrams,
+                 potassium_milligrams = EXCLUDED.potassium_milligrams,
+                 vitamin_c_milligrams = EXCLUDED.vitamin_c_milligrams,
+                 grams_per_cup = EXCLUDED.grams_per_cup,
+                 grams_per_piece = EXCLUDED.grams_per_piece,
+                 grams_per_can = EXCLUDED.grams_per_can
+             RETURNING *"
+        )
+        .bind(&params.name)
+        .bind(&params.aliases)
+        .bind(nutrients.calories)
+        .bind(nutrients.protein_grams)
+        .bind(nutrients.fat_grams)
+        .bind(nutrients.saturated_fat_grams)
+        .bind(nutrients.carbohydrate_grams)
+        .bind(nutrients.fiber_grams)
+        .bind(nutrients.sugar_grams)
+        .bind(nutrients.sodium_milligrams)
+        .bind(nutrients.calcium_milligrams)
+        .bind(nutrients.iron_milligrams)
+        .bind(nutrients.potassium_milligrams)
+        .bind(nutrients.vitamin_c_milligrams)
+        .bind(params.grams_per_cup)
+        .bind(params.grams_per_piece)
+        .bind(params.grams_per_can)
+        .fetch_one(db)
+        .await?;
+
+        Ok(nutrition_fact)
+    }
+
+    pub async fn list(db: &PgPool) -> Result<Vec<Self>, crate::models::Error> {
+        let nutrition_facts = sqlx::query_as("SELECT * FROM nutrition_facts ORDER BY name")
+            .fetch_all(db)
+            .await?;
+
+        Ok(nutrition_facts)
+    }
+
+    /// How many grams `quantity` of the unit named `unit` weighs, if it can be known
+    fn grams(&self, quantity: &Quantity, unit: &str, converter: &UnitConverter) -> Option<f64> {
+        let unit = converter.find(unit)?;
+        let amount = quantity.to_f64();
+        match (&unit.dimension, unit.name.as_str()) {
+            (UnitDimension::Mass, _) => unit.base_factor.map(|grams| amount * grams),
+            (UnitDimension::Volume, _) => Some(amount * unit.base_factor? * self.grams_per_cup? / CUP_MILLILITERS),
+            (UnitDimension::Count, "pinch") => self.grams_per_cup.map(|grams_per_cup| amount * PINCH_MILLILITERS * grams_per_cup / CUP_MILLILITERS),
+            (UnitDimension::Count, "piece") => self.grams_per_piece.map(|grams| amount * grams),
+            (UnitDimension::Count, "can") => self.grams_per_can.map(|grams| amount * grams),
+            (UnitDimension::Count, _) => None,
+        }
+    }
+}
+
+impl RecipeNutrition {
+    pub async fn for_recipe(db: &PgPool, recipe: &FullRecipeDetails) -> Result<Self, crate::models::Error> {
+        let nutrition_facts = NutritionFact::list(db).await?;
+        let converter = UnitConverter::load(db).await?;
+
+        Ok(recipe_nutrition(recipe, &nutrition_facts, &converter))
+    }
+}
+
+/// Saves the foods in `data/nutrition.csv`, updating the ones saved before
+pub async fn create_default_nutrition_facts(db: &PgPool) -> Result<(), crate::models::Error> {
+    let nutrition_facts = parse_nutrition_csv(DEFAULT_NUTRITION_FACTS).expect("The bundled nutrition facts are valid");
+    for params in nutrition_facts {
+        NutritionFact::save(db, &params).await?;
+    }
+
+    Ok(())
+}
+
+/// Reads nutrition facts with the columns in `CSV_COLUMNS`. Aliases are separated by
+/// semicolons and the gram weights may be left empty.
+pub fn parse_nutrition_csv(text: &str) -> Result<Vec<SaveNutritionFactParams>, String> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    match lines.next() {
+        Some((_, header)) if split_csv_line(header) == CSV_COLUMNS => {},
+        _ => return Err(format!("The header should be {}", CSV_COLUMNS.join(","))),
+    }
+
+    lines
+        .map(|(index, line)| {
+            let line_number = index + 1;
+            let fields = split_csv_line(line);
+            if fields.len() != CSV_COLUMNS.len() {
+                return Err(format!("Line {} has {} columns instead of {}", line_number, fields.len(), CSV_COLUMNS.len()));
+            }
+            let number = |column: usize| -> Result<f64, String> {
+                fields[column].trim().parse::<f64>()
+                    .map_err(|_| format!("Line {}: {} is not a number for {}", line_number, fields[column], CSV_COLUMNS[column]))
+            };
+            let weight = |column: usize| -> Result<Option<f64>, String> {
+                match fields[column].trim() {
+                    "" => Ok(None),
+                    _ => number(column).map(Some),
+                }
+            };
+
+            Ok(SaveNutritionFactParams {
+                name: fields[0].trim().to_string(),
+                aliases: fields[1].split(';').map(str::trim).filter(|alias| !alias.is_empty()).map(str::to_string).collect(),
+                nutrients: Nutrients {
+                    calories: number(2)?,
+                    protein_grams: number(3)?,
+                    fat_grams: number(4)?,
+                    saturated_fat_grams: number(5)?,
+                    carbohydrate_grams: number(6)?,
+                    fiber_grams: number(7)?,
+                    sugar_grams: number(8)?,
+                    sodium_milligrams: number(9)?,
+                    calcium_milligrams: number(10)?,
+                    iron_milligrams: number(11)?,
+                    potassium_milligrams: number(12)?,
+                    vitamin_c_milligrams: number(13)?,
+                },
+                grams_per_cup: weight(14)?,
+                grams_per_piece: weight(15)?,
+                grams_per_can: weight(16)?,
+            })
+        })
+        .collect()
+}
+
+/// Splits a line on commas outside double quotes. Quotes are doubled inside quoted fields.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut in_quotes = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().expect("There is always a field").push('"');
+            },
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(String::new()),
+            c => fields.last_mut().expect("There is always a field").push(c),
+        }
+    }
+
+    fields
+}
+
+/// The words of a food or ingredient name, lowercased and singular, without anything in
+/// parentheses, so that "Tomatoes (diced)" and "tomato" read the same
+fn name_words(name: &str) -> Vec<String> {
+    let mut depth = 0;
+    let mut text = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = (depth - 1).max(0),
+            c if depth == 0 && c.is_alphanumeric() => text.extend(c.to_lowercase()),
+            _ if depth == 0 => text.push(' '),
+            _ => {},
+        }
+    }
+
+    text.split_whitespace().map(singular).collect()
+}
+
+fn singular(word: &str) -> String {
+    if let Some(stem) = word.strip_suffix("ies") {
+        format!("{}y", stem)
+    } else if let Some(stem) = word.strip_suffix("oes") {
+        format!("{}o", stem)
+    } else if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+        word[..word.len() - 1].to_string()
+    } else {
+        word.to_string()
+    }
+}
+
+/// The food whose name or alias appears in the ingredient's name. The longest match wins,
+/// then the one nearest the end, since "bread flour" is flour.
+fn find_nutrition_fact<'a>(ingredient_name: &str, nutrition_facts: &'a [NutritionFact]) -> Option<&'a NutritionFact> {
+    let ingredient_words = name_words(ingredient_name);
+    let mut best: Option<(usize, usize, &NutritionFact)> = None;
+    for nutrition_fact in nutrition_facts {
+        for food_name in std::iter::once(&nutrition_fact.name).chain(&nutrition_fact.aliases) {
+            let food_words = name_words(food_name);
+            if food_words.is_empty() || food_words.len() > ingredient_words.len() {
+                continue;
+            }
+            let end = ingredient_words
+                .windows(food_words.len())
+                .rposition(|window| window == food_words.as_slice())
+                .map(|start| start + food_words.len());
+            if let Some(end) = end {
+                let is_better = match best {
+                    Some((length, best_end, _)) => (food_words.len(), end) > (length, best_end),
+                    None => true,
+                };
+                if is_better {
+                    best = Some((food_words.len(), end, nutrition_fact));
+                }
+            }
+        }
+    }
+
+    best.map(|(_, _, nutrition_fact)| nutrition_fact)
+}
+
+/// Adds up the recipe's required ingredients. Ingredients are listed as unmatched or
+/// unconverted once, however many components use them.
+pub fn recipe_nutrition(recipe: &FullRecipeDetails, nutrition_facts: &[NutritionFact], converter: &UnitConverter) -> RecipeNutrition {
+    let mut nutrition = RecipeNutrition {
+        servings: recipe.servings.filter(|servings| *servings > 0),
+        ..RecipeNutrition::default()
+    };
+    let ingredients = recipe.recipe_components
+        .iter()
+        .filter(|component| !component.is_optional)
+        .flat_map(|component| &component.component_ingredients)
+        .filter(|ingredient| !ingredient.is_optional);
+    for ingredient in ingredients {
+        let nutrition_fact = match find_nutrition_fact(&ingredient.name, nutrition_facts) {
+            Some(nutrition_fact) => nutrition_fact,
+            None => {
+                if !nutrition.unmatched.contains(&ingredient.name) {
+                    nutrition.unmatched.push(ingredient.name.clone());
+                }
+                continue;
+            },
+        };
+        match nutrition_fact.grams(&ingredient.quantity, &ingredient.unit, converter) {
+            Some(grams) => nutrition.total.add_scaled(&nutrition_fact.nutrients, grams / 100.0),
+            None => {
+                let unconverted = UnconvertedIngredient { name: ingredient.name.clone(), unit: ingredient.unit.clone() };
+                if !nutrition.unconverted.contains(&unconverted) {
+                    nutrition.unconverted.push(unconverted);
+                }
+            },
+        }
+    }
+    nutrition.per_serving = nutrition.total.scaled(1.0 / f64::from(nutrition.servings.unwrap_or(1)));
+
+    nutrition
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_nutrition_fact, name_words, parse_nutrition_csv, recipe_nutrition, split_csv_line, NutritionFact, UnconvertedIngredient, DEFAULT_NUTRITION_FACTS};
+    use crate::domain::Quantity;
+    use crate::models::recipe::{FullRecipeComponent, FullRecipeComponentIngredient, FullRecipeDetails};
+    use crate::models::unit::{Unit, UnitConverter, UnitDimension, UnitSystem};
+
+    fn nutrition_facts() -> Vec<NutritionFact> {
+        parse_nutrition_csv(DEFAULT_NUTRITION_FACTS)
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .map(|(index, params)| NutritionFact {
+                id: index as i32,
+                name: params.name,
+                aliases: params.aliases,
+                nutrients: params.nutrients,
+                grams_per_cup: params.grams_per_cup,
+                grams_per_piece: params.grams_per_piece,
+                grams_per_can: params.grams_per_can,
+            })
+            .collect()
+    }
+
+    fn unit(name: &str, dimension: UnitDimension, base_factor: Option<f64>) -> Unit {
+        Unit {
+            id: 0,
+            name: name.to_string(),
+            abbreviation: None,
+            system: UnitSystem::Universal,
+            dimension,
+            base_factor,
+        }
+    }
+
+    fn converter() -> UnitConverter {
+        UnitConverter::new(vec![
+            unit("gram", UnitDimension::Mass, Some(1.0)),
+            unit("kilogram", UnitDimension::Mass, Some(1000.0)),
+            unit("tablespoon", UnitDimension::Volume, Some(14.78676478125)),
+            unit("cup", UnitDimension::Volume, Some(236.5882365)),
+            unit("piece", UnitDimension::Count, None),
+            unit("pinch", UnitDimension::Count, None),
+            unit("package", UnitDimension::Count, None),
+        ])
+    }
+
+    fn ingredient(name: &str, unit: &str, quantity: Quantity, is_optional: bool) -> FullRecipeComponentIngredient {
+        FullRecipeComponentIngredient {
+            ingredient_id: 1,
+            name: name.to_string(),
+            description: String::new(),
+            unit: unit.to_string(),
+            quantity,
+            is_optional,
+        }
+    }
+
+    fn recipe(servings: Option<i32>, components: Vec<(bool, Vec<FullRecipeComponentIngredient>)>) -> FullRecipeDetails {
+        FullRecipeDetails {
+            recipe_id: 1,
+            name: "Recipe".to_string(),
+            description: String::new(),
+            is_public: false,
+            prep_time: None,
+            cook_time: None,
+            rest_time: None,
+            servings,
+            source_url: None,
+            recipe_components: components
+                .into_iter()
+                .map(|(is_optional, component_ingredients)| FullRecipeComponent {
+                    name: "Main".to_string(),
+                    is_optional,
+                    component_ingredients,
+                })
+                .collect(),
+            recipe_instructions: Vec::new(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 0.01, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn the_bundled_nutrition_facts_are_valid() {
+        let nutrition_facts = nutrition_facts();
+        assert!(nutrition_facts.len() > 40);
+        let egg = nutrition_facts.iter().find(|nutrition_fact| nutrition_fact.name == "egg").unwrap();
+        assert_eq!(egg.aliases, ["eggs", "large egg"]);
+        assert_eq!(egg.grams_per_piece, Some(50.0));
+        assert_eq!(egg.grams_per_can, None);
+    }
+
+    #[test]
+    fn csv_fields_can_be_quoted() {
+        assert_eq!(split_csv_line(r#"a,"b, ""c""",,d"#), ["a", r#"b, "c""#, "", "d"]);
+        assert!(parse_nutrition_csv("name,calories\nsalt,0").is_err());
+        let header = DEFAULT_NUTRITION_FACTS.lines().next().unwrap();
+        let error = parse_nutrition_csv(&format!("{}\nsalt,,lots,0,0,0,0,0,0,0,0,0,0,0,,,", header)).unwrap_err();
+        assert_eq!(error, "Line 2: lots is not a number for calories");
+    }
+
+    #[test]
+    fn names_are_compared_by_their_singular_words() {
+        assert_eq!(name_words("Pink Beans (habichuelas rosadas)"), ["pink", "bean"]);
+        assert_eq!(name_words("Pimento-stuffed olives"), ["pimento", "stuffed", "olive"]);
+        assert_eq!(name_words("Tomatoes, diced"), ["tomato", "diced"]);
+    }
+
+    #[test]
+    fn ingredients_match_the_longest_food_name_they_contain() {
+        let nutrition_facts = nutrition_facts();
+        let name_of = |ingredient_name: &str| find_nutrition_fact(ingredient_name, &nutrition_facts).map(|nutrition_fact| nutrition_fact.name.as_str());
+        assert_eq!(name_of("Low sodium chicken broth"), Some("chicken broth"));
+        assert_eq!(name_of("Olive oil"), Some("olive oil"));
+        assert_eq!(name_of("Dried bay leaves"), Some("bay leaf"));
+        assert_eq!(name_of("Pink beans (habichuelas rosadas)"), Some("kidney beans"));
+        assert_eq!(name_of("Bread flour"), Some("all-purpose flour"));
+        assert_eq!(name_of("Puerto Rican sofrito"), None);
+    }
+
+    #[test]
+    fn recipes_add_up_their_required_ingredients_per_serving() {
+        let recipe = recipe(Some(2), vec![
+            (false, vec![
+                ingredient("Flour", "cup", Quantity::whole(1), false),
+                ingredient("Eggs", "piece", Quantity::whole(2), false),
+                ingredient("Salt", "pinch", Quantity::whole(1), false),
+                ingredient("Butter", "gram", Quantity::whole(100), true),
+                ingredient("Sofrito", "cup", Quantity::whole(1), false),
+                ingredient("Tofu", "package", Quantity::whole(1), false),
+            ]),
+            (true, vec![ingredient("Honey", "tablespoon", Quantity::whole(1), false)]),
+            (false, vec![ingredient("Sofrito", "tablespoon", Quantity::whole(1), false)]),
+        ]);
+        let nutrition = recipe_nutrition(&recipe, &nutrition_facts(), &converter());
+
+        // 125 g of flour and 100 g of egg
+        assert_close(nutrition.total.calories, 364.0 * 1.25 + 143.0);
+        assert_close(nutrition.total.protein_grams, 10.3 * 1.25 + 12.6);
+        // A pinch of salt is 292 g a cup
+        assert_close(nutrition.total.sodium_milligrams, 2.0 * 1.25 + 142.0 + 387.58 * 0.30805759960937 * 292.0 / 236.5882365);
+        assert_eq!(nutrition.servings, Some(2));
+        assert_close(nutrition.per_serving.calories, nutrition.total.calories / 2.0);
+        assert_eq!(nutrition.unmatched, ["Sofrito"]);
+        assert_eq!(nutrition.unconverted, [UnconvertedIngredient { name: "Tofu".to_string(), unit: "package".to_string() }]);
+    }
+
+    #[test]
+    fn recipes_without_servings_are_one_serving() {
+        let recipe = recipe(None, vec![(false, vec![ingredient("Sugar", "kilogram", Quantity::new(1, 10).unwrap(), false)])]);
+        let nutrition = recipe_nutrition(&recipe, &nutrition_facts(), &converter());
+        assert_eq!(nutrition.servings, None);
+        assert_close(nutrition.per_serving.calories, 387.0);
+        assert_eq!(nutrition.per_serving, nutrition.total);
+    }
+}
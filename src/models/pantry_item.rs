@@ -0,0 +1,420 @@
+use serde::{Serialize, Deserialize};
+use sqlx::{FromRow, PgPool};
+use time::Date;
+use crate::domain::{Quantity, QuantityError};
+use crate::models::ingredient::{Ingredient, CreateIngredientParams};
+use crate::models::shopping_list::ShoppingList;
+use crate::models::unit::{Unit, UnitConverter};
+
+/// Stock left over after part of it was used in another unit is kept to these fractions, which
+/// are finer than the ones recipes are shown in so that repeated use doesn't drift
+const STOCK_DENOMINATORS: &[i32] = &[1, 2, 3, 4, 8, 10, 16, 100];
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PantryItem {
+    pub id: i32,
+    pub user_id: uuid::Uuid,
+    pub ingredient_id: i32,
+    pub unit_id: i32,
+    pub quantity_numerator: i32,
+    pub quantity_denominator: i32,
+    pub expires_on: Option<Date>,
+}
+
+/// A pantry item as it is entered, by the names of its ingredient and unit
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavePantryItemParams {
+    pub ingredient: String,
+    pub unit: String,
+    pub quantity: Quantity,
+    pub expires_on: Option<Date>,
+}
+
+/// A pantry item with the names of its ingredient and unit, as the pantry page lists it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PantryItemDetails {
+    pub id: i32,
+    pub ingredient_id: i32,
+    pub ingredient: String,
+    pub unit: String,
+    pub quantity: Quantity,
+    pub expires_on: Option<Date>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct PantryItemDetailsRow {
+    id: i32,
+    ingredient_id: i32,
+    ingredient: String,
+    unit: String,
+    quantity_numerator: i32,
+    quantity_denominator: i32,
+    expires_on: Option<Date>,
+}
+
+impl TryFrom<PantryItemDetailsRow> for PantryItemDetails {
+    type Error = QuantityError;
+
+    fn try_from(row: PantryItemDetailsRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.id,
+            ingredient_id: row.ingredient_id,
+            ingredient: row.ingredient,
+            unit: row.unit,
+            quantity: Quantity::new(row.quantity_numerator, row.quantity_denominator)?,
+            expires_on: row.expires_on,
+        })
+    }
+}
+
+/// How much of an ingredient one pantry item holds, while recipes are taken out of the pantry
+#[derive(Debug, Clone, PartialEq)]
+pub struct PantryStock {
+    pub id: i32,
+    pub ingredient_id: i32,
+    pub unit: String,
+    pub quantity: Quantity,
+}
+
+impl From<PantryItemDetails> for PantryStock {
+    fn from(item: PantryItemDetails) -> Self {
+        Self {
+            id: item.id,
+            ingredient_id: item.ingredient_id,
+            unit: item.unit,
+            quantity: item.quantity,
+        }
+    }
+}
+
+impl PantryItem {
+    pub fn quantity(&self) -> Result<Quantity, QuantityError> {
+        Quantity::new(self.quantity_numerator, self.quantity_denominator)
+    }
+
+    pub async fn create(db: &PgPool, user_id: &uuid::Uuid, params: &SavePantryItemParams) -> Result<Self, crate::models::Error> {
+        let (ingredient, unit) = Self::find_ingredient_and_unit(db, params).await?;
+        let pantry_item = sqlx::query_as(
+            "INSERT INTO pantry_items (user_id, ingredient_id, unit_id, quantity_numerator, quantity_denominator, expires_on)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING *"
+        )
+        .bind(user_id)
+        .bind(ingredient.id)
+        .bind(unit.id)
+        .bind(params.quantity.numerator())
+        .bind(params.quantity.denominator())
+        .bind(params.expires_on)
+        .fetch_one(db)
+        .await?;
+
+        Ok(pantry_item)
+    }
+
+    /// Returns None if the user has no such pantry item
+    pub async fn update(db: &PgPool, user_id: &uuid::Uuid, pantry_item_id: i32, params: &SavePantryItemParams) -> Result<Option<Self>, crate::models::Error> {
+        let (ingredient, unit) = Self::find_ingredient_and_unit(db, params).await?;
+        let pantry_item = sqlx::query_as(
+            "UPDATE pantry_items
+             SET ingredient_id = $1, unit_id = $2, quantity_numerator = $3, quantity_denominator = $4, expires_on = $5
+             WHERE id = $6 AND user_id = $7
+             RETURNING *"
+        )
+        .bind(ingredient.id)
+        .bind(unit.id)
+        .bind(params.quantity.numerator())
+        .bind(params.quantity.denominator())
+        .bind(params.expires_on)
+        .bind(pantry_item_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(pantry_item)
+    }
+
+    /// Returns false if the user has no such pantry item
+    pub async fn delete(db: &PgPool, user_id: &uuid::Uuid, pantry_item_id: i32) -> Result<bool, crate::models::Error> {
+        let result = sqlx::query("DELETE FROM pantry_items WHERE id = $1 AND user_id = $2")
+            .bind(pantry_item_id)
+            .bind(user_id)
+            .execute(db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn find_details(db: &PgPool, user_id: &uuid::Uuid, pantry_item_id: i32) -> Result<Option<PantryItemDetails>, crate::models::Error> {
+        let row: Option<PantryItemDetailsRow> = sqlx::query_as(
+            "SELECT p.id, p.ingredient_id, i.name AS ingredient, u.name AS unit, p.quantity_numerator, p.quantity_denominator, p.expires_on
+             FROM pantry_items p
+             JOIN ingredients i ON i.id = p.ingredient_id
+             JOIN units u ON u.id = p.unit_id
+             WHERE p.id = $1 AND p.user_id = $2"
+        )
+        .bind(pantry_item_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(row.map(PantryItemDetails::try_from).transpose()?)
+    }
+
+    /// The user's pantry by ingredient name, soonest to expire first
+    pub async fn list_for_user(db: &PgPool, user_id: &uuid::Uuid) -> Result<Vec<PantryItemDetails>, crate::models::Error> {
+        let rows: Vec<PantryItemDetailsRow> = sqlx::query_as(
+            "SELECT p.id, p.ingredient_id, i.name AS ingredient, u.name AS unit, p.quantity_numerator, p.quantity_denominator, p.expires_on
+             FROM pantry_items p
+             JOIN ingredients i ON i.id = p.ingredient_id
+             JOIN units u ON u.id = p.unit_id
+             WHERE p.user_id = $1
+             ORDER BY i.name, p.expires_on NULLS LAST, p.id"
+        )
+        .bind(user_id)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows.into_iter().map(PantryItemDetails::try_from).collect::<Result<_, _>>()?)
+    }
+
+    /// Everything in the user's pantry in the order it should be used up, soonest to expire first
+    pub async fn stock_for_user(db: &PgPool, user_id: &uuid::Uuid) -> Result<Vec<PantryStock>, crate::models::Error> {
+        let rows: Vec<PantryItemDetailsRow> = sqlx::query_as(
+            "SELECT p.id, p.ingredient_id, i.name AS ingredient, u.name AS unit, p.quantity_numerator, p.quantity_denominator, p.expires_on
+             FROM pantry_items p
+             JOIN ingredients i ON i.id = p.ingredient_id
+             JOIN units u ON u.id = p.unit_id
+             WHERE p.user_id = $1
+             ORDER BY p.expires_on NULLS LAST, p.id"
+        )
+        .bind(user_id)
+        .fetch_all(db)
+        .await?;
+
+        stock_from_rows(rows)
+    }
+
+    /// `stock_for_user`, with the rows locked until the transaction ends so that nothing
+    /// else can take from them in the meantime
+    async fn lock_stock_for_user(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        user_id: &uuid::Uuid,
+    ) -> Result<Vec<PantryStock>, crate::models::Error> {
+        let rows: Vec<PantryItemDetailsRow> = sqlx::query_as(
+            "SELECT p.id, p.ingredient_id, i.name AS ingredient, u.name AS unit, p.quantity_numerator, p.quantity_denominator, p.expires_on
+             FROM pantry_items p
+             JOIN ingredients i ON i.id = p.ingredient_id
+             JOIN units u ON u.id = p.unit_id
+             WHERE p.user_id = $1
+             ORDER BY p.expires_on NULLS LAST, p.id
+             FOR UPDATE OF p"
+        )
+        .bind(user_id)
+        .fetch_all(&mut **transaction)
+        .await?;
+
+        stock_from_rows(rows)
+    }
+
+    /// Takes what the recipe needs out of the user's pantry. Optional ingredients are left
+    /// alone, since there's no telling whether they were used, and pantry items that are used
+    /// up are removed. Returns false if the user has no such recipe.
+    pub async fn cook_recipe(db: &PgPool, user_id: &uuid::Uuid, recipe_id: i32) -> Result<bool, crate::models::Error> {
+        let recipe_exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM recipes WHERE id = $1 AND user_id = $2)")
+            .bind(recipe_id)
+            .bind(user_id)
+            .fetch_one(db)
+            .await?;
+        if !recipe_exists {
+            return Ok(false);
+        }
+
+        let items = ShoppingList::items_for_recipes(db, user_id, &[recipe_id]).await?;
+        let converter = UnitConverter::load(db).await?;
+        let mut transaction = db.begin().await?;
+        let stock = Self::lock_stock_for_user(&mut transaction, user_id).await?;
+        let mut remaining = stock.clone();
+        for item in items.iter().filter(|item| !item.is_optional) {
+            take_from_stock(&mut remaining, item.ingredient_id, &item.unit, item.quantity, &converter)?;
+        }
+
+        for (before, after) in stock.iter().zip(&remaining) {
+            if before.quantity == after.quantity {
+                continue;
+            }
+            if after.quantity > Quantity::zero() {
+                sqlx::query("UPDATE pantry_items SET quantity_numerator = $1, quantity_denominator = $2 WHERE id = $3")
+                    .bind(after.quantity.numerator())
+                    .bind(after.quantity.denominator())
+                    .bind(after.id)
+                    .execute(&mut *transaction)
+                    .await?;
+            } else {
+                sqlx::query("DELETE FROM pantry_items WHERE id = $1")
+                    .bind(after.id)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+        }
+        transaction.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Ingredients that aren't known yet are created, like they are when a recipe is saved
+    async fn find_ingredient_and_unit(db: &PgPool, params: &SavePantryItemParams) -> Result<(Ingredient, Unit), crate::models::Error> {
+        let ingredient = match Ingredient::find_by_name(db, &params.ingredient).await? {
+            Some(ingredient) => ingredient,
+            None => Ingredient::create(db, &CreateIngredientParams::new(params.ingredient.clone(), String::new())).await?,
+        };
+        let unit = Unit::find_by_name(db, &params.unit).await?
+            .ok_or_else(|| crate::models::Error::UnknownUnit(params.unit.clone()))?;
+
+        Ok((ingredient, unit))
+    }
+}
+
+fn stock_from_rows(rows: Vec<PantryItemDetailsRow>) -> Result<Vec<PantryStock>, crate::models::Error> {
+    let items = rows.into_iter().map(PantryItemDetails::try_from).collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items.into_iter().map(PantryStock::from).collect())
+}
+
+/// Takes up to `quantity` of the unit named `unit` of an ingredient out of `stock`, and answers
+/// how much of it the pantry couldn't cover. Stock in the same unit is used first and exactly,
+/// then stock in units that convert to it. Stock is used in the order it is given.
+pub fn take_from_stock(
+    stock: &mut [PantryStock],
+    ingredient_id: i32,
+    unit: &str,
+    quantity: Quantity,
+    converter: &UnitConverter,
+) -> Result<Quantity, QuantityError> {
+    let mut needed = quantity;
+    for row in stock.iter_mut().filter(|row| row.ingredient_id == ingredient_id && row.unit == unit) {
+        if needed <= Quantity::zero() {
+            break;
+        }
+        let taken = needed.min(row.quantity);
+        row.quantity = row.quantity.checked_sub(&taken)?;
+        needed = needed.checked_sub(&taken)?;
+    }
+
+    for row in stock.iter_mut().filter(|row| row.ingredient_id == ingredient_id && row.unit != unit) {
+        if needed <= Quantity::zero() {
+            break;
+        }
+        let available = match converter.convert_value(&row.quantity, &row.unit, unit) {
+            Some(available) if available > 0.0 => available,
+            _ => continue,
+        };
+        let wanted = needed.to_f64();
+        if available > wanted {
+            let left = row.quantity.to_f64() * (1.0 - wanted / available);
+            row.quantity = Quantity::approximate(left, STOCK_DENOMINATORS)?;
+            needed = Quantity::zero();
+        } else {
+            row.quantity = Quantity::zero();
+            let remainder = wanted - available;
+            if remainder <= 0.0 {
+                needed = Quantity::zero();
+                continue;
+            }
+            // A remainder too small to read in the unit is still kept, to the finer stock fractions
+            needed = match converter.approximate(remainder, unit) {
+                Some(readable) if readable > Quantity::zero() => readable,
+                _ => Quantity::approximate(remainder, STOCK_DENOMINATORS)?,
+            };
+        }
+    }
+
+    Ok(needed.max(Quantity::zero()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{take_from_stock, PantryStock};
+    use crate::domain::Quantity;
+    use crate::models::unit::{Unit, UnitConverter, UnitDimension, UnitSystem};
+
+    fn unit(name: &str, system: UnitSystem, dimension: UnitDimension, base_factor: Option<f64>) -> Unit {
+        Unit {
+            id: 0,
+            name: name.to_string(),
+            abbreviation: None,
+            system,
+            dimension,
+            base_factor,
+        }
+    }
+
+    fn converter() -> UnitConverter {
+        UnitConverter::new(vec![
+            unit("gram", UnitSystem::Metric, UnitDimension::Mass, Some(1.0)),
+            unit("kilogram", UnitSystem::Metric, UnitDimension::Mass, Some(1000.0)),
+            unit("milliliter", UnitSystem::Metric, UnitDimension::Volume, Some(1.0)),
+            unit("teaspoon", UnitSystem::Universal, UnitDimension::Volume, Some(4.92892159375)),
+            unit("tablespoon", UnitSystem::Universal, UnitDimension::Volume, Some(14.78676478125)),
+            unit("cup", UnitSystem::Universal, UnitDimension::Volume, Some(236.5882365)),
+            unit("piece", UnitSystem::Universal, UnitDimension::Count, None),
+        ])
+    }
+
+    fn stock(id: i32, ingredient_id: i32, unit: &str, quantity: Quantity) -> PantryStock {
+        PantryStock { id, ingredient_id, unit: unit.to_string(), quantity }
+    }
+
+    fn quantity(numerator: i32, denominator: i32) -> Quantity {
+        Quantity::new(numerator, denominator).unwrap()
+    }
+
+    #[test]
+    fn stock_in_the_same_unit_is_used_exactly() {
+        let mut pantry = vec![stock(1, 1, "cup", quantity(1, 4)), stock(2, 1, "cup", quantity(1, 1))];
+        let needed = take_from_stock(&mut pantry, 1, "cup", quantity(3, 4), &converter()).unwrap();
+        assert_eq!(needed, Quantity::zero());
+        assert_eq!((pantry[0].quantity, pantry[1].quantity), (Quantity::zero(), quantity(1, 2)));
+    }
+
+    #[test]
+    fn what_the_pantry_cant_cover_is_still_needed() {
+        let mut pantry = vec![stock(1, 1, "piece", quantity(2, 1)), stock(2, 2, "piece", quantity(5, 1))];
+        let needed = take_from_stock(&mut pantry, 1, "piece", quantity(3, 1), &converter()).unwrap();
+        assert_eq!(needed, Quantity::whole(1));
+        assert_eq!((pantry[0].quantity, pantry[1].quantity), (Quantity::zero(), Quantity::whole(5)));
+    }
+
+    #[test]
+    fn stock_in_other_units_is_converted() {
+        let mut pantry = vec![stock(1, 1, "kilogram", Quantity::whole(1))];
+        let needed = take_from_stock(&mut pantry, 1, "gram", Quantity::whole(200), &converter()).unwrap();
+        assert_eq!((needed, pantry[0].quantity), (Quantity::zero(), quantity(4, 5)));
+
+        let mut pantry = vec![stock(1, 1, "milliliter", Quantity::whole(120))];
+        let needed = take_from_stock(&mut pantry, 1, "cup", Quantity::whole(1), &converter()).unwrap();
+        assert_eq!((needed, pantry[0].quantity), (quantity(1, 2), Quantity::zero()));
+    }
+
+    #[test]
+    fn a_small_remainder_is_still_needed() {
+        let mut pantry = vec![stock(1, 1, "milliliter", Quantity::whole(225))];
+        let needed = take_from_stock(&mut pantry, 1, "cup", Quantity::whole(1), &converter()).unwrap();
+        assert_eq!((needed, pantry[0].quantity), (quantity(1, 20), Quantity::zero()));
+    }
+
+    #[test]
+    fn the_same_unit_is_used_before_converting() {
+        let mut pantry = vec![stock(1, 1, "tablespoon", Quantity::whole(1)), stock(2, 1, "teaspoon", Quantity::whole(2))];
+        let needed = take_from_stock(&mut pantry, 1, "teaspoon", Quantity::whole(3), &converter()).unwrap();
+        assert_eq!(needed, Quantity::zero());
+        assert_eq!(pantry[1].quantity, Quantity::zero());
+        assert_eq!(pantry[0].quantity, quantity(2, 3));
+    }
+
+    #[test]
+    fn units_that_dont_convert_are_left_alone() {
+        let mut pantry = vec![stock(1, 1, "piece", Quantity::whole(4))];
+        let needed = take_from_stock(&mut pantry, 1, "gram", Quantity::whole(100), &converter()).unwrap();
+        assert_eq!((needed, pantry[0].quantity), (Quantity::whole(100), Quantity::whole(4)));
+    }
+}
@@ -1,10 +1,23 @@
 use serde::{Serialize, Deserialize};
+use serde_aux::field_attributes::deserialize_option_number_from_string;
 use sqlx::{FromRow, PgPool};
 
+use crate::domain::{normalize_source_url, Quantity, QuantityError, SearchSnippet};
 use crate::models::tag::{Tag, CreateTagParams};
-use crate::models::ingredient::Ingredient;
+use crate::models::ingredient::{Ingredient, CreateIngredientParams};
+use crate::models::recipe_component::{RecipeComponent, CreateRecipeComponentParams};
+use crate::models::recipe_component_ingredient::{RecipeComponentIngredient, CreateRecipeComponentIngredientParams};
+use crate::models::recipe_instruction::{RecipeInstruction, CreateRecipeInstructionParams};
+use crate::models::recipe_instruction_step::{RecipeInstructionStep, CreateRecipeInstructionStepParams};
+use crate::models::unit::{self, Unit, UnitConverter, UnitSystem};
 use crate::models::recipe_tag::{RecipeTag, CreateRecipeTagParams};
 
+/// The most recipes a search returns
+const SEARCH_LIMIT: i64 = 50;
+/// Prep, cook and rest times are each at most a week, in minutes. Anything longer is a
+/// mistake, and too long to plan a meal around.
+pub const MAX_STEP_MINUTES: i32 = 7 * 24 * 60;
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Recipe {
     pub id: i32,
@@ -19,6 +32,33 @@ pub struct Recipe {
     pub source_url: Option<String>,
 }
 
+/// What an import does when the user already has a recipe from the same page
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[derive(sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    /// Keep the existing recipe and import nothing
+    #[default]
+    Skip,
+    /// Replace the existing recipe's contents, keeping its id
+    Overwrite,
+    /// Import it as another recipe
+    Copy,
+}
+
+/// What an import ended up doing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ImportOutcome {
+    Created,
+    Skipped,
+    Overwritten,
+    Copied,
+}
+
 pub struct CreateRecipeParams {
     pub user_id: uuid::Uuid,
     pub name: String,
@@ -31,6 +71,54 @@ pub struct CreateRecipeParams {
     pub source_url: Option<String>,
 }
 
+/// Everything needed to write a recipe and all of its children in one go.
+/// Ingredients and units are referenced by name, ingredients are created if they don't exist yet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveRecipeParams {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub is_public: bool,
+    pub prep_time: Option<i32>,
+    pub cook_time: Option<i32>,
+    pub rest_time: Option<i32>,
+    pub servings: Option<i32>,
+    pub source_url: Option<String>,
+    #[serde(default)]
+    pub components: Vec<SaveRecipeComponentParams>,
+    #[serde(default)]
+    pub instructions: Vec<SaveRecipeInstructionParams>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveRecipeComponentParams {
+    pub name: String,
+    #[serde(default)]
+    pub is_optional: bool,
+    #[serde(default)]
+    pub ingredients: Vec<SaveRecipeIngredientParams>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveRecipeIngredientParams {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub unit: String,
+    pub quantity: Quantity,
+    #[serde(default)]
+    pub is_optional: bool,
+}
+
+/// Steps are numbered in the order they are given, starting at 1
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveRecipeInstructionParams {
+    pub title: String,
+    #[serde(default)]
+    pub steps: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FullRecipeDetails {
     pub recipe_id: i32,
@@ -59,8 +147,7 @@ pub struct FullRecipeComponentIngredient {
     pub name: String,
     pub description: String,
     pub unit: String,
-    pub quantity_numerator: i32,
-    pub quantity_denominator: i32,
+    pub quantity: Quantity,
     pub is_optional: bool,
 }
 
@@ -77,6 +164,222 @@ pub struct FullRecipeInstructionStep {
     pub content: String,
 }
 
+/// Narrows down a user's recipes. Every filter that is given has to match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecipeSearchParams {
+    /// Words to find in the name, description, ingredients or steps. Quoted phrases, "or"
+    /// and "-word" work the way they do in web searches.
+    pub q: Option<String>,
+    /// The name of one of the recipe's tags
+    pub tag: Option<String>,
+    /// The most minutes the prep, cook and rest time may add up to. Times that aren't
+    /// known count as 0.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub max_time: Option<i32>,
+    /// Part of the name of an ingredient the recipe uses
+    pub ingredient: Option<String>,
+}
+
+/// A recipe found by a search, best matches first
+#[derive(Debug, Clone, Serialize)]
+pub struct RecipeSearchResult {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub prep_time: Option<i32>,
+    pub cook_time: Option<i32>,
+    pub rest_time: Option<i32>,
+    pub servings: Option<i32>,
+    /// How well the recipe matches `q`. Without `q` every recipe ranks 0 and they are
+    /// sorted by name.
+    pub rank: f32,
+    /// Where `q` matched, empty without `q`
+    pub snippet: SearchSnippet,
+}
+
+#[derive(FromRow)]
+struct RecipeSearchRow {
+    id: i32,
+    name: String,
+    description: String,
+    prep_time: Option<i32>,
+    cook_time: Option<i32>,
+    rest_time: Option<i32>,
+    servings: Option<i32>,
+    rank: f32,
+    headline: String,
+}
+
+impl From<RecipeSearchRow> for RecipeSearchResult {
+    fn from(row: RecipeSearchRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            description: row.description,
+            prep_time: row.prep_time,
+            cook_time: row.cook_time,
+            rest_time: row.rest_time,
+            servings: row.servings,
+            rank: row.rank,
+            snippet: SearchSnippet::from_headline(&row.headline),
+        }
+    }
+}
+
+impl RecipeSearchParams {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        match self.max_time {
+            Some(max_time) if max_time < 0 => Err(vec!["max_time cannot be negative".to_string()]),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether any filter is given, as opposed to listing every recipe
+    pub fn is_search(&self) -> bool {
+        self.query().is_some() || self.tag_name().is_some() || self.max_time.is_some() || self.ingredient_name().is_some()
+    }
+
+    fn query(&self) -> Option<&str> {
+        filled(&self.q)
+    }
+
+    fn tag_name(&self) -> Option<&str> {
+        filled(&self.tag)
+    }
+
+    fn ingredient_name(&self) -> Option<&str> {
+        filled(&self.ingredient)
+    }
+
+    /// An ILIKE pattern for ingredient names containing `ingredient`, with the characters
+    /// ILIKE treats specially escaped so that "50%" matches only itself
+    fn ingredient_pattern(&self) -> Option<String> {
+        self.ingredient_name().map(|name| {
+            let escaped = name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            format!("%{}%", escaped)
+        })
+    }
+}
+
+/// Blank filters are left out, since forms send every field
+fn filled(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|value| !value.is_empty())
+}
+
+impl FullRecipeDetails {
+    /// Scales every ingredient amount exactly so that the recipe makes `servings`.
+    /// Recipes without a serving count are left untouched.
+    pub fn scale_to_servings(&mut self, servings: i32) -> Result<(), QuantityError> {
+        let original_servings = match self.servings {
+            Some(original_servings) => original_servings,
+            None => return Ok(()),
+        };
+        let factor = Quantity::new(servings, original_servings)?;
+        for component in self.recipe_components.iter_mut() {
+            for ingredient in component.component_ingredients.iter_mut() {
+                if unit::is_scalable(&ingredient.unit) {
+                    ingredient.quantity = ingredient.quantity.checked_mul(&factor)?;
+                }
+            }
+        }
+        self.servings = Some(servings);
+
+        Ok(())
+    }
+
+    /// Rewrites ingredient amounts into the given unit system wherever a conversion exists
+    pub fn convert_units(&mut self, converter: &UnitConverter, system: &UnitSystem) {
+        for component in self.recipe_components.iter_mut() {
+            for ingredient in component.component_ingredients.iter_mut() {
+                if let Some((quantity, unit)) = converter.to_system(&ingredient.quantity, &ingredient.unit, system) {
+                    ingredient.quantity = quantity;
+                    ingredient.unit = unit.name.clone();
+                }
+            }
+        }
+    }
+}
+
+impl SaveRecipeParams {
+    /// Checks what the database can't, collecting every problem so that they can all be reported at once
+    pub fn validate(&self, units: &[Unit]) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push("Name is required".to_string());
+        }
+        for (label, value) in [("prep_time", self.prep_time), ("cook_time", self.cook_time), ("rest_time", self.rest_time)] {
+            if value.is_some_and(|value| value < 0) {
+                errors.push(format!("{} cannot be negative", label));
+            }
+            if value.is_some_and(|value| value > MAX_STEP_MINUTES) {
+                errors.push(format!("{} cannot be more than {} minutes", label, MAX_STEP_MINUTES));
+            }
+        }
+        if self.servings.is_some_and(|servings| servings < 1) {
+            errors.push("servings must be at least 1".to_string());
+        }
+        for component in &self.components {
+            if component.name.trim().is_empty() {
+                errors.push("Every component needs a name".to_string());
+            }
+            for ingredient in &component.ingredients {
+                if ingredient.name.trim().is_empty() {
+                    errors.push(format!("An ingredient in {} needs a name", component.name));
+                }
+                if !units.iter().any(|unit| unit.name == ingredient.unit) {
+                    errors.push(format!("{}: unknown unit {}", ingredient.name, ingredient.unit));
+                }
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    pub fn create_params(&self, user_id: uuid::Uuid) -> CreateRecipeParams {
+        CreateRecipeParams {
+            user_id,
+            name: self.name.clone(),
+            description: self.description.clone(),
+            is_public: self.is_public,
+            prep_time: self.prep_time,
+            cook_time: self.cook_time,
+            rest_time: self.rest_time,
+            servings: self.servings,
+            source_url: self.source_url.clone(),
+        }
+    }
+}
+
+impl From<&FullRecipeDetails> for SaveRecipeParams {
+    fn from(recipe: &FullRecipeDetails) -> Self {
+        Self {
+            name: recipe.name.clone(),
+            description: recipe.description.clone(),
+            is_public: recipe.is_public,
+            prep_time: recipe.prep_time,
+            cook_time: recipe.cook_time,
+            rest_time: recipe.rest_time,
+            servings: recipe.servings,
+            source_url: recipe.source_url.clone(),
+            components: recipe.recipe_components.iter().map(|component| SaveRecipeComponentParams {
+                name: component.name.clone(),
+                is_optional: component.is_optional,
+                ingredients: component.component_ingredients.iter().map(|ingredient| SaveRecipeIngredientParams {
+                    name: ingredient.name.clone(),
+                    description: ingredient.description.clone(),
+                    unit: ingredient.unit.clone(),
+                    quantity: ingredient.quantity,
+                    is_optional: ingredient.is_optional,
+                }).collect(),
+            }).collect(),
+            instructions: recipe.recipe_instructions.iter().map(|instruction| SaveRecipeInstructionParams {
+                title: instruction.title.clone(),
+                steps: instruction.instruction_steps.iter().map(|step| step.content.clone()).collect(),
+            }).collect(),
+        }
+    }
+}
+
 impl CreateRecipeParams {
     pub fn new(user_id: uuid::Uuid, name: String, description: String) -> Self {
         Self {
@@ -116,14 +419,24 @@ impl CreateRecipeParams {
         self.source_url = Some(source_url);
         self
     }
+
+    /// What `find_by_source_url` looks the recipe up by. None without a source URL.
+    fn normalized_source_url(&self) -> Option<String> {
+        self.source_url.as_deref().filter(|source_url| !source_url.trim().is_empty()).map(normalize_source_url)
+    }
 }
 
 impl Recipe {
-    pub async fn create(db: &PgPool, create_params: &CreateRecipeParams) -> Result<Option<Self>, crate::models::Error> {
-        // Just bind everything. If it is None, it will convert to NULL
+    pub async fn create<'e, E>(db: E, create_params: &CreateRecipeParams) -> Result<Option<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
+        // Just bind everything. If it is None, it will convert to NULL. Only the user's first
+        // recipe from a page keeps the normalized URL, later copies leave it NULL.
         let recipe = sqlx::query_as(
-            "INSERT INTO recipes (user_id, name, description, is_public, prep_time, cook_time, rest_time, servings, source_url)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "INSERT INTO recipes (user_id, name, description, is_public, prep_time, cook_time, rest_time, servings, source_url, normalized_source_url)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9,
+                CASE WHEN EXISTS (SELECT 1 FROM recipes WHERE user_id = $1 AND normalized_source_url = $10) THEN NULL ELSE $10 END)
              RETURNING *"
         )
             .bind(create_params.user_id)
@@ -135,11 +448,272 @@ impl Recipe {
             .bind(create_params.rest_time)
             .bind(create_params.servings)
             .bind(&create_params.source_url)
+            .bind(create_params.normalized_source_url())
             .fetch_optional(db)
             .await?;
         Ok(recipe)
     }
 
+    /// Creates the recipe with its components, ingredients, instructions and steps.
+    /// Nothing is written unless all of it is.
+    pub async fn create_full(db: &PgPool, user_id: &uuid::Uuid, params: &SaveRecipeParams) -> Result<Self, crate::models::Error> {
+        let mut transaction = db.begin().await?;
+        let recipe = Self::insert_full(&mut transaction, user_id, params).await?;
+        transaction.commit().await?;
+
+        Ok(recipe)
+    }
+
+    /// Replaces the recipe and all of its children. Returns None if the user has no such recipe.
+    pub async fn update_full(db: &PgPool, user_id: &uuid::Uuid, recipe_id: i32, params: &SaveRecipeParams) -> Result<Option<Self>, crate::models::Error> {
+        let mut transaction = db.begin().await?;
+        let recipe = Self::replace_full(&mut transaction, user_id, recipe_id, params).await?;
+        transaction.commit().await?;
+
+        Ok(recipe)
+    }
+
+    /// `create_full` as part of a larger transaction
+    pub async fn insert_full(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        user_id: &uuid::Uuid,
+        params: &SaveRecipeParams,
+    ) -> Result<Self, crate::models::Error> {
+        let recipe = Recipe::create(&mut **transaction, &params.create_params(*user_id)).await?
+            .ok_or(crate::models::Error::NotFound)?;
+        Self::create_children(transaction, recipe.id, params).await?;
+
+        Ok(recipe)
+    }
+
+    /// `update_full` as part of a larger transaction
+    pub async fn replace_full(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        user_id: &uuid::Uuid,
+        recipe_id: i32,
+        params: &SaveRecipeParams,
+    ) -> Result<Option<Self>, crate::models::Error> {
+        let recipe = match Self::overwrite(transaction, recipe_id, &params.create_params(*user_id)).await? {
+            Some(recipe) => recipe,
+            None => return Ok(None),
+        };
+        Self::create_children(transaction, recipe.id, params).await?;
+
+        Ok(Some(recipe))
+    }
+
+    /// Updates the recipe's own fields and removes its components and instructions, so that
+    /// new ones can be written under the same id. Returns None if the user has no such recipe.
+    pub async fn overwrite(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        recipe_id: i32,
+        params: &CreateRecipeParams,
+    ) -> Result<Option<Self>, crate::models::Error> {
+        let recipe: Option<Recipe> = sqlx::query_as(
+            "UPDATE recipes
+             SET name = $1, description = $2, is_public = $3, prep_time = $4, cook_time = $5,
+                 rest_time = $6, servings = $7, source_url = $8,
+                 normalized_source_url = CASE
+                    WHEN EXISTS (SELECT 1 FROM recipes WHERE user_id = $11 AND normalized_source_url = $9 AND id <> $10) THEN NULL
+                    ELSE $9
+                 END
+             WHERE id = $10 AND user_id = $11
+             RETURNING *"
+        )
+            .bind(&params.name)
+            .bind(&params.description)
+            .bind(params.is_public)
+            .bind(params.prep_time)
+            .bind(params.cook_time)
+            .bind(params.rest_time)
+            .bind(params.servings)
+            .bind(&params.source_url)
+            .bind(params.normalized_source_url())
+            .bind(recipe_id)
+            .bind(params.user_id)
+            .fetch_optional(&mut **transaction)
+            .await?;
+        let recipe = match recipe {
+            Some(recipe) => recipe,
+            None => return Ok(None),
+        };
+
+        // Component ingredients and instruction steps are removed by the cascades
+        sqlx::query("DELETE FROM recipe_components WHERE recipe_id = $1")
+            .bind(recipe.id)
+            .execute(&mut **transaction)
+            .await?;
+        sqlx::query("DELETE FROM recipe_instructions WHERE recipe_id = $1")
+            .bind(recipe.id)
+            .execute(&mut **transaction)
+            .await?;
+
+        Ok(Some(recipe))
+    }
+
+    /// Finds the user's oldest recipe from the same page as `source_url`, comparing
+    /// normalized URLs so tracking parameters and the like don't matter
+    pub async fn find_by_source_url(db: &PgPool, user_id: &uuid::Uuid, source_url: &str) -> Result<Option<Self>, crate::models::Error> {
+        if source_url.trim().is_empty() {
+            return Ok(None);
+        }
+        let recipe = sqlx::query_as(
+            "SELECT * FROM recipes WHERE user_id = $1 AND normalized_source_url = $2 ORDER BY id LIMIT 1"
+        )
+            .bind(user_id)
+            .bind(normalize_source_url(source_url))
+            .fetch_optional(db)
+            .await?;
+
+        Ok(recipe)
+    }
+
+    /// Finds the user's oldest recipe without a source URL that has the same name and content
+    /// as `params`, for recipes that can't be matched by where they came from
+    pub async fn find_same_without_source_url(db: &PgPool, user_id: &uuid::Uuid, params: &SaveRecipeParams) -> Result<Option<Self>, crate::models::Error> {
+        let candidates: Vec<Self> = sqlx::query_as(
+            "SELECT * FROM recipes WHERE user_id = $1 AND name = $2 AND btrim(COALESCE(source_url, '')) = '' ORDER BY id"
+        )
+            .bind(user_id)
+            .bind(&params.name)
+            .fetch_all(db)
+            .await?;
+
+        for recipe in candidates {
+            let mut saved = SaveRecipeParams::from(&Self::get_full_recipe_details(db, user_id, recipe.id).await?);
+            saved.source_url.clone_from(&params.source_url);
+            if saved == *params {
+                return Ok(Some(recipe));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The user's recipes, oldest first
+    pub async fn list_for_user(db: &PgPool, user_id: &uuid::Uuid) -> Result<Vec<Self>, crate::models::Error> {
+        let recipes = sqlx::query_as("SELECT * FROM recipes WHERE user_id = $1 ORDER BY id")
+            .bind(user_id)
+            .fetch_all(db)
+            .await?;
+
+        Ok(recipes)
+    }
+
+    /// The user's recipes that match every filter in `params`, ranked by how well they
+    /// match `q` with a highlighted passage for each
+    pub async fn search(db: &PgPool, user_id: &uuid::Uuid, params: &RecipeSearchParams) -> Result<Vec<RecipeSearchResult>, crate::models::Error> {
+        let rows: Vec<RecipeSearchRow> = sqlx::query_as(
+            "SELECT r.id, r.name, r.description, r.prep_time, r.cook_time, r.rest_time, r.servings,
+                COALESCE(ts_rank_cd(r.search_vector, websearch_to_tsquery('english', $2)), 0)::REAL AS rank,
+                COALESCE(ts_headline(
+                    'english',
+                    concat_ws(' · ', NULLIF(r.description, ''), NULLIF(r.search_ingredients, ''), NULLIF(r.search_steps, '')),
+                    websearch_to_tsquery('english', $2),
+                    $6
+                ), '') AS headline
+             FROM recipes r
+             WHERE r.user_id = $1
+                AND ($2::TEXT IS NULL OR r.search_vector @@ websearch_to_tsquery('english', $2))
+                AND ($3::TEXT IS NULL OR EXISTS (
+                    SELECT 1 FROM recipes_tags rt
+                    JOIN tags t ON t.id = rt.tag_id
+                    WHERE rt.recipe_id = r.id AND LOWER(t.name) = LOWER($3)
+                ))
+                AND ($4::INTEGER IS NULL OR COALESCE(r.prep_time, 0) + COALESCE(r.cook_time, 0) + COALESCE(r.rest_time, 0) <= $4)
+                AND ($5::TEXT IS NULL OR EXISTS (
+                    SELECT 1 FROM recipe_components rc
+                    JOIN recipe_component_ingredients rci ON rci.recipe_component_id = rc.id
+                    JOIN ingredients i ON i.id = rci.ingredient_id
+                    WHERE rc.recipe_id = r.id AND i.name ILIKE $5
+                ))
+             ORDER BY rank DESC, r.name, r.id
+             LIMIT $7"
+        )
+            .bind(user_id)
+            .bind(params.query())
+            .bind(params.tag_name())
+            .bind(params.max_time)
+            .bind(params.ingredient_pattern())
+            .bind(SearchSnippet::headline_options())
+            .bind(SEARCH_LIMIT)
+            .fetch_all(db)
+            .await?;
+
+        Ok(rows.into_iter().map(RecipeSearchResult::from).collect())
+    }
+
+    /// Returns false if the user has no such recipe
+    pub async fn delete(db: &PgPool, user_id: &uuid::Uuid, recipe_id: i32) -> Result<bool, crate::models::Error> {
+        let result = sqlx::query("DELETE FROM recipes WHERE id = $1 AND user_id = $2")
+            .bind(recipe_id)
+            .bind(user_id)
+            .execute(db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn create_children(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        recipe_id: i32,
+        params: &SaveRecipeParams,
+    ) -> Result<(), crate::models::Error> {
+        for component in &params.components {
+            let component_params = CreateRecipeComponentParams::new(recipe_id, component.name.clone(), component.is_optional);
+            let recipe_component = RecipeComponent::create(&mut **transaction, &component_params).await?
+                .ok_or(crate::models::Error::NotFound)?;
+
+            for component_ingredient in &component.ingredients {
+                let ingredient = match Ingredient::find_by_name(&mut **transaction, &component_ingredient.name).await? {
+                    Some(ingredient) => ingredient,
+                    None => {
+                        let ingredient_params = CreateIngredientParams::new(component_ingredient.name.clone(), component_ingredient.description.clone());
+                        Ingredient::create(&mut **transaction, &ingredient_params).await?
+                    }
+                };
+                let unit = Unit::find_by_name(&mut **transaction, &component_ingredient.unit).await?
+                    .ok_or_else(|| crate::models::Error::UnknownUnit(component_ingredient.unit.clone()))?;
+
+                let component_ingredient_params = CreateRecipeComponentIngredientParams::new(
+                    recipe_component.id,
+                    ingredient.id,
+                    unit.id,
+                    component_ingredient.quantity,
+                ).with_optional(component_ingredient.is_optional);
+                RecipeComponentIngredient::create(&mut **transaction, &component_ingredient_params).await?;
+            }
+        }
+
+        for (instruction_idx, instruction) in params.instructions.iter().enumerate() {
+            let instruction_params = CreateRecipeInstructionParams::new(recipe_id, instruction_idx as i32 + 1, instruction.title.clone());
+            let recipe_instruction = RecipeInstruction::create(&mut **transaction, &instruction_params).await?
+                .ok_or(crate::models::Error::NotFound)?;
+
+            for (step_idx, content) in instruction.steps.iter().enumerate() {
+                let step_params = CreateRecipeInstructionStepParams::new(recipe_id, recipe_instruction.id, step_idx as i32 + 1, content.clone());
+                RecipeInstructionStep::create(&mut **transaction, &step_params).await?;
+            }
+        }
+        Self::refresh_search(&mut **transaction, recipe_id).await?;
+
+        Ok(())
+    }
+
+    /// Copies the recipe's ingredient names and steps onto it for searching. New ingredients
+    /// and steps don't do this themselves, so it is called once all of them are written.
+    pub async fn refresh_search<'e, E>(db: E, recipe_id: i32) -> Result<(), crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
+        sqlx::query("SELECT refresh_recipe_search($1)")
+            .bind(recipe_id)
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn add_tag(&self, db: &PgPool, tag_name: &str) -> Result<(), crate::models::Error> {
         let tag = match Tag::find_by_name(db, tag_name).await? {
             Some(t) => t,
@@ -159,6 +733,22 @@ impl Recipe {
         Ok(())
     }
 
+    /// `add_tag` as part of a larger transaction
+    pub async fn tag_with(&self, transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>, tag_name: &str) -> Result<(), crate::models::Error> {
+        let tag = match Tag::find_by_name(&mut **transaction, tag_name).await? {
+            Some(tag) => tag,
+            None => Tag::create(&mut **transaction, &CreateTagParams { name: tag_name.to_string() }).await?
+                .ok_or(crate::models::Error::NotFound)?,
+        };
+        let recipe_tag_params = CreateRecipeTagParams {
+            recipe_id: self.id,
+            tag_id: tag.id,
+        };
+        RecipeTag::create(&mut **transaction, &recipe_tag_params).await?;
+
+        Ok(())
+    }
+
     pub async fn get_ingredients(&self, db: &PgPool) -> Result<Vec<Ingredient>, crate::models::Error> {
         Ok(Ingredient::get_ingredients_by_recipe_id(db, self.id).await?)
     }
@@ -190,12 +780,14 @@ impl Recipe {
             json_agg(json_build_object(
                 'ingredient_id', i.id,
                 'unit', u.name,
-                'quantity_numerator', rci.quantity_numerator,
-                'quantity_denominator', rci.quantity_denominator,
+                'quantity', json_build_object(
+                    'numerator', rci.quantity_numerator,
+                    'denominator', rci.quantity_denominator
+                ),
                 'is_optional', rci.is_optional,
                 'name', i.name,
                 'description', i.description
-            ) ORDER BY rci.id) as component_ingredients
+            ) ORDER BY rci.id) FILTER (WHERE rci.id IS NOT NULL) as component_ingredients
         FROM recipe_components rc
         LEFT JOIN recipe_component_ingredients rci ON rc.id = rci.recipe_component_id
         LEFT JOIN ingredients i ON rci.ingredient_id = i.id
@@ -230,15 +822,22 @@ impl Recipe {
             .fetch_all(db)
             .await?;
 
-        // Convert the raw data into the FullRecipeDetails struct
-        let recipe_components: Vec<FullRecipeComponent> = components
+        // Convert the raw data into the FullRecipeDetails struct.
+        // Ingredients are not defaulted so that an invalid stored quantity is reported instead of
+        // silently emptying the component
+        let recipe_components = components
             .into_iter()
-            .map(|c| FullRecipeComponent {
-                name: c.component_name,
-                is_optional: c.component_is_optional,
-                component_ingredients: serde_json::from_value(c.component_ingredients.unwrap_or_default()).unwrap_or_default(),
+            .map(|c| -> Result<FullRecipeComponent, crate::models::Error> {
+                Ok(FullRecipeComponent {
+                    name: c.component_name,
+                    is_optional: c.component_is_optional,
+                    component_ingredients: match c.component_ingredients {
+                        Some(component_ingredients) => serde_json::from_value(component_ingredients)?,
+                        None => Vec::new(),
+                    },
+                })
             })
-        .collect();
+        .collect::<Result<Vec<FullRecipeComponent>, _>>()?;
 
         let recipe_instructions: Vec<FullRecipeInstruction> = instructions
             .into_iter()
@@ -265,3 +864,95 @@ impl Recipe {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::{FullRecipeComponent, FullRecipeComponentIngredient, FullRecipeDetails, RecipeSearchParams, SaveRecipeParams, MAX_STEP_MINUTES};
+    use crate::domain::Quantity;
+
+    fn ingredient(name: &str, unit: &str, quantity: Quantity) -> FullRecipeComponentIngredient {
+        FullRecipeComponentIngredient {
+            ingredient_id: 0,
+            name: name.to_string(),
+            description: String::new(),
+            unit: unit.to_string(),
+            quantity,
+            is_optional: false,
+        }
+    }
+
+    fn recipe(servings: Option<i32>) -> FullRecipeDetails {
+        FullRecipeDetails {
+            recipe_id: 0,
+            name: "Stew".to_string(),
+            description: String::new(),
+            is_public: false,
+            prep_time: None,
+            cook_time: None,
+            rest_time: None,
+            servings,
+            source_url: None,
+            recipe_components: vec![FullRecipeComponent {
+                name: "Main".to_string(),
+                is_optional: false,
+                component_ingredients: vec![
+                    ingredient("Potato", "cup", Quantity::new(1, 3).unwrap()),
+                    ingredient("Salt", "to_taste", Quantity::whole(1)),
+                    ingredient("Pepper", "pinch", Quantity::whole(2)),
+                ],
+            }],
+            recipe_instructions: vec![],
+        }
+    }
+
+    #[test]
+    fn scaling_multiplies_quantities_exactly() {
+        let mut recipe = recipe(Some(4));
+        recipe.scale_to_servings(6).unwrap();
+
+        let ingredients = &recipe.recipe_components[0].component_ingredients;
+        assert_eq!(ingredients[0].quantity, Quantity::new(1, 2).unwrap());
+        assert_eq!(recipe.servings, Some(6));
+    }
+
+    #[test]
+    fn scaling_leaves_non_scalable_units_alone() {
+        let mut recipe = recipe(Some(4));
+        recipe.scale_to_servings(8).unwrap();
+
+        let ingredients = &recipe.recipe_components[0].component_ingredients;
+        assert_eq!(ingredients[1].quantity, Quantity::whole(1));
+        assert_eq!(ingredients[2].quantity, Quantity::whole(2));
+    }
+
+    #[test]
+    fn recipes_without_servings_are_not_scaled() {
+        let mut recipe = recipe(None);
+        recipe.scale_to_servings(8).unwrap();
+
+        assert_eq!(recipe.recipe_components[0].component_ingredients[0].quantity, Quantity::new(1, 3).unwrap());
+        assert_eq!(recipe.servings, None);
+    }
+
+    #[test]
+    fn zero_original_servings_is_an_error() {
+        let mut recipe = recipe(Some(0));
+        assert!(recipe.scale_to_servings(2).is_err());
+    }
+
+    #[test]
+    fn times_are_at_most_a_week() {
+        let mut params = SaveRecipeParams::from(&recipe(Some(4)));
+        params.prep_time = Some(MAX_STEP_MINUTES);
+        params.rest_time = Some(MAX_STEP_MINUTES + 1);
+        params.components.clear();
+
+        assert_eq!(params.validate(&[]), Err(vec!["rest_time cannot be more than 10080 minutes".to_string()]));
+    }
+
+    #[test]
+    fn ingredient_filters_match_wildcards_literally() {
+        let params = RecipeSearchParams { ingredient: Some(" 50%_dark\\ ".to_string()), ..Default::default() };
+        assert_eq!(params.ingredient_pattern().as_deref(), Some("%50\\%\\_dark\\\\%"));
+    }
+}
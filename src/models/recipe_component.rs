@@ -30,7 +30,10 @@ impl CreateRecipeComponentParams {
 }
 
 impl RecipeComponent {
-    pub async fn create(db: &PgPool, create_params: &CreateRecipeComponentParams) -> Result<Option<Self>, crate::models::Error> {
+    pub async fn create<'e, E>(db: E, create_params: &CreateRecipeComponentParams) -> Result<Option<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let recipe_component = sqlx::query_as(
             "INSERT INTO recipe_components (recipe_id, name, is_optional)
              VALUES ($1, $2, $3)
@@ -1,5 +1,6 @@
 use serde::{Serialize, Deserialize};
 use sqlx::{FromRow, PgPool};
+use crate::domain::{Quantity, QuantityError};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct RecipeComponentIngredient {
@@ -16,8 +17,7 @@ pub struct CreateRecipeComponentIngredientParams {
     pub recipe_component_id: i32,
     pub ingredient_id: i32,
     pub unit_id: i32,
-    pub quantity_numerator: i32,
-    pub quantity_denominator: i32,
+    pub quantity: Quantity,
     pub is_optional: bool,
 }
 
@@ -26,15 +26,13 @@ impl CreateRecipeComponentIngredientParams {
         recipe_component_id: i32,
         ingredient_id: i32,
         unit_id: i32,
-        quantity_numerator: i32,
-        quantity_denominator: i32,
+        quantity: Quantity,
     ) -> Self {
         Self {
             recipe_component_id,
             ingredient_id,
             unit_id,
-            quantity_numerator,
-            quantity_denominator,
+            quantity,
             is_optional: false,
         }
     }
@@ -46,7 +44,14 @@ impl CreateRecipeComponentIngredientParams {
 }
 
 impl RecipeComponentIngredient {
-    pub async fn create(db: &PgPool, create_params: &CreateRecipeComponentIngredientParams) -> Result<Option<Self>, crate::models::Error> {
+    pub fn quantity(&self) -> Result<Quantity, QuantityError> {
+        Quantity::new(self.quantity_numerator, self.quantity_denominator)
+    }
+
+    pub async fn create<'e, E>(db: E, create_params: &CreateRecipeComponentIngredientParams) -> Result<Option<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let recipe_component_ingredient = sqlx::query_as(
             "INSERT INTO recipe_component_ingredients (recipe_component_id, ingredient_id, unit_id, quantity_numerator, quantity_denominator, is_optional)
              VALUES ($1, $2, $3, $4, $5, $6)
@@ -55,8 +60,8 @@ impl RecipeComponentIngredient {
         .bind(create_params.recipe_component_id)
         .bind(create_params.ingredient_id)
         .bind(create_params.unit_id)
-        .bind(create_params.quantity_numerator)
-        .bind(create_params.quantity_denominator)
+        .bind(create_params.quantity.numerator())
+        .bind(create_params.quantity.denominator())
         .bind(create_params.is_optional)
         .fetch_optional(db)
         .await?;
@@ -30,7 +30,10 @@ impl CreateRecipeInstructionParams {
 }
 
 impl RecipeInstruction {
-    pub async fn create(db: &PgPool, create_params: &CreateRecipeInstructionParams) -> Result<Option<Self>, crate::models::Error> {
+    pub async fn create<'e, E>(db: E, create_params: &CreateRecipeInstructionParams) -> Result<Option<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let recipe_instruction = sqlx::query_as(
             "INSERT INTO recipe_instructions (recipe_id, order_idx, title)
              VALUES ($1, $2, $3)
@@ -34,7 +34,10 @@ impl CreateRecipeInstructionStepParams {
 }
 
 impl RecipeInstructionStep {
-    pub async fn create(db: &PgPool, create_params: &CreateRecipeInstructionStepParams) -> Result<Option<Self>, crate::models::Error> {
+    pub async fn create<'e, E>(db: E, create_params: &CreateRecipeInstructionStepParams) -> Result<Option<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let recipe_instruction_step = sqlx::query_as(
             "INSERT INTO recipe_instruction_steps (recipe_id, recipe_instruction_id, step_number, content)
              VALUES ($1, $2, $3, $4)
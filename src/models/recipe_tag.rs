@@ -14,7 +14,10 @@ pub struct CreateRecipeTagParams {
 }
 
 impl RecipeTag {
-    pub async fn create(db: &PgPool, params: &CreateRecipeTagParams) -> Result<Self, crate::models::Error> {
+    pub async fn create<'e, E>(db: E, params: &CreateRecipeTagParams) -> Result<Self, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let recipe_tag = sqlx::query_as(
             "INSERT INTO recipes_tags (recipe_id, tag_id) VALUES ($1, $2) RETURNING *"
         )
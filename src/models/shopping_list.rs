@@ -0,0 +1,318 @@
+use serde::{Serialize, Deserialize};
+use sqlx::{FromRow, PgPool};
+use time::Date;
+use crate::domain::{Quantity, QuantityError};
+use crate::models::pantry_item::{take_from_stock, PantryItem, PantryStock};
+use crate::models::unit::{is_scalable, UnitConverter};
+
+/// A merged list of everything needed to cook a set of recipes.
+/// Quantities of the same ingredient in the same unit are summed together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShoppingList {
+    pub recipe_ids: Vec<i32>,
+    /// What is left to buy once the pantry has been used
+    pub items: Vec<ShoppingListItem>,
+    /// How much of each item the pantry already covers
+    pub from_pantry: Vec<ShoppingListItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShoppingListItem {
+    pub ingredient_id: i32,
+    pub name: String,
+    pub unit: String,
+    pub quantity: Quantity,
+    pub is_optional: bool,
+}
+
+/// One row per recipe_component_ingredient of the selected recipes
+#[derive(Debug, Clone, FromRow)]
+struct ShoppingListRow {
+    ingredient_id: i32,
+    name: String,
+    unit: String,
+    quantity_numerator: i32,
+    quantity_denominator: i32,
+    is_optional: bool,
+}
+
+/// One row per recipe_component_ingredient of each meal planned, with the servings to scale it to
+#[derive(Debug, Clone, FromRow)]
+struct PlannedIngredientRow {
+    ingredient_id: i32,
+    name: String,
+    unit: String,
+    quantity_numerator: i32,
+    quantity_denominator: i32,
+    is_optional: bool,
+    recipe_id: i32,
+    planned_servings: Option<i32>,
+    recipe_servings: Option<i32>,
+}
+
+impl ShoppingList {
+    /// The list for the user's recipes, less what is already in their pantry
+    pub async fn from_recipes(db: &PgPool, user_id: &uuid::Uuid, recipe_ids: &[i32]) -> Result<Self, crate::models::Error> {
+        let items = Self::items_for_recipes(db, user_id, recipe_ids).await?;
+
+        Self::less_pantry(db, user_id, recipe_ids.to_vec(), items).await
+    }
+
+    /// The list for every meal the user planned from `from` to `to`, both included, less what
+    /// is already in their pantry. A recipe planned twice is bought for twice, and meals
+    /// planned for a different number of servings are scaled.
+    pub async fn from_meal_plan(db: &PgPool, user_id: &uuid::Uuid, from: Date, to: Date) -> Result<Self, crate::models::Error> {
+        let rows: Vec<PlannedIngredientRow> = sqlx::query_as(
+            "SELECT
+                 i.id AS ingredient_id,
+                 i.name,
+                 u.name AS unit,
+                 rci.quantity_numerator,
+                 rci.quantity_denominator,
+                 (rci.is_optional OR rc.is_optional) AS is_optional,
+                 r.id AS recipe_id,
+                 mp.servings AS planned_servings,
+                 r.servings AS recipe_servings
+             FROM meal_plans mp
+             JOIN recipes r ON r.id = mp.recipe_id
+             JOIN recipe_components rc ON rc.recipe_id = r.id
+             JOIN recipe_component_ingredients rci ON rci.recipe_component_id = rc.id
+             JOIN ingredients i ON i.id = rci.ingredient_id
+             JOIN units u ON u.id = rci.unit_id
+             WHERE mp.user_id = $1 AND r.user_id = $1 AND mp.planned_on BETWEEN $2 AND $3
+             ORDER BY mp.planned_on, mp.id, rci.id"
+        )
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(db)
+        .await?;
+
+        let mut recipe_ids: Vec<i32> = rows.iter().map(|row| row.recipe_id).collect();
+        recipe_ids.sort_unstable();
+        recipe_ids.dedup();
+        let items = merge_rows(scale_planned_rows(rows)?)?;
+
+        Self::less_pantry(db, user_id, recipe_ids, items).await
+    }
+
+    async fn less_pantry(db: &PgPool, user_id: &uuid::Uuid, recipe_ids: Vec<i32>, items: Vec<ShoppingListItem>) -> Result<Self, crate::models::Error> {
+        let mut stock = PantryItem::stock_for_user(db, user_id).await?;
+        let converter = UnitConverter::load(db).await?;
+        let (items, from_pantry) = subtract_stock(items, &mut stock, &converter)?;
+
+        Ok(Self {
+            recipe_ids,
+            items,
+            from_pantry,
+        })
+    }
+
+    /// Everything the user's recipes call for, whether or not it is in the pantry
+    pub async fn items_for_recipes(db: &PgPool, user_id: &uuid::Uuid, recipe_ids: &[i32]) -> Result<Vec<ShoppingListItem>, crate::models::Error> {
+        // An ingredient is optional if it is marked optional itself or if it belongs to an
+        // optional component
+        let rows: Vec<ShoppingListRow> = sqlx::query_as(
+            "SELECT
+                 i.id AS ingredient_id,
+                 i.name,
+                 u.name AS unit,
+                 rci.quantity_numerator,
+                 rci.quantity_denominator,
+                 (rci.is_optional OR rc.is_optional) AS is_optional
+             FROM recipe_component_ingredients rci
+             JOIN recipe_components rc ON rc.id = rci.recipe_component_id
+             JOIN recipes r ON r.id = rc.recipe_id
+             JOIN ingredients i ON i.id = rci.ingredient_id
+             JOIN units u ON u.id = rci.unit_id
+             WHERE r.user_id = $1 AND r.id = ANY($2)
+             ORDER BY rci.id"
+        )
+        .bind(user_id)
+        .bind(recipe_ids)
+        .fetch_all(db)
+        .await?;
+
+        Ok(merge_rows(rows)?)
+    }
+}
+
+/// Covers what it can of each item from the pantry. Answers the items that are still to be
+/// bought and how much of each item the pantry covered.
+fn subtract_stock(
+    items: Vec<ShoppingListItem>,
+    stock: &mut [PantryStock],
+    converter: &UnitConverter,
+) -> Result<(Vec<ShoppingListItem>, Vec<ShoppingListItem>), QuantityError> {
+    let mut to_buy = Vec::new();
+    let mut from_pantry = Vec::new();
+    for item in items {
+        let needed = take_from_stock(stock, item.ingredient_id, &item.unit, item.quantity, converter)?;
+        if needed < item.quantity {
+            from_pantry.push(ShoppingListItem {
+                quantity: item.quantity.checked_sub(&needed)?,
+                ..item.clone()
+            });
+        }
+        if needed > Quantity::zero() {
+            to_buy.push(ShoppingListItem { quantity: needed, ..item });
+        }
+    }
+
+    Ok((to_buy, from_pantry))
+}
+
+/// Scales each planned meal's ingredients to the servings it was planned for, the same way
+/// the recipe page scales them
+fn scale_planned_rows(rows: Vec<PlannedIngredientRow>) -> Result<Vec<ShoppingListRow>, QuantityError> {
+    rows.into_iter()
+        .map(|row| {
+            let mut quantity = Quantity::new(row.quantity_numerator, row.quantity_denominator)?;
+            if let (Some(planned_servings), Some(recipe_servings)) = (row.planned_servings, row.recipe_servings) {
+                if is_scalable(&row.unit) {
+                    quantity = quantity.checked_mul(&Quantity::new(planned_servings, recipe_servings)?)?;
+                }
+            }
+
+            Ok(ShoppingListRow {
+                ingredient_id: row.ingredient_id,
+                name: row.name,
+                unit: row.unit,
+                quantity_numerator: quantity.numerator(),
+                quantity_denominator: quantity.denominator(),
+                is_optional: row.is_optional,
+            })
+        })
+        .collect()
+}
+
+/// Sums the rows that share an ingredient and unit. Items keep the order in which their
+/// ingredient first appeared and are only optional if every recipe lists them as optional.
+fn merge_rows(rows: Vec<ShoppingListRow>) -> Result<Vec<ShoppingListItem>, QuantityError> {
+    let mut items: Vec<ShoppingListItem> = Vec::new();
+    for row in rows {
+        let quantity = Quantity::new(row.quantity_numerator, row.quantity_denominator)?;
+        match items.iter_mut().find(|item| item.ingredient_id == row.ingredient_id && item.unit == row.unit) {
+            Some(item) => {
+                item.quantity = item.quantity.checked_add(&quantity)?;
+                item.is_optional = item.is_optional && row.is_optional;
+            },
+            None => items.push(ShoppingListItem {
+                ingredient_id: row.ingredient_id,
+                name: row.name,
+                unit: row.unit,
+                quantity,
+                is_optional: row.is_optional,
+            }),
+        }
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_rows, scale_planned_rows, subtract_stock, PlannedIngredientRow, ShoppingListRow};
+    use crate::domain::Quantity;
+    use crate::models::pantry_item::PantryStock;
+    use crate::models::unit::{Unit, UnitConverter, UnitDimension, UnitSystem};
+
+    fn row(ingredient_id: i32, unit: &str, quantity_numerator: i32, quantity_denominator: i32, is_optional: bool) -> ShoppingListRow {
+        ShoppingListRow {
+            ingredient_id,
+            name: format!("ingredient {}", ingredient_id),
+            unit: unit.to_string(),
+            quantity_numerator,
+            quantity_denominator,
+            is_optional,
+        }
+    }
+
+    #[test]
+    fn same_ingredient_and_unit_are_summed() {
+        let items = merge_rows(vec![row(1, "cup", 1, 4, false), row(1, "cup", 1, 2, false)]).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].quantity, Quantity::new(3, 4).unwrap());
+    }
+
+    #[test]
+    fn sums_are_reduced() {
+        let items = merge_rows(vec![row(1, "cup", 1, 4, false), row(1, "cup", 1, 4, false)]).unwrap();
+        assert_eq!(items[0].quantity, Quantity::new(1, 2).unwrap());
+    }
+
+    #[test]
+    fn different_units_are_kept_apart() {
+        let items = merge_rows(vec![row(1, "cup", 1, 1, false), row(1, "tablespoon", 2, 1, false), row(2, "cup", 1, 1, false)]).unwrap();
+        assert_eq!(items.len(), 3);
+    }
+
+    #[test]
+    fn zero_denominator_is_rejected() {
+        assert!(merge_rows(vec![row(1, "cup", 1, 0, false)]).is_err());
+    }
+
+    #[test]
+    fn item_is_required_if_any_recipe_requires_it() {
+        let items = merge_rows(vec![row(1, "cup", 1, 1, true), row(1, "cup", 1, 1, false)]).unwrap();
+        assert!(!items[0].is_optional);
+
+        let items = merge_rows(vec![row(1, "cup", 1, 1, true), row(1, "cup", 1, 1, true)]).unwrap();
+        assert!(items[0].is_optional);
+    }
+
+    #[test]
+    fn pantry_stock_is_taken_off_the_list() {
+        let cup = Unit {
+            id: 0,
+            name: "cup".to_string(),
+            abbreviation: None,
+            system: UnitSystem::Universal,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(236.5882365),
+        };
+        let items = merge_rows(vec![row(1, "cup", 1, 1, false), row(2, "cup", 1, 1, false), row(3, "cup", 1, 1, false)]).unwrap();
+        let mut stock = vec![
+            PantryStock { id: 1, ingredient_id: 1, unit: "cup".to_string(), quantity: Quantity::new(1, 4).unwrap() },
+            PantryStock { id: 2, ingredient_id: 2, unit: "cup".to_string(), quantity: Quantity::whole(2) },
+        ];
+
+        let (to_buy, from_pantry) = subtract_stock(items, &mut stock, &UnitConverter::new(vec![cup])).unwrap();
+        assert_eq!(to_buy.iter().map(|item| (item.ingredient_id, item.quantity)).collect::<Vec<_>>(), [(1, Quantity::new(3, 4).unwrap()), (3, Quantity::whole(1))]);
+        assert_eq!(from_pantry.iter().map(|item| (item.ingredient_id, item.quantity)).collect::<Vec<_>>(), [(1, Quantity::new(1, 4).unwrap()), (2, Quantity::whole(1))]);
+        assert_eq!(stock[1].quantity, Quantity::whole(1));
+    }
+
+    fn planned_row(unit: &str, quantity_numerator: i32, planned_servings: Option<i32>, recipe_servings: Option<i32>) -> PlannedIngredientRow {
+        PlannedIngredientRow {
+            ingredient_id: 1,
+            name: "ingredient 1".to_string(),
+            unit: unit.to_string(),
+            quantity_numerator,
+            quantity_denominator: 1,
+            is_optional: false,
+            recipe_id: 1,
+            planned_servings,
+            recipe_servings,
+        }
+    }
+
+    #[test]
+    fn planned_meals_are_scaled_to_their_servings() {
+        let rows = scale_planned_rows(vec![
+            planned_row("cup", 1, Some(6), Some(4)),
+            planned_row("pinch", 1, Some(6), Some(4)),
+            planned_row("cup", 1, None, Some(4)),
+            planned_row("cup", 1, Some(6), None),
+        ]).unwrap();
+        let quantities: Vec<(i32, i32)> = rows.iter().map(|row| (row.quantity_numerator, row.quantity_denominator)).collect();
+        assert_eq!(quantities, [(3, 2), (1, 1), (1, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn the_same_recipe_planned_twice_is_bought_twice() {
+        let rows = scale_planned_rows(vec![planned_row("cup", 1, None, Some(4)), planned_row("cup", 1, Some(2), Some(4))]).unwrap();
+        let items = merge_rows(rows).unwrap();
+        assert_eq!(items[0].quantity, Quantity::new(3, 2).unwrap());
+    }
+}
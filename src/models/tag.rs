@@ -21,7 +21,10 @@ impl CreateTagParams {
 }
 
 impl Tag {
-    pub async fn create(db: &PgPool, params: &CreateTagParams) -> Result<Option<Self>, crate::models::Error> {
+    pub async fn create<'e, E>(db: E, params: &CreateTagParams) -> Result<Option<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let tag = sqlx::query_as(
             "INSERT INTO tags (name) 
              VALUES ($1) 
@@ -44,7 +47,10 @@ impl Tag {
         Ok(recipe_ingredient)
     }
 
-    pub async fn find_by_name(db: &PgPool, name: &str) -> Result<Option<Self>, crate::models::Error> {
+    pub async fn find_by_name<'e, E>(db: E, name: &str) -> Result<Option<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let unit = sqlx::query_as("SELECT * FROM tags WHERE name = $1")
             .bind(name)
             .fetch_optional(db)
@@ -53,7 +59,10 @@ impl Tag {
         Ok(unit)
     }
 
-    pub async fn find_by_recipe_id(db: &PgPool, recipe_id: i32) -> Result<Vec<Self>, crate::models::Error> {
+    pub async fn find_by_recipe_id<'e, E>(db: E, recipe_id: i32) -> Result<Vec<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let tags = sqlx::query_as(
             "SELECT t.* FROM tags t
              JOIN recipes_tags rt ON t.id = rt.tag_id
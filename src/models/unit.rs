@@ -1,6 +1,17 @@
 use serde::{Serialize, Deserialize};
 use sqlx::{PgPool, FromRow};
 use std::str::FromStr;
+use crate::domain::{Quantity, UnitMatcher};
+
+/// Amounts in these units describe how to season rather than how much, so they stay the
+/// same when a recipe is scaled
+pub const NON_SCALABLE_UNITS: &[&str] = &["to_taste", "pinch"];
+/// How far off, as a share of the exact amount, a readable amount may be
+const READABLE_ERROR: f64 = 0.05;
+
+pub fn is_scalable(unit_name: &str) -> bool {
+    !NON_SCALABLE_UNITS.contains(&unit_name)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Unit {
@@ -8,11 +19,15 @@ pub struct Unit {
     pub name: String,
     pub abbreviation: Option<String>,
     pub system: UnitSystem,
+    pub dimension: UnitDimension,
+    /// How many of the dimension's base unit (grams or milliliters) one of this unit is
+    pub base_factor: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[derive(sqlx::Type)]
 #[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
 pub enum UnitSystem {
     Metric,
     Imperial,
@@ -42,10 +57,45 @@ impl std::fmt::Display for UnitSystem {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum UnitDimension {
+    Mass,
+    Volume,
+    Count,
+}
+
+impl FromStr for UnitDimension {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mass" => Ok(UnitDimension::Mass),
+            "volume" => Ok(UnitDimension::Volume),
+            "count" => Ok(UnitDimension::Count),
+            _ => Err(format!("Invalid unit dimension: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for UnitDimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnitDimension::Mass => write!(f, "mass"),
+            UnitDimension::Volume => write!(f, "volume"),
+            UnitDimension::Count => write!(f, "count"),
+        }
+    }
+}
+
 pub struct CreateUnitParams {
     pub name: String,
     pub abbreviation: Option<String>,
     pub system: UnitSystem,
+    pub dimension: UnitDimension,
+    pub base_factor: Option<f64>,
 }
 
 impl CreateUnitParams {
@@ -54,18 +104,25 @@ impl CreateUnitParams {
             name: name.clone(),
             abbreviation: None,
             system: UnitSystem::Universal,
+            dimension: UnitDimension::Count,
+            base_factor: None,
         }
     }
 }
 
 impl Unit {
-    pub async fn create(db: &PgPool, params: &CreateUnitParams) -> Result<Self, crate::models::Error> {
+    pub async fn create<'e, E>(db: E, params: &CreateUnitParams) -> Result<Self, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let unit = sqlx::query_as(
-            "INSERT INTO units (name, abbreviation, system) VALUES ($1, $2, $3) RETURNING *"
+            "INSERT INTO units (name, abbreviation, system, dimension, base_factor) VALUES ($1, $2, $3, $4, $5) RETURNING *"
         )
         .bind(&params.name)
         .bind(&params.abbreviation)
         .bind(params.system.to_string())
+        .bind(params.dimension.to_string())
+        .bind(params.base_factor)
         .fetch_one(db)
         .await?;
 
@@ -81,7 +138,10 @@ impl Unit {
         Ok(unit)
     }
 
-    pub async fn find_by_name(db: &PgPool, name: &str) -> Result<Option<Self>, crate::models::Error> {
+    pub async fn find_by_name<'e, E>(db: E, name: &str) -> Result<Option<Self>, crate::models::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
         let unit = sqlx::query_as("SELECT * FROM units WHERE name = $1")
             .bind(name)
             .fetch_optional(db)
@@ -89,83 +149,248 @@ impl Unit {
 
         Ok(unit)
     }
+
+    pub async fn list(db: &PgPool) -> Result<Vec<Self>, crate::models::Error> {
+        let units = sqlx::query_as("SELECT * FROM units ORDER BY name")
+            .fetch_all(db)
+            .await?;
+
+        Ok(units)
+    }
+
+    /// Units convert into each other when they measure the same dimension and both have a factor
+    pub fn can_convert_to(&self, other: &Unit) -> bool {
+        self.dimension == other.dimension && self.base_factor.is_some() && other.base_factor.is_some()
+    }
+
+    /// The amount of `to` that equals `quantity` of this unit, or None if the units aren't compatible
+    pub fn convert(&self, quantity: &Quantity, to: &Unit) -> Option<f64> {
+        match (self.base_factor, to.base_factor) {
+            (Some(from_factor), Some(to_factor)) if self.can_convert_to(to) => Some(quantity.to_f64() * from_factor / to_factor),
+            _ => None,
+        }
+    }
 }
 
-pub async fn create_default_units(db: &PgPool) -> Result<(), crate::models::Error> {
-    let default_units = vec![
+/// Converts quantities between the known units and picks readable units when a recipe is
+/// shown in a different unit system than it was written in.
+#[derive(Debug, Clone)]
+pub struct UnitConverter {
+    units: Vec<Unit>,
+}
+
+impl UnitConverter {
+    pub fn new(units: Vec<Unit>) -> Self {
+        Self { units }
+    }
+
+    pub async fn load(db: &PgPool) -> Result<Self, crate::models::Error> {
+        Ok(Self::new(Unit::list(db).await?))
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Unit> {
+        self.units.iter().find(|unit| unit.name == name)
+    }
+
+    /// Converts `quantity` of the unit named `from` into the unit named `to`
+    pub fn convert(&self, quantity: &Quantity, from: &str, to: &str) -> Option<Quantity> {
+        let value = self.convert_value(quantity, from, to)?;
+
+        self.approximate(value, to)
+    }
+
+    /// Like `convert`, but without rounding, for amounts that are added up before being shown
+    pub fn convert_value(&self, quantity: &Quantity, from: &str, to: &str) -> Option<f64> {
+        self.find(from)?.convert(quantity, self.find(to)?)
+    }
+
+    /// The readable amount of the unit named `unit` closest to `value`
+    pub fn approximate(&self, value: f64, unit: &str) -> Option<Quantity> {
+        let unit = self.find(unit)?;
+
+        Quantity::approximate(value, readable_denominators(value, &unit.system)).ok()
+    }
+
+    /// Rewrites a quantity into the given unit system. Universal units such as cups are left
+    /// alone, and so are units that are already in the target system or can't be converted.
+    pub fn to_system(&self, quantity: &Quantity, from: &str, system: &UnitSystem) -> Option<(Quantity, &Unit)> {
+        let from = self.find(from)?;
+        if from.system == UnitSystem::Universal || from.system == *system {
+            return None;
+        }
+
+        // Prefer units of the target system and fall back to universal ones, since cups and
+        // spoons are the only imperial volume units
+        let mut candidates: Vec<&Unit> = self.units.iter()
+            .filter(|unit| unit.system == *system && from.can_convert_to(unit))
+            .collect();
+        if candidates.is_empty() {
+            candidates = self.units.iter()
+                .filter(|unit| unit.system == UnitSystem::Universal && from.can_convert_to(unit))
+                .collect();
+        }
+        candidates.sort_by(|a, b| b.base_factor.partial_cmp(&a.base_factor).unwrap_or(std::cmp::Ordering::Equal));
+
+        // Use the largest unit that gives a readable amount, e.g. "1/2 cup" rather than "8 tablespoon"
+        let mut chosen: Option<(Quantity, &Unit)> = None;
+        for (idx, unit) in candidates.iter().enumerate() {
+            let value = from.convert(quantity, unit)?;
+            let converted = Quantity::approximate(value, readable_denominators(value, system)).ok()?;
+            let next_value = candidates.get(idx + 1).and_then(|next| from.convert(quantity, next));
+            if is_readable(value, &converted, next_value, system) {
+                return Some((converted, unit));
+            }
+            chosen = Some((converted, unit));
+        }
+
+        chosen
+    }
+}
+
+/// Metric amounts are kept to halves and quarters; imperial cooks are used to thirds and eighths.
+/// Anything from 10 up is rounded to a whole number.
+fn readable_denominators(value: f64, system: &UnitSystem) -> &'static [i32] {
+    if value.abs() >= 10.0 {
+        &[1]
+    } else if *system == UnitSystem::Metric {
+        &[1, 2, 4]
+    } else {
+        &[1, 2, 3, 4, 8]
+    }
+}
+
+/// At least one whole unit is readable as long as it is close to the exact amount, otherwise
+/// the next smaller unit is used, e.g. "1361 gram" rather than "1 1/4 kilogram" for 3 pounds.
+/// Outside of metric a fraction down to 1/4 is readable too, as long as it is close to the
+/// exact amount and the next smaller unit would need four or more.
+fn is_readable(value: f64, converted: &Quantity, next_value: Option<f64>, system: &UnitSystem) -> bool {
+    let error = (converted.to_f64() - value).abs() / value;
+    if value >= 1.0 {
+        return error <= READABLE_ERROR;
+    }
+    if *system == UnitSystem::Metric || value < 0.25 {
+        return false;
+    }
+
+    error <= READABLE_ERROR && next_value.is_some_and(|next_value| next_value >= 4.0)
+}
+
+/// Matches ingredient lines against the names and abbreviations of `units`
+pub fn unit_matcher(units: &[Unit]) -> UnitMatcher {
+    UnitMatcher::new(units.iter().map(|unit| (unit.name.as_str(), unit.abbreviation.as_deref())))
+}
+
+/// Matches ingredient lines against the units every database starts with
+pub fn default_unit_matcher() -> UnitMatcher {
+    let units = default_units();
+    UnitMatcher::new(units.iter().map(|unit| (unit.name.as_str(), unit.abbreviation.as_deref())))
+}
+
+fn default_units() -> Vec<CreateUnitParams> {
+    vec![
         CreateUnitParams {
             name: "gram".to_string(),
             abbreviation: Some("g".to_string()),
             system: UnitSystem::Metric,
+            dimension: UnitDimension::Mass,
+            base_factor: Some(1.0),
         },
         CreateUnitParams {
             name: "kilogram".to_string(),
             abbreviation: Some("kg".to_string()),
             system: UnitSystem::Metric,
+            dimension: UnitDimension::Mass,
+            base_factor: Some(1000.0),
         },
         CreateUnitParams {
             name: "milliliter".to_string(),
             abbreviation: Some("ml".to_string()),
             system: UnitSystem::Metric,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(1.0),
         },
         CreateUnitParams {
             name: "liter".to_string(),
             abbreviation: Some("L".to_string()),
             system: UnitSystem::Metric,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(1000.0),
         },
         CreateUnitParams {
             name: "teaspoon".to_string(),
             abbreviation: Some("tsp".to_string()),
             system: UnitSystem::Universal,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(4.92892159375),
         },
         CreateUnitParams {
             name: "tablespoon".to_string(),
             abbreviation: Some("tbsp".to_string()),
             system: UnitSystem::Universal,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(14.78676478125),
         },
         CreateUnitParams {
             name: "cup".to_string(),
             abbreviation: Some("cup".to_string()),
             system: UnitSystem::Universal,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(236.5882365),
         },
         CreateUnitParams {
             name: "ounce".to_string(),
             abbreviation: Some("oz".to_string()),
             system: UnitSystem::Imperial,
+            dimension: UnitDimension::Mass,
+            base_factor: Some(28.349523125),
         },
         CreateUnitParams {
             name: "pound".to_string(),
             abbreviation: Some("lb".to_string()),
             system: UnitSystem::Imperial,
+            dimension: UnitDimension::Mass,
+            base_factor: Some(453.59237),
         },
         CreateUnitParams {
             name: "piece".to_string(),
             abbreviation: Some("pc".to_string()),
             system: UnitSystem::Universal,
+            dimension: UnitDimension::Count,
+            base_factor: None,
         },
         CreateUnitParams {
             name: "pinch".to_string(),
             abbreviation: None,
             system: UnitSystem::Universal,
+            dimension: UnitDimension::Count,
+            base_factor: None,
         },
         CreateUnitParams {
             name: "can".to_string(),
             abbreviation: None,
             system: UnitSystem::Universal,
+            dimension: UnitDimension::Count,
+            base_factor: None,
         },
         CreateUnitParams {
             name: "to_taste".to_string(),
             abbreviation: None,
             system: UnitSystem::Universal,
+            dimension: UnitDimension::Count,
+            base_factor: None,
         },
         CreateUnitParams {
             name: "package".to_string(),
             abbreviation: None,
             system: UnitSystem::Universal,
+            dimension: UnitDimension::Count,
+            base_factor: None,
         },
-    ];
+    ]
+}
 
-    for unit_params in default_units {
+pub async fn create_default_units(db: &PgPool) -> Result<(), crate::models::Error> {
+    for unit_params in default_units() {
         if Unit::find_by_name(db, &unit_params.name).await?.is_none() {
             Unit::create(db, &unit_params).await?;
         }
@@ -173,3 +398,91 @@ pub async fn create_default_units(db: &PgPool) -> Result<(), crate::models::Erro
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Unit, UnitConverter, UnitDimension, UnitSystem};
+    use crate::domain::Quantity;
+
+    fn unit(name: &str, system: UnitSystem, dimension: UnitDimension, base_factor: Option<f64>) -> Unit {
+        Unit {
+            id: 0,
+            name: name.to_string(),
+            abbreviation: None,
+            system,
+            dimension,
+            base_factor,
+        }
+    }
+
+    fn converter() -> UnitConverter {
+        UnitConverter::new(vec![
+            unit("gram", UnitSystem::Metric, UnitDimension::Mass, Some(1.0)),
+            unit("kilogram", UnitSystem::Metric, UnitDimension::Mass, Some(1000.0)),
+            unit("milliliter", UnitSystem::Metric, UnitDimension::Volume, Some(1.0)),
+            unit("liter", UnitSystem::Metric, UnitDimension::Volume, Some(1000.0)),
+            unit("teaspoon", UnitSystem::Universal, UnitDimension::Volume, Some(4.92892159375)),
+            unit("tablespoon", UnitSystem::Universal, UnitDimension::Volume, Some(14.78676478125)),
+            unit("cup", UnitSystem::Universal, UnitDimension::Volume, Some(236.5882365)),
+            unit("ounce", UnitSystem::Imperial, UnitDimension::Mass, Some(28.349523125)),
+            unit("pound", UnitSystem::Imperial, UnitDimension::Mass, Some(453.59237)),
+            unit("piece", UnitSystem::Universal, UnitDimension::Count, None),
+        ])
+    }
+
+    #[test]
+    fn converts_between_compatible_units() {
+        let converter = converter();
+        assert_eq!(converter.convert(&Quantity::whole(2), "pound", "ounce"), Some(Quantity::whole(32)));
+        assert_eq!(converter.convert(&Quantity::whole(3), "teaspoon", "tablespoon"), Some(Quantity::whole(1)));
+    }
+
+    #[test]
+    fn incompatible_units_are_not_converted() {
+        let converter = converter();
+        assert_eq!(converter.convert(&Quantity::whole(1), "cup", "gram"), None);
+        assert_eq!(converter.convert(&Quantity::whole(1), "piece", "gram"), None);
+        assert_eq!(converter.convert(&Quantity::whole(1), "handful", "gram"), None);
+    }
+
+    #[test]
+    fn grams_are_shown_in_imperial() {
+        let converter = converter();
+        let (quantity, unit) = converter.to_system(&Quantity::whole(100), "gram", &UnitSystem::Imperial).unwrap();
+        assert_eq!((quantity.to_string().as_str(), unit.name.as_str()), ("3 1/2", "ounce"));
+
+        let (quantity, unit) = converter.to_system(&Quantity::whole(1000), "gram", &UnitSystem::Imperial).unwrap();
+        assert_eq!((quantity.to_string().as_str(), unit.name.as_str()), ("2 1/4", "pound"));
+    }
+
+    #[test]
+    fn milliliters_are_shown_in_cups_and_spoons() {
+        let converter = converter();
+        let (quantity, unit) = converter.to_system(&Quantity::whole(120), "milliliter", &UnitSystem::Imperial).unwrap();
+        assert_eq!((quantity.to_string().as_str(), unit.name.as_str()), ("1/2", "cup"));
+
+        let (quantity, unit) = converter.to_system(&Quantity::whole(5), "milliliter", &UnitSystem::Imperial).unwrap();
+        assert_eq!((quantity.to_string().as_str(), unit.name.as_str()), ("1", "teaspoon"));
+    }
+
+    #[test]
+    fn pounds_are_shown_in_metric() {
+        let converter = converter();
+        let (quantity, unit) = converter.to_system(&Quantity::new(1, 2).unwrap(), "pound", &UnitSystem::Metric).unwrap();
+        assert_eq!((quantity.to_string().as_str(), unit.name.as_str()), ("227", "gram"));
+
+        let (quantity, unit) = converter.to_system(&Quantity::whole(3), "pound", &UnitSystem::Metric).unwrap();
+        assert_eq!((quantity.to_string().as_str(), unit.name.as_str()), ("1361", "gram"));
+
+        let (quantity, unit) = converter.to_system(&Quantity::whole(11), "pound", &UnitSystem::Metric).unwrap();
+        assert_eq!((quantity.to_string().as_str(), unit.name.as_str()), ("5", "kilogram"));
+    }
+
+    #[test]
+    fn universal_and_same_system_units_are_left_alone() {
+        let converter = converter();
+        assert!(converter.to_system(&Quantity::whole(1), "cup", &UnitSystem::Metric).is_none());
+        assert!(converter.to_system(&Quantity::whole(1), "gram", &UnitSystem::Metric).is_none());
+        assert!(converter.to_system(&Quantity::whole(1), "piece", &UnitSystem::Imperial).is_none());
+    }
+}
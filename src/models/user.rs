@@ -2,6 +2,7 @@ use serde::{Serialize, Deserialize};
 use sqlx::{FromRow, PgPool};
 use crate::models::recipe::Recipe;
 use crate::models::recipe_component::RecipeComponent;
+use crate::models::unit::UnitSystem;
 
 #[derive(Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
@@ -9,6 +10,13 @@ pub struct User {
     pub username: String,
     pub email: String,
     pub password_hash: String,
+    /// Recipes are shown in this unit system. Not every query selects it, hence the default.
+    #[sqlx(default)]
+    pub preferred_unit_system: Option<UnitSystem>,
+    /// Secret for the meal plan's calendar feed, None when there is no feed. Not every query
+    /// selects it, hence the default.
+    #[sqlx(default)]
+    pub calendar_token: Option<String>,
 }
 
 #[derive(Debug, FromRow)]
@@ -65,7 +73,7 @@ impl User {
     }
 
     pub async fn find_by_api_key(db: &PgPool, api_key: &str) -> Result<Option<User>, crate::models::Error> {
-        let user = sqlx::query_as("SELECT id, username, email, password_hash, api_key FROM users WHERE api_key = $1")
+        let user = sqlx::query_as("SELECT * FROM users WHERE api_key = $1")
             .bind(api_key)
             .fetch_optional(db)
             .await?;
@@ -84,6 +92,38 @@ impl User {
         Ok(api_key)
     }
 
+    pub async fn find_by_calendar_token(db: &PgPool, calendar_token: &str) -> Result<Option<User>, crate::models::Error> {
+        let user = sqlx::query_as("SELECT * FROM users WHERE calendar_token = $1")
+            .bind(calendar_token)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(user)
+    }
+
+    /// Replaces the calendar token, so that any feed subscribed with the old one stops working
+    pub async fn generate_calendar_token(&mut self, db: &PgPool) -> Result<String, crate::models::Error> {
+        let calendar_token = uuid::Uuid::new_v4().simple().to_string();
+        sqlx::query("UPDATE users SET calendar_token = $1 WHERE id = $2")
+            .bind(&calendar_token)
+            .bind(self.id)
+            .execute(db)
+            .await?;
+        self.calendar_token = Some(calendar_token.clone());
+
+        Ok(calendar_token)
+    }
+
+    pub async fn revoke_calendar_token(&mut self, db: &PgPool) -> Result<(), crate::models::Error> {
+        sqlx::query("UPDATE users SET calendar_token = NULL WHERE id = $1")
+            .bind(self.id)
+            .execute(db)
+            .await?;
+        self.calendar_token = None;
+
+        Ok(())
+    }
+
     pub async fn update(&self, db: &PgPool) -> Result<(), crate::models::Error> {
         sqlx::query(
             "UPDATE users
@@ -100,6 +140,27 @@ impl User {
         Ok(())
     }
 
+    pub async fn update_preferred_unit_system(&mut self, db: &PgPool, preferred_unit_system: Option<UnitSystem>) -> Result<(), crate::models::Error> {
+        sqlx::query("UPDATE users SET preferred_unit_system = $1 WHERE id = $2")
+            .bind(preferred_unit_system.as_ref().map(|system| system.to_string()))
+            .bind(self.id)
+            .execute(db)
+            .await?;
+        self.preferred_unit_system = preferred_unit_system;
+
+        Ok(())
+    }
+
+    /// The unit system to show recipes in. A requested "metric" or "imperial" wins over the
+    /// saved preference, and "original" shows the units the recipe was written in.
+    pub fn unit_system_for(&self, requested: Option<&str>) -> Option<UnitSystem> {
+        match requested {
+            Some("original") => None,
+            Some(system) => system.parse::<UnitSystem>().ok(),
+            None => self.preferred_unit_system.clone(),
+        }
+    }
+
     pub async fn get_recipes(&self, db: &PgPool) -> Result<Vec<Recipe>, crate::models::Error> {
         let recipes = sqlx::query_as("SELECT * FROM recipes WHERE user_id = $1")
             .bind(self.id)
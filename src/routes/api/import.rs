@@ -0,0 +1,64 @@
+use axum::{
+    extract::Json,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Router,
+};
+use axum::Extension;
+use reqwest::Url;
+use serde::Deserialize;
+use crate::startup::AppState;
+use crate::constants::route_paths;
+use crate::models::chopper_job::{ChopperJob, CreateChopperJobParams};
+use crate::models::recipe::DuplicatePolicy;
+use crate::models::user::User;
+use crate::utils::ApiError;
+use super::chopper::{job_path, ChopperJobResponse};
+
+/// A recipe page for the server to fetch, for clients such as the phone's share sheet
+/// that can't send the page themselves
+#[derive(Debug, Deserialize)]
+pub struct ImportUrlRequest {
+    url: String,
+    #[serde(default, rename = "onDuplicate")]
+    on_duplicate: DuplicatePolicy,
+}
+
+pub fn routes(state: &AppState) -> Router {
+    Router::new()
+        .route(route_paths::IMPORT_URL, post(self::post::url))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), crate::middleware::api_auth::api_key_auth))
+}
+
+mod post {
+    use super::*;
+
+    /// Fetches the page and queues it for the chopper worker like a page sent by the extension
+    #[axum::debug_handler]
+    pub async fn url(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        Json(payload): Json<ImportUrlRequest>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let url = Url::parse(payload.url.trim())
+            .map_err(|_| ApiError::Validation(vec![format!("url: {} is not a URL", payload.url)]))?;
+        let page = state.page_fetcher.fetch(&url).await?;
+
+        let params = CreateChopperJobParams {
+            user_id: user.id,
+            text: page.html,
+            source_url: page.url.to_string(),
+            hostname: page.url.host_str().unwrap_or_default().to_string(),
+            pathname: page.url.path().to_string(),
+            on_duplicate: payload.on_duplicate,
+        };
+        let job = ChopperJob::create(&state.db, &params).await?;
+
+        Ok((
+            StatusCode::ACCEPTED,
+            [(header::LOCATION, job_path(&job.id))],
+            Json(ChopperJobResponse::from(job)),
+        ))
+    }
+}
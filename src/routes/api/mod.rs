@@ -1 +1,3 @@
 pub mod chopper;
+pub mod import;
+pub mod recipes;
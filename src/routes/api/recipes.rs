@@ -0,0 +1,271 @@
+use axum::{
+    extract::{DefaultBodyLimit, Json, Multipart, Path, Query},
+    extract::multipart::MultipartRejection,
+    extract::rejection::JsonRejection,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Router,
+};
+use axum::Extension;
+use serde::Deserialize;
+use crate::startup::AppState;
+use crate::constants::route_paths;
+use crate::formats::cooklang::{parse_cooklang, to_cooklang, CooklangError};
+use crate::formats::archive::{export_archive, ArchiveError, ARCHIVE_FILE_NAME};
+use crate::formats::library::ImportReport;
+use crate::models::cookable::{CookableRecipes, IngredientRef};
+use crate::models::recipe::{Recipe, RecipeSearchParams, SaveRecipeParams};
+use crate::models::unit::{unit_matcher, Unit, UnitConverter};
+use crate::models::user::User;
+use crate::utils::ApiError;
+use crate::routes::recipes::upload::{LibraryUpload, MAX_UPLOAD_BYTES};
+
+#[derive(Debug, Deserialize)]
+pub struct ShowRecipeParams {
+    /// Scales the ingredient amounts to make this many servings
+    pub servings: Option<i32>,
+    /// "metric", "imperial" or "original". Defaults to the user's preferred unit system
+    pub system: Option<String>,
+}
+
+/// What is on hand, as ingredient ids or names: `{"ingredients": [12, "garlic"]}`
+#[derive(Debug, Deserialize)]
+pub struct CookableParams {
+    pub ingredients: Vec<IngredientRef>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportCooklangParams {
+    /// Names recipes without a title, usually the file name
+    pub name: Option<String>,
+}
+
+pub fn routes(state: &AppState) -> Router {
+    Router::new()
+        .route(route_paths::RECIPES, get(self::get::index).post(self::post::create))
+        .route(
+            &format!("{}/:recipe_id", route_paths::RECIPES),
+            get(self::get::show).put(self::put::update).delete(self::delete::destroy),
+        )
+        .route(&format!("{}{}", route_paths::RECIPES, route_paths::SEARCH), get(self::get::search))
+        .route(&format!("{}{}", route_paths::RECIPES, route_paths::COOKABLE), post(self::post::cookable))
+        .route(&format!("{}/cooklang", route_paths::RECIPES), post(self::post::cooklang))
+        .route(&format!("{}/:recipe_id/cooklang", route_paths::RECIPES), get(self::get::cooklang))
+        .route(&format!("{}{}", route_paths::RECIPES, route_paths::EXPORT), get(self::get::export))
+        .route(
+            &format!("{}{}", route_paths::RECIPES, route_paths::IMPORT_LIBRARY),
+            post(self::post::library).layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
+        .layer(axum::middleware::from_fn_with_state(state.clone(), crate::middleware::api_auth::api_key_auth))
+}
+
+fn recipe_path(recipe_id: i32) -> String {
+    format!("{}{}{}/{}", route_paths::API, route_paths::V1, route_paths::RECIPES, recipe_id)
+}
+
+async fn validate(state: &AppState, params: &SaveRecipeParams) -> Result<(), ApiError> {
+    let units = Unit::list(&state.db).await?;
+    params.validate(&units).map_err(ApiError::Validation)
+}
+
+impl From<CooklangError> for ApiError {
+    fn from(err: CooklangError) -> Self {
+        ApiError::Validation(vec![err.to_string()])
+    }
+}
+
+impl From<ArchiveError> for ApiError {
+    fn from(err: ArchiveError) -> Self {
+        match err {
+            ArchiveError::Model(err) => err.into(),
+            err => ApiError::Internal(format!("{:?}", err)),
+        }
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn index(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let recipes = user.get_recipes(&state.db).await?;
+
+        Ok(Json(recipes))
+    }
+
+    /// The user's recipes matching the filters, best matches first
+    pub async fn search(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        Query(params): Query<RecipeSearchParams>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        params.validate().map_err(ApiError::Validation)?;
+        let recipes = Recipe::search(&state.db, &user.id, &params).await?;
+
+        Ok(Json(recipes))
+    }
+
+    pub async fn show(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        Path(recipe_id): Path<i32>,
+        Query(params): Query<ShowRecipeParams>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let mut recipe = Recipe::get_full_recipe_details(&state.db, &user.id, recipe_id).await?;
+        if let Some(servings) = params.servings {
+            if servings < 1 {
+                return Err(ApiError::BadRequest("servings must be at least 1".to_string()));
+            }
+            recipe.scale_to_servings(servings).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+        }
+        if let Some(unit_system) = user.unit_system_for(params.system.as_deref()) {
+            let converter = UnitConverter::load(&state.db).await?;
+            recipe.convert_units(&converter, &unit_system);
+        }
+
+        Ok(Json(recipe))
+    }
+
+    pub async fn cooklang(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        Path(recipe_id): Path<i32>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let recipe = Recipe::get_full_recipe_details(&state.db, &user.id, recipe_id).await?;
+
+        Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], to_cooklang(&recipe)))
+    }
+
+    /// All of the user's recipes as an archive that `POST /recipes/import` restores
+    pub async fn export(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let exported_at = time::OffsetDateTime::now_utc().unix_timestamp();
+        let archive = export_archive(&state.db, &user.id, exported_at).await?;
+
+        Ok((
+            [
+                (header::CONTENT_TYPE, "application/zip".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", ARCHIVE_FILE_NAME)),
+            ],
+            archive,
+        ))
+    }
+}
+
+mod post {
+    use super::*;
+
+    pub async fn create(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        payload: Result<Json<SaveRecipeParams>, JsonRejection>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let Json(params) = payload?;
+        validate(&state, &params).await?;
+        let recipe = Recipe::create_full(&state.db, &user.id, &params).await?;
+        let recipe = Recipe::get_full_recipe_details(&state.db, &user.id, recipe.id).await?;
+
+        Ok((
+            StatusCode::CREATED,
+            [(header::LOCATION, recipe_path(recipe.recipe_id))],
+            Json(recipe),
+        ))
+    }
+
+    /// Ranks the user's recipes by how many of the ingredients they need are on hand
+    pub async fn cookable(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        payload: Result<Json<CookableParams>, JsonRejection>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let Json(params) = payload?;
+        if params.ingredients.is_empty() {
+            return Err(ApiError::Validation(vec!["ingredients can't be empty".to_string()]));
+        }
+        let recipes = CookableRecipes::from_ingredients(&state.db, &user.id, &params.ingredients).await?;
+
+        Ok(Json(recipes))
+    }
+
+    /// Creates a recipe from the text of a `.cook` file
+    pub async fn cooklang(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        Query(params): Query<ImportCooklangParams>,
+        body: String,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let units = Unit::list(&state.db).await?;
+        let mut recipe_params = parse_cooklang(&body, &unit_matcher(&units))?;
+        if recipe_params.name.trim().is_empty() {
+            if let Some(name) = params.name {
+                recipe_params.name = name.trim_end_matches(".cook").to_string();
+            }
+        }
+        recipe_params.validate(&units).map_err(ApiError::Validation)?;
+        let recipe = Recipe::create_full(&state.db, &user.id, &recipe_params).await?;
+        let recipe = Recipe::get_full_recipe_details(&state.db, &user.id, recipe.id).await?;
+
+        Ok((
+            StatusCode::CREATED,
+            [(header::LOCATION, recipe_path(recipe.recipe_id))],
+            Json(recipe),
+        ))
+    }
+
+    /// Imports every recipe in archives or Paprika, MealMaster or Mela exports and
+    /// reports how each one went
+    pub async fn library(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        multipart: Result<Multipart, MultipartRejection>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let upload = LibraryUpload::read(multipart?).await?;
+        let mut report = ImportReport::default();
+        for (file_name, bytes) in &upload.files {
+            report.import_file(&state.db, &user.id, file_name, bytes, upload.on_duplicate).await;
+        }
+
+        Ok(Json(report))
+    }
+}
+
+mod put {
+    use super::*;
+
+    pub async fn update(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        Path(recipe_id): Path<i32>,
+        payload: Result<Json<SaveRecipeParams>, JsonRejection>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let Json(params) = payload?;
+        validate(&state, &params).await?;
+        if Recipe::update_full(&state.db, &user.id, recipe_id, &params).await?.is_none() {
+            return Err(ApiError::NotFound("Recipe not found".to_string()));
+        }
+        let recipe = Recipe::get_full_recipe_details(&state.db, &user.id, recipe_id).await?;
+
+        Ok(Json(recipe))
+    }
+}
+
+mod delete {
+    use super::*;
+
+    pub async fn destroy(
+        Extension(state): Extension<AppState>,
+        user: Extension<User>,
+        Path(recipe_id): Path<i32>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        if !Recipe::delete(&state.db, &user.id, recipe_id).await? {
+            return Err(ApiError::NotFound("Recipe not found".to_string()));
+        }
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+}
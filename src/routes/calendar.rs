@@ -0,0 +1,55 @@
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use axum::Extension;
+use time::Duration;
+use crate::startup::AppState;
+
+use crate::formats::icalendar::to_icalendar;
+use crate::models::meal_plan::MealPlan;
+use crate::models::user::User;
+use crate::utils::e500;
+
+/// Meals this long ago are still in the feed, so that calendars don't drop them straight away
+const FEED_PAST_DAYS: i64 = 28;
+
+/// The feed is found by its secret token rather than by a session, since calendar apps
+/// subscribe without logging in
+pub fn routes() -> Router {
+    Router::new()
+        .route("/:calendar_file", get(self::get::feed))
+}
+
+mod get {
+    use super::*;
+
+    /// The user's meal plan as `/calendar/<token>.ics`
+    pub async fn feed(
+        Extension(state): Extension<AppState>,
+        Path(calendar_file): Path<String>,
+    ) -> impl IntoResponse {
+        let calendar_token = match calendar_file.strip_suffix(".ics") {
+            Some(calendar_token) if !calendar_token.is_empty() => calendar_token,
+            _ => return StatusCode::NOT_FOUND.into_response()
+        };
+        let user = match User::find_by_calendar_token(&state.db, calendar_token).await.map_err(e500) {
+            Ok(Some(user)) => user,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return err.into_response()
+        };
+        let since = time::OffsetDateTime::now_utc().date() - Duration::days(FEED_PAST_DAYS);
+        let events = match MealPlan::events_since(&state.db, &user.id, since).await.map_err(e500) {
+            Ok(events) => events,
+            Err(err) => return err.into_response()
+        };
+
+        (
+            [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+            to_icalendar(&events, &state.base_url),
+        ).into_response()
+    }
+}
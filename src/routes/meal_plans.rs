@@ -0,0 +1,283 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_messages::Messages;
+use serde::{Serialize, Deserialize};
+use time::{Date, Duration};
+use time::format_description::well_known::Iso8601;
+use crate::startup::AppState;
+use crate::template_helpers::{render_content, RenderTemplateParams, err_500_template};
+
+use crate::user::AuthSession;
+use crate::models::meal_plan::{calendar_days, month_bounds, week_start, CreateMealPlanParams, MealPlan, MealSlot};
+use crate::models::shopping_list::ShoppingList;
+use crate::models::user::User;
+use crate::utils::e500;
+use crate::constants::{
+    route_paths,
+    html_templates,
+};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::week).post(self::post::create))
+        .route("/month", get(self::get::month))
+        .route(route_paths::SHOPPING_LIST, get(self::get::shopping_list))
+        .route("/:meal_plan_id/delete", post(self::post::delete))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CalendarParams {
+    /// Any day of the week or month to show, as `YYYY-MM-DD`. Defaults to today
+    pub date: Option<String>,
+}
+
+impl CalendarParams {
+    fn date(&self) -> Result<Date, String> {
+        match self.date.as_deref().map(str::trim).filter(|date| !date.is_empty()) {
+            Some(date) => parse_date(date),
+            None => Ok(time::OffsetDateTime::now_utc().date()),
+        }
+    }
+}
+
+/// A meal to plan exactly as it was submitted, so that it can be rendered again when it is invalid
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MealPlanForm {
+    pub recipe_id: String,
+    pub planned_on: String,
+    pub meal: String,
+    /// Empty to make the recipe as written
+    #[serde(default)]
+    pub servings: String,
+}
+
+impl MealPlanForm {
+    /// Collects every problem so that they can all be shown at once
+    fn validate(&self, user_id: uuid::Uuid) -> Result<CreateMealPlanParams, Vec<String>> {
+        let mut errors = Vec::new();
+        let recipe_id = self.recipe_id.trim().parse::<i32>().map_err(|_| errors.push("Pick a recipe".to_string())).ok();
+        let planned_on = parse_date(self.planned_on.trim()).map_err(|err| errors.push(err)).ok();
+        let meal = self.meal.parse::<MealSlot>().map_err(|err| errors.push(err)).ok();
+        let servings = match self.servings.trim() {
+            "" => None,
+            servings => match servings.parse::<i32>() {
+                Ok(servings) if servings >= 1 => Some(servings),
+                _ => {
+                    errors.push("Servings must be a whole number of at least 1".to_string());
+                    None
+                },
+            },
+        };
+
+        match (recipe_id, planned_on, meal) {
+            (Some(recipe_id), Some(planned_on), Some(meal)) if errors.is_empty() => {
+                let params = CreateMealPlanParams::new(user_id, recipe_id, planned_on, meal);
+                Ok(match servings {
+                    Some(servings) => params.with_servings(servings),
+                    None => params,
+                })
+            },
+            _ => Err(errors),
+        }
+    }
+}
+
+fn parse_date(value: &str) -> Result<Date, String> {
+    Date::parse(value, &Iso8601::DATE).map_err(|_| format!("{} is not a date", value))
+}
+
+fn week_path(date: Date) -> String {
+    format!("{}?date={}", route_paths::MEAL_PLANS, week_start(date))
+}
+
+/// Renders the week `date` is in with the form for planning a meal. Invalid submissions are
+/// shown again with their errors.
+async fn render_week(state: &AppState, user: &User, date: Date, form: MealPlanForm, errors: &[String]) -> Response {
+    let start = week_start(date);
+    let end = start + Duration::days(6);
+    let (entries, recipes) = match (MealPlan::entries_between(&state.db, &user.id, start, end).await, user.get_recipes(&state.db).await) {
+        (Ok(entries), Ok(recipes)) => (entries, recipes),
+        (Err(err), _) | (_, Err(err)) => return (StatusCode::INTERNAL_SERVER_ERROR, Html(err_500_template(&state.tera, err))).into_response()
+    };
+    let form = MealPlanForm {
+        planned_on: if form.planned_on.is_empty() { date.to_string() } else { form.planned_on },
+        ..form
+    };
+    let status = if errors.is_empty() { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+
+    let mut context = tera::Context::new();
+    context.insert("week_start", &start);
+    context.insert("week_end", &end);
+    context.insert("previous_week", &(start - Duration::days(7)));
+    context.insert("next_week", &(start + Duration::days(7)));
+    context.insert("days", &calendar_days(start, end, &entries));
+    context.insert("recipes", &recipes);
+    context.insert("meals", &MealSlot::ALL);
+    context.insert("form", &form);
+    context.insert("errors", errors);
+    match render_content(
+        &RenderTemplateParams::new(html_templates::MEAL_PLANS_WEEK, &state.tera)
+        .with_context(&context)
+    ) {
+        Ok(week_template) => (status, Html(week_template)).into_response(),
+        Err(e) => e.into_response()
+    }
+}
+
+mod post {
+    use super::*;
+
+    pub async fn create(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<MealPlanForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let today = time::OffsetDateTime::now_utc().date();
+        let params = match form.validate(user.id) {
+            Ok(params) => params,
+            Err(errors) => {
+                let date = parse_date(form.planned_on.trim()).unwrap_or(today);
+                return render_week(&state, &user, date, form, &errors).await
+            }
+        };
+
+        match MealPlan::create(&state.db, &params).await.map_err(e500) {
+            Ok(Some(meal_plan)) => {
+                messages.success(format!("Planned {} for {}", meal_plan.meal, meal_plan.planned_on));
+                Redirect::to(&week_path(meal_plan.planned_on)).into_response()
+            },
+            Ok(None) => render_week(&state, &user, params.planned_on, form, &["Pick one of your recipes".to_string()]).await,
+            Err(err) => err.into_response()
+        }
+    }
+
+    pub async fn delete(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(meal_plan_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+
+        match MealPlan::delete(&state.db, &user.id, meal_plan_id).await.map_err(e500) {
+            Ok(Some(meal_plan)) => {
+                messages.success("Meal removed");
+                Redirect::to(&week_path(meal_plan.planned_on)).into_response()
+            },
+            Ok(None) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => err.into_response()
+        }
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn week(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Query(params): Query<CalendarParams>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let date = match params.date() {
+            Ok(date) => date,
+            Err(err) => return (StatusCode::BAD_REQUEST, err).into_response()
+        };
+
+        render_week(&state, &user, date, MealPlanForm { meal: MealSlot::Dinner.to_string(), ..MealPlanForm::default() }, &[]).await
+    }
+
+    /// Every week that has a day in the month, so that the calendar is made of whole weeks
+    pub async fn month(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Query(params): Query<CalendarParams>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let date = match params.date() {
+            Ok(date) => date,
+            Err(err) => return (StatusCode::BAD_REQUEST, err).into_response()
+        };
+        let (first_day, last_day) = month_bounds(date);
+        let start = week_start(first_day);
+        let end = week_start(last_day) + Duration::days(6);
+        let entries = match MealPlan::entries_between(&state.db, &user.id, start, end).await.map_err(e500) {
+            Ok(entries) => entries,
+            Err(err) => return err.into_response()
+        };
+        let weeks: Vec<_> = calendar_days(start, end, &entries)
+            .chunks(7)
+            .map(<[_]>::to_vec)
+            .collect();
+
+        let mut context = tera::Context::new();
+        context.insert("month_name", &format!("{} {}", first_day.month(), first_day.year()));
+        // Days are serialized as YYYY-MM-DD, so the days of the month start with this
+        context.insert("month_prefix", &first_day.to_string()[..7]);
+        context.insert("previous_month", &(first_day - Duration::days(1)));
+        context.insert("next_month", &(last_day + Duration::days(1)));
+        context.insert("weeks", &weeks);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::MEAL_PLANS_MONTH, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(month_template) => Html(month_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
+    /// Everything needed for the meals planned in the week, less what is in the pantry
+    pub async fn shopping_list(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Query(params): Query<CalendarParams>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let date = match params.date() {
+            Ok(date) => date,
+            Err(err) => return (StatusCode::BAD_REQUEST, err).into_response()
+        };
+        let start = week_start(date);
+        let end = start + Duration::days(6);
+        let shopping_list = match ShoppingList::from_meal_plan(&state.db, &user.id, start, end).await.map_err(e500) {
+            Ok(shopping_list) => shopping_list,
+            Err(err) => return err.into_response()
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("week_start", &start);
+        context.insert("week_end", &end);
+        context.insert("shopping_list", &shopping_list);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::MEAL_PLANS_SHOPPING_LIST, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(shopping_list_template) => Html(shopping_list_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
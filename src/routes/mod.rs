@@ -10,6 +10,11 @@ mod auth;
 mod protected;
 mod recipes;
 mod api;
+mod shopping_list;
+mod pantry;
+mod meal_plans;
+mod calendar;
+mod settings;
 
 pub fn homepage_routes() -> Router {
     Router::new().nest(route_paths::ROOT, homepage::routes())
@@ -31,6 +36,31 @@ pub fn recipe_routes() -> Router {
     Router::new().nest(route_paths::RECIPES, recipes::routes::routes())
 }
 
+pub fn shopping_list_routes() -> Router {
+    Router::new().nest(route_paths::SHOPPING_LIST, shopping_list::routes())
+}
+
+pub fn pantry_routes() -> Router {
+    Router::new().nest(route_paths::PANTRY, pantry::routes())
+}
+
+pub fn meal_plan_routes() -> Router {
+    Router::new().nest(route_paths::MEAL_PLANS, meal_plans::routes())
+}
+
+pub fn calendar_routes() -> Router {
+    Router::new().nest(route_paths::CALENDAR, calendar::routes())
+}
+
+pub fn settings_routes() -> Router {
+    Router::new().nest(route_paths::SETTINGS, settings::routes())
+}
+
 pub fn api_routes(state: &AppState) -> Router {
-    Router::new().nest(route_paths::API,api::chopper::routes(state))
+    Router::new().nest(
+        route_paths::API,
+        api::chopper::routes(state)
+            .merge(api::import::routes(state))
+            .nest(route_paths::V1, api::recipes::routes(state)),
+    )
 }
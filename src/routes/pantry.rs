@@ -0,0 +1,255 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_messages::Messages;
+use serde::{Serialize, Deserialize};
+use time::Date;
+use time::format_description::well_known::Iso8601;
+use crate::startup::AppState;
+use crate::template_helpers::{render_content, RenderTemplateParams, err_500_template};
+
+use crate::user::AuthSession;
+use crate::domain::{parse_quantity, Quantity};
+use crate::models::pantry_item::{PantryItem, PantryItemDetails, SavePantryItemParams};
+use crate::models::unit::Unit;
+use crate::utils::e500;
+use crate::constants::{
+    route_paths,
+    html_templates,
+};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::index).post(self::post::create))
+        .route("/:pantry_item_id", post(self::post::update))
+        .route("/:pantry_item_id/edit", get(self::get::edit))
+        .route("/:pantry_item_id/delete", post(self::post::delete))
+}
+
+/// A pantry item exactly as it was submitted, so that it can be rendered again when it is invalid
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PantryItemForm {
+    pub ingredient: String,
+    pub quantity: String,
+    pub unit: String,
+    /// `YYYY-MM-DD`, or empty for things that keep
+    #[serde(default)]
+    pub expires_on: String,
+}
+
+impl PantryItemForm {
+    fn from_item(item: &PantryItemDetails) -> Self {
+        Self {
+            ingredient: item.ingredient.clone(),
+            quantity: item.quantity.to_string(),
+            unit: item.unit.clone(),
+            expires_on: item.expires_on.map(|date| date.to_string()).unwrap_or_default(),
+        }
+    }
+
+    /// Collects every problem so that they can all be shown at once
+    fn validate(&self, units: &[Unit]) -> Result<SavePantryItemParams, Vec<String>> {
+        let mut errors = Vec::new();
+        let ingredient = self.ingredient.trim();
+        if ingredient.is_empty() {
+            errors.push("Ingredient is required".to_string());
+        }
+        let quantity = match parse_quantity(&self.quantity) {
+            Ok(quantity) if quantity > Quantity::zero() => Some(quantity),
+            Ok(_) => {
+                errors.push("Quantity must be more than 0".to_string());
+                None
+            },
+            Err(err) => {
+                errors.push(err.to_string());
+                None
+            },
+        };
+        if !units.iter().any(|unit| unit.name == self.unit) {
+            errors.push(format!("Unknown unit {}", self.unit));
+        }
+        let expires_on = match self.expires_on.trim() {
+            "" => None,
+            expires_on => match Date::parse(expires_on, &Iso8601::DATE) {
+                Ok(date) => Some(date),
+                Err(_) => {
+                    errors.push(format!("{} is not a date", expires_on));
+                    None
+                },
+            },
+        };
+
+        match quantity {
+            Some(quantity) if errors.is_empty() => Ok(SavePantryItemParams {
+                ingredient: ingredient.to_string(),
+                unit: self.unit.clone(),
+                quantity,
+                expires_on,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// Renders the pantry with the form for adding to it. Invalid submissions are shown again with their errors.
+async fn render_index(state: &AppState, user_id: &uuid::Uuid, form: &PantryItemForm, errors: &[String]) -> Response {
+    let (items, units) = match (PantryItem::list_for_user(&state.db, user_id).await, Unit::list(&state.db).await) {
+        (Ok(items), Ok(units)) => (items, units),
+        (Err(err), _) | (_, Err(err)) => return (StatusCode::INTERNAL_SERVER_ERROR, Html(err_500_template(&state.tera, err))).into_response()
+    };
+    let status = if errors.is_empty() { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+
+    let mut context = tera::Context::new();
+    context.insert("items", &items);
+    context.insert("units", &units);
+    context.insert("form", form);
+    context.insert("errors", errors);
+    match render_content(
+        &RenderTemplateParams::new(html_templates::PANTRY_INDEX, &state.tera)
+        .with_context(&context)
+    ) {
+        Ok(pantry_template) => (status, Html(pantry_template)).into_response(),
+        Err(e) => e.into_response()
+    }
+}
+
+async fn render_edit(state: &AppState, pantry_item_id: i32, form: &PantryItemForm, errors: &[String]) -> Response {
+    let units = match Unit::list(&state.db).await {
+        Ok(units) => units,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, Html(err_500_template(&state.tera, err))).into_response()
+    };
+    let status = if errors.is_empty() { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+
+    let mut context = tera::Context::new();
+    context.insert("pantry_item_id", &pantry_item_id);
+    context.insert("units", &units);
+    context.insert("form", form);
+    context.insert("errors", errors);
+    match render_content(
+        &RenderTemplateParams::new(html_templates::PANTRY_EDIT, &state.tera)
+        .with_context(&context)
+    ) {
+        Ok(edit_template) => (status, Html(edit_template)).into_response(),
+        Err(e) => e.into_response()
+    }
+}
+
+mod post {
+    use super::*;
+
+    pub async fn create(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<PantryItemForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let units = match Unit::list(&state.db).await.map_err(e500) {
+            Ok(units) => units,
+            Err(err) => return err.into_response()
+        };
+        let params = match form.validate(&units) {
+            Ok(params) => params,
+            Err(errors) => return render_index(&state, &user.id, &form, &errors).await
+        };
+
+        match PantryItem::create(&state.db, &user.id, &params).await.map_err(e500) {
+            Ok(_) => {
+                messages.success(format!("Added {} to your pantry", params.ingredient));
+                Redirect::to(route_paths::PANTRY).into_response()
+            },
+            Err(err) => err.into_response()
+        }
+    }
+
+    pub async fn update(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(pantry_item_id): Path<i32>,
+        Form(form): Form<PantryItemForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let units = match Unit::list(&state.db).await.map_err(e500) {
+            Ok(units) => units,
+            Err(err) => return err.into_response()
+        };
+        let params = match form.validate(&units) {
+            Ok(params) => params,
+            Err(errors) => return render_edit(&state, pantry_item_id, &form, &errors).await
+        };
+
+        match PantryItem::update(&state.db, &user.id, pantry_item_id, &params).await.map_err(e500) {
+            Ok(Some(_)) => {
+                messages.success(format!("Saved {}", params.ingredient));
+                Redirect::to(route_paths::PANTRY).into_response()
+            },
+            Ok(None) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => err.into_response()
+        }
+    }
+
+    pub async fn delete(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(pantry_item_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+
+        match PantryItem::delete(&state.db, &user.id, pantry_item_id).await.map_err(e500) {
+            Ok(true) => {
+                messages.success("Removed from your pantry");
+                Redirect::to(route_paths::PANTRY).into_response()
+            },
+            Ok(false) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => err.into_response()
+        }
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn index(auth_session: AuthSession, Extension(state): Extension<AppState>) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+
+        render_index(&state, &user.id, &PantryItemForm::default(), &[]).await
+    }
+
+    pub async fn edit(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Path(pantry_item_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let item = match PantryItem::find_details(&state.db, &user.id, pantry_item_id).await {
+            Ok(Some(item)) => item,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return Html(err_500_template(&state.tera, err)).into_response()
+        };
+
+        render_edit(&state, pantry_item_id, &PantryItemForm::from_item(&item), &[]).await
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+
+use crate::domain::{parse_quantity, IngredientLine};
+use crate::models::recipe::{
+    FullRecipeDetails,
+    SaveRecipeParams,
+    SaveRecipeComponentParams,
+    SaveRecipeIngredientParams,
+    SaveRecipeInstructionParams,
+    MAX_STEP_MINUTES,
+};
+use crate::models::unit::{unit_matcher, Unit};
+
+/// How many empty ingredient rows each component gets when the form is rendered
+const BLANK_INGREDIENT_ROWS: usize = 3;
+
+/// The unit for pasted lines that don't name one, such as "2 eggs"
+const PASTED_DEFAULT_UNIT: &str = "piece";
+
+/// The recipe form exactly as it was submitted, so that it can be rendered again when it is invalid.
+/// Nested fields are named like `components[0].ingredients[1].quantity` and steps are one per line.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RecipeForm {
+    pub name: String,
+    pub description: String,
+    pub is_public: bool,
+    pub prep_time: String,
+    pub cook_time: String,
+    pub rest_time: String,
+    pub servings: String,
+    pub source_url: String,
+    pub components: Vec<ComponentForm>,
+    pub instructions: Vec<InstructionForm>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ComponentForm {
+    pub name: String,
+    pub is_optional: bool,
+    pub ingredients: Vec<IngredientForm>,
+    /// Whole ingredient lines such as "2 cups flour, sifted", one per line
+    pub pasted: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IngredientForm {
+    pub name: String,
+    pub description: String,
+    pub quantity: String,
+    pub unit: String,
+    pub is_optional: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InstructionForm {
+    pub title: String,
+    pub steps: String,
+}
+
+impl ComponentForm {
+    fn is_blank(&self) -> bool {
+        self.name.trim().is_empty()
+            && self.pasted.trim().is_empty()
+            && self.ingredients.iter().all(IngredientForm::is_blank)
+    }
+}
+
+impl IngredientForm {
+    fn is_blank(&self) -> bool {
+        self.name.trim().is_empty() && self.quantity.trim().is_empty()
+    }
+}
+
+impl InstructionForm {
+    fn is_blank(&self) -> bool {
+        self.title.trim().is_empty() && self.steps.trim().is_empty()
+    }
+}
+
+impl RecipeForm {
+    pub fn from_pairs(pairs: Vec<(String, String)>) -> Self {
+        let mut form = RecipeForm::default();
+        let mut components: BTreeMap<usize, ComponentForm> = BTreeMap::new();
+        let mut ingredients: BTreeMap<(usize, usize), IngredientForm> = BTreeMap::new();
+        let mut instructions: BTreeMap<usize, InstructionForm> = BTreeMap::new();
+
+        for (key, value) in pairs {
+            if let Some((component_idx, field)) = indexed_field("components", &key) {
+                if let Some((ingredient_idx, field)) = indexed_field("ingredients", field) {
+                    let ingredient = ingredients.entry((component_idx, ingredient_idx)).or_default();
+                    match field {
+                        "name" => ingredient.name = value,
+                        "description" => ingredient.description = value,
+                        "quantity" => ingredient.quantity = value,
+                        "unit" => ingredient.unit = value,
+                        "is_optional" => ingredient.is_optional = true,
+                        _ => {},
+                    }
+                    components.entry(component_idx).or_default();
+                    continue;
+                }
+                let component = components.entry(component_idx).or_default();
+                match field {
+                    "name" => component.name = value,
+                    "is_optional" => component.is_optional = true,
+                    "pasted" => component.pasted = value,
+                    _ => {},
+                }
+            } else if let Some((instruction_idx, field)) = indexed_field("instructions", &key) {
+                let instruction = instructions.entry(instruction_idx).or_default();
+                match field {
+                    "title" => instruction.title = value,
+                    "steps" => instruction.steps = value,
+                    _ => {},
+                }
+            } else {
+                match key.as_str() {
+                    "name" => form.name = value,
+                    "description" => form.description = value,
+                    "is_public" => form.is_public = true,
+                    "prep_time" => form.prep_time = value,
+                    "cook_time" => form.cook_time = value,
+                    "rest_time" => form.rest_time = value,
+                    "servings" => form.servings = value,
+                    "source_url" => form.source_url = value,
+                    _ => {},
+                }
+            }
+        }
+
+        for ((component_idx, _), ingredient) in ingredients {
+            if let Some(component) = components.get_mut(&component_idx) {
+                component.ingredients.push(ingredient);
+            }
+        }
+        form.components = components.into_values().collect();
+        form.instructions = instructions.into_values().collect();
+
+        form
+    }
+
+    pub fn from_recipe(recipe: &FullRecipeDetails) -> Self {
+        let optional_number = |value: Option<i32>| value.map(|v| v.to_string()).unwrap_or_default();
+        Self {
+            name: recipe.name.clone(),
+            description: recipe.description.clone(),
+            is_public: recipe.is_public,
+            prep_time: optional_number(recipe.prep_time),
+            cook_time: optional_number(recipe.cook_time),
+            rest_time: optional_number(recipe.rest_time),
+            servings: optional_number(recipe.servings),
+            source_url: recipe.source_url.clone().unwrap_or_default(),
+            components: recipe.recipe_components.iter().map(|component| ComponentForm {
+                name: component.name.clone(),
+                is_optional: component.is_optional,
+                pasted: String::new(),
+                ingredients: component.component_ingredients.iter().map(|ingredient| IngredientForm {
+                    name: ingredient.name.clone(),
+                    description: ingredient.description.clone(),
+                    quantity: ingredient.quantity.to_string(),
+                    unit: ingredient.unit.clone(),
+                    is_optional: ingredient.is_optional,
+                }).collect(),
+            }).collect(),
+            instructions: recipe.recipe_instructions.iter().map(|instruction| InstructionForm {
+                title: instruction.title.clone(),
+                steps: instruction.instruction_steps
+                    .iter()
+                    .map(|step| step.content.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n"),
+            }).collect(),
+        }
+    }
+
+    /// Adds empty rows so that new components, ingredients and instructions can be filled in
+    /// without any javascript. Blank rows are skipped again when the form is validated.
+    pub fn with_blank_rows(mut self) -> Self {
+        self.components.push(ComponentForm::default());
+        for component in self.components.iter_mut() {
+            component.ingredients.extend(std::iter::repeat_with(IngredientForm::default).take(BLANK_INGREDIENT_ROWS));
+        }
+        self.instructions.push(InstructionForm::default());
+        self
+    }
+
+    /// Turns the submitted strings into params for saving, collecting every problem
+    /// so that they can all be shown at once
+    pub fn validate(&self, units: &[Unit]) -> Result<SaveRecipeParams, Vec<String>> {
+        let mut errors = Vec::new();
+        let matcher = unit_matcher(units);
+
+        if self.name.trim().is_empty() {
+            errors.push("Name is required".to_string());
+        }
+        let prep_time = parse_number("Prep time", &self.prep_time, 0, MAX_STEP_MINUTES, &mut errors);
+        let cook_time = parse_number("Cook time", &self.cook_time, 0, MAX_STEP_MINUTES, &mut errors);
+        let rest_time = parse_number("Rest time", &self.rest_time, 0, MAX_STEP_MINUTES, &mut errors);
+        let servings = parse_number("Servings", &self.servings, 1, i32::MAX, &mut errors);
+
+        let mut components = Vec::new();
+        for component in self.components.iter().filter(|component| !component.is_blank()) {
+            if component.name.trim().is_empty() {
+                errors.push("Every component needs a name".to_string());
+            }
+            let mut component_ingredients = Vec::new();
+            for ingredient in component.ingredients.iter().filter(|ingredient| !ingredient.is_blank()) {
+                let name = ingredient.name.trim();
+                if name.is_empty() {
+                    errors.push(format!("An ingredient in {} needs a name", component.name.trim()));
+                    continue;
+                }
+                let quantity = match parse_quantity(&ingredient.quantity) {
+                    Ok(quantity) => quantity,
+                    Err(err) => {
+                        errors.push(format!("{}: {}", name, err));
+                        continue;
+                    }
+                };
+                if !units.iter().any(|unit| unit.name == ingredient.unit) {
+                    errors.push(format!("{}: unknown unit {}", name, ingredient.unit));
+                    continue;
+                }
+                component_ingredients.push(SaveRecipeIngredientParams {
+                    name: name.to_string(),
+                    description: ingredient.description.trim().to_string(),
+                    unit: ingredient.unit.clone(),
+                    quantity,
+                    is_optional: ingredient.is_optional,
+                });
+            }
+            for line in component.pasted.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                let line = IngredientLine::parse(line, &matcher);
+                if line.name.is_empty() {
+                    errors.push(format!("An ingredient in {} needs a name", component.name.trim()));
+                    continue;
+                }
+                let unit = line.unit.clone().unwrap_or_else(|| PASTED_DEFAULT_UNIT.to_string());
+                if !units.iter().any(|known| known.name == unit) {
+                    errors.push(format!("{}: unknown unit {}", line.name, unit));
+                    continue;
+                }
+                component_ingredients.push(SaveRecipeIngredientParams {
+                    quantity: line.amount(),
+                    unit,
+                    description: line.note.unwrap_or_default(),
+                    is_optional: line.is_optional,
+                    name: line.name,
+                });
+            }
+            components.push(SaveRecipeComponentParams {
+                name: component.name.trim().to_string(),
+                is_optional: component.is_optional,
+                ingredients: component_ingredients,
+            });
+        }
+
+        let instructions = self.instructions
+            .iter()
+            .filter(|instruction| !instruction.is_blank())
+            .map(|instruction| SaveRecipeInstructionParams {
+                title: instruction.title.trim().to_string(),
+                steps: instruction.steps
+                    .lines()
+                    .map(str::trim)
+                    .filter(|step| !step.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            })
+            .collect();
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(SaveRecipeParams {
+            name: self.name.trim().to_string(),
+            description: self.description.trim().to_string(),
+            is_public: self.is_public,
+            prep_time,
+            cook_time,
+            rest_time,
+            servings,
+            source_url: Some(self.source_url.trim().to_string()).filter(|url| !url.is_empty()),
+            components,
+            instructions,
+        })
+    }
+}
+
+/// Splits `prefix[3].rest` into `(3, "rest")`
+fn indexed_field<'a>(prefix: &str, key: &'a str) -> Option<(usize, &'a str)> {
+    let rest = key.strip_prefix(prefix)?.strip_prefix('[')?;
+    let (idx, field) = rest.split_once("].")?;
+    Some((idx.parse().ok()?, field))
+}
+
+fn parse_number(label: &str, value: &str, min: i32, max: i32, errors: &mut Vec<String>) -> Option<i32> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    match value.parse::<i32>() {
+        Ok(number) if number > max => {
+            errors.push(format!("{} can't be more than {}", label, max));
+            None
+        },
+        Ok(number) if number >= min => Some(number),
+        _ => {
+            errors.push(format!("{} must be a whole number of at least {}", label, min));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecipeForm;
+    use crate::domain::Quantity;
+    use crate::models::unit::{Unit, UnitDimension, UnitSystem};
+
+    fn units() -> Vec<Unit> {
+        vec![Unit {
+            id: 1,
+            name: "cup".to_string(),
+            abbreviation: Some("c".to_string()),
+            system: UnitSystem::Imperial,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(236.5882365),
+        }, Unit {
+            id: 2,
+            name: "piece".to_string(),
+            abbreviation: Some("pc".to_string()),
+            system: UnitSystem::Universal,
+            dimension: UnitDimension::Count,
+            base_factor: None,
+        }]
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn nested_fields_are_grouped_by_index() {
+        let form = RecipeForm::from_pairs(pairs(&[
+            ("name", "Stew"),
+            ("components[0].name", "Main"),
+            ("components[0].ingredients[0].name", "Potato"),
+            ("components[0].ingredients[0].quantity", "1 1/2"),
+            ("components[0].ingredients[0].unit", "cup"),
+            ("components[0].ingredients[1].name", "Carrot"),
+            ("components[0].ingredients[1].is_optional", "on"),
+            ("instructions[0].title", "Cook"),
+            ("instructions[0].steps", "Chop\nBoil"),
+        ]));
+
+        assert_eq!(form.name, "Stew");
+        assert_eq!(form.components.len(), 1);
+        assert_eq!(form.components[0].ingredients.len(), 2);
+        assert_eq!(form.components[0].ingredients[0].quantity, "1 1/2");
+        assert!(form.components[0].ingredients[1].is_optional);
+        assert_eq!(form.instructions[0].steps, "Chop\nBoil");
+    }
+
+    #[test]
+    fn blank_rows_are_skipped() {
+        let form = RecipeForm::from_pairs(pairs(&[
+            ("name", "Stew"),
+            ("components[0].name", "Main"),
+            ("components[0].ingredients[0].name", "Potato"),
+            ("components[0].ingredients[0].quantity", "2"),
+            ("components[0].ingredients[0].unit", "cup"),
+            ("components[0].ingredients[1].name", ""),
+            ("components[0].ingredients[1].quantity", ""),
+            ("components[1].name", ""),
+            ("components[1].ingredients[0].name", ""),
+            ("instructions[0].title", "Cook"),
+            ("instructions[0].steps", "Chop\n\n  Boil  \n"),
+            ("instructions[1].title", ""),
+            ("instructions[1].steps", ""),
+        ]));
+
+        let params = form.validate(&units()).unwrap();
+        assert_eq!(params.components.len(), 1);
+        assert_eq!(params.components[0].ingredients.len(), 1);
+        assert_eq!(params.components[0].ingredients[0].quantity, Quantity::whole(2));
+        assert_eq!(params.instructions.len(), 1);
+        assert_eq!(params.instructions[0].steps, vec!["Chop".to_string(), "Boil".to_string()]);
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let form = RecipeForm::from_pairs(pairs(&[
+            ("name", ""),
+            ("servings", "0"),
+            ("components[0].name", "Main"),
+            ("components[0].ingredients[0].name", "Potato"),
+            ("components[0].ingredients[0].quantity", "lots"),
+            ("components[0].ingredients[0].unit", "cup"),
+            ("components[0].ingredients[1].name", "Carrot"),
+            ("components[0].ingredients[1].quantity", "1"),
+            ("components[0].ingredients[1].unit", "bushel"),
+        ]));
+
+        let errors = form.validate(&units()).unwrap_err();
+        assert_eq!(errors.len(), 4);
+    }
+
+    #[test]
+    fn times_are_at_most_a_week() {
+        let form = RecipeForm::from_pairs(pairs(&[("name", "Ham"), ("prep_time", "10080"), ("rest_time", "10081")]));
+        assert_eq!(form.validate(&units()).unwrap_err(), vec!["Rest time can't be more than 10080".to_string()]);
+    }
+
+    #[test]
+    fn empty_optional_fields_are_none() {
+        let form = RecipeForm::from_pairs(pairs(&[("name", "Toast"), ("prep_time", ""), ("source_url", " ")]));
+        let params = form.validate(&units()).unwrap();
+        assert_eq!(params.prep_time, None);
+        assert_eq!(params.source_url, None);
+    }
+
+    #[test]
+    fn pasted_lines_become_ingredients() {
+        let form = RecipeForm::from_pairs(pairs(&[
+            ("name", "Stew"),
+            ("components[0].name", "Main"),
+            ("components[0].pasted", "1½ cups stock (warm)\n\n2 carrots, diced"),
+        ]));
+
+        let params = form.validate(&units()).unwrap();
+        let ingredients = &params.components[0].ingredients;
+        assert_eq!(ingredients.len(), 2);
+        assert_eq!(ingredients[0].quantity, Quantity::new(3, 2).unwrap());
+        assert_eq!(ingredients[0].unit, "cup");
+        assert_eq!(ingredients[0].name, "stock");
+        assert_eq!(ingredients[0].description, "warm");
+        assert_eq!(ingredients[1].unit, "piece");
+        assert_eq!(ingredients[1].description, "diced");
+    }
+
+    #[test]
+    fn malformed_keys_are_ignored() {
+        let form = RecipeForm::from_pairs(pairs(&[("components[x].name", "Main"), ("components[0]name", "Main")]));
+        assert!(form.components.is_empty());
+    }
+}
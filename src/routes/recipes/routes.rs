@@ -1,18 +1,32 @@
 use axum::{
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Redirect, Response},
     routing::{get, post}, Router,
-    extract::{Path, Json},
+    extract::{DefaultBodyLimit, Path, Json, Multipart, Query},
+    extract::multipart::MultipartRejection,
+    extract::rejection::JsonRejection,
+    Form,
 };
 use axum::Extension;
 use axum::response::Html;
+use axum_messages::Messages;
 use serde::Deserialize;
 use crate::startup::AppState;
 use crate::template_helpers::{render_content, RenderTemplateParams, err_500_template};
 
 use crate::user::AuthSession;
-use crate::models::recipe::Recipe;
-use crate::utils::e500;
+use crate::chopper::ChopperRequest;
+use crate::formats::archive::{export_archive, ARCHIVE_FILE_NAME};
+use crate::formats::library::ImportReport;
+use crate::formats::markdown::to_markdown;
+use crate::models::nutrition::RecipeNutrition;
+use crate::models::pantry_item::PantryItem;
+use crate::models::recipe::{DuplicatePolicy, FullRecipeDetails, ImportOutcome, Recipe, RecipeSearchParams};
+use crate::models::unit::{Unit, UnitConverter, UnitSystem};
+use crate::models::user::User;
+use super::form::RecipeForm;
+use super::upload::{LibraryUpload, MAX_UPLOAD_BYTES};
+use crate::utils::{e500, ApiError};
 use crate::constants::{
     route_paths,
     html_templates,
@@ -20,61 +34,404 @@ use crate::constants::{
 
 pub fn routes() -> Router {
     Router::new()
-        .route(route_paths::ROOT, get(self::get::index))
-        .route("/:recipe_id", get(self::get::show))
+        .route(route_paths::ROOT, get(self::get::index).post(self::post::create))
+        .route("/new", get(self::get::new))
+        .route("/extension", post(self::post::create_from_extension))
+        .route(route_paths::EXPORT, get(self::get::export))
+        .route(
+            route_paths::IMPORT_LIBRARY,
+            get(self::get::import).post(self::post::import).layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
+        .route("/:recipe_id", get(self::get::show).post(self::post::update))
+        .route("/:recipe_id/edit", get(self::get::edit))
+        .route("/:recipe_id/print", get(self::get::print))
+        .route("/:recipe_id/delete", post(self::post::delete))
+        .route("/:recipe_id/cooked", post(self::post::cooked))
+}
+
+fn recipe_path(recipe_id: i32) -> String {
+    format!("{}/{}", route_paths::RECIPES, recipe_id)
+}
+
+/// Renders the new or edit page. Invalid submissions are shown again with their errors.
+async fn render_form(state: &AppState, template: &'static str, form: RecipeForm, errors: &[String], recipe_id: Option<i32>) -> Response {
+    let units = match Unit::list(&state.db).await {
+        Ok(units) => units,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, Html(err_500_template(&state.tera, err))).into_response()
+    };
+    let status = if errors.is_empty() { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+
+    let mut context = tera::Context::new();
+    context.insert("form", &form.with_blank_rows());
+    context.insert("units", &units);
+    context.insert("errors", errors);
+    context.insert("recipe_id", &recipe_id);
+    match render_content(
+        &RenderTemplateParams::new(template, &state.tera)
+        .with_context(&context)
+    ) {
+        Ok(form_template) => (status, Html(form_template)).into_response(),
+        Err(e) => e.into_response()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum RecipeFormat {
+    Html,
+    Markdown,
+}
+
+/// Reads "12" or "12.md" from the path of a recipe
+fn parse_recipe_file(recipe_file: &str) -> Option<(i32, RecipeFormat)> {
+    let (recipe_id, format) = match recipe_file.strip_suffix(".md") {
+        Some(recipe_id) => (recipe_id, RecipeFormat::Markdown),
+        None => (recipe_file, RecipeFormat::Html),
+    };
+
+    recipe_id.parse().ok().map(|recipe_id| (recipe_id, format))
+}
+
+/// A recipe as it is shown, and as it was before its units were converted
+struct LoadedRecipe {
+    shown: FullRecipeDetails,
+    /// Converted amounts are rounded to be readable, so nutrition is worked out from these
+    scaled: FullRecipeDetails,
+    unit_system: Option<UnitSystem>,
+}
+
+/// Loads the recipe scaled to the requested servings and in the requested unit system.
+/// Problems are answered with the response to send instead.
+async fn load_recipe(
+    state: &AppState,
+    user: &User,
+    recipe_id: i32,
+    params: &ShowRecipeParams,
+) -> Result<LoadedRecipe, Response> {
+    let mut recipe = match Recipe::get_full_recipe_details(&state.db, &user.id, recipe_id).await {
+        Ok(recipe_full_details) => recipe_full_details,
+        Err(crate::models::Error::Sqlx(sqlx::Error::RowNotFound)) => return Err(StatusCode::NOT_FOUND.into_response()),
+        Err(err) => return Err(Html(err_500_template(&state.tera, err)).into_response())
+    };
+    if let Some(servings) = params.servings {
+        if servings < 1 {
+            return Err((StatusCode::BAD_REQUEST, "servings must be at least 1").into_response());
+        }
+        if let Err(err) = recipe.scale_to_servings(servings) {
+            return Err((StatusCode::BAD_REQUEST, err.to_string()).into_response());
+        }
+    }
+    let scaled = recipe.clone();
+    let unit_system = user.unit_system_for(params.system.as_deref());
+    if let Some(unit_system) = &unit_system {
+        match UnitConverter::load(&state.db).await {
+            Ok(converter) => recipe.convert_units(&converter, unit_system),
+            Err(err) => return Err(Html(err_500_template(&state.tera, err)).into_response())
+        }
+    }
+
+    Ok(LoadedRecipe { shown: recipe, scaled, unit_system })
+}
+
+/// Renders the recipe page, with its nutrition when there is some, or its printable card
+fn render_recipe(state: &AppState, template: &'static str, recipe: &FullRecipeDetails, unit_system: &Option<UnitSystem>, nutrition: Option<&RecipeNutrition>) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("recipe", recipe);
+    context.insert("unit_system", unit_system);
+    context.insert("nutrition", &nutrition);
+    match render_content(
+        &RenderTemplateParams::new(template, &state.tera)
+        .with_context(&context)
+    ).map_err(e500) {
+        Ok(recipe_template) => Html(recipe_template).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, Html(err_500_template(&state.tera, err))).into_response()
+    }
+}
+
+/// Renders the library import page, with the report of an upload once there is one
+fn render_import(state: &AppState, report: Option<&ImportReport>, errors: &[String]) -> Response {
+    let status = if errors.is_empty() { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+
+    let mut context = tera::Context::new();
+    context.insert("report", &report);
+    context.insert("errors", errors);
+    match render_content(
+        &RenderTemplateParams::new(html_templates::RECIPES_IMPORT, &state.tera)
+        .with_context(&context)
+    ) {
+        Ok(import_template) => (status, Html(import_template)).into_response(),
+        Err(e) => e.into_response()
+    }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ShowRecipeParams {
+    /// Scales the ingredient amounts to make this many servings
+    pub servings: Option<i32>,
+    /// "metric", "imperial" or "original". Defaults to the user's preferred unit system
+    pub system: Option<String>,
+}
+
+/// A page sent by the browser extension of a logged in user
 #[derive(Debug, Deserialize)]
 pub struct ExtensionRecipeParams {
     pub content: String,
+    #[serde(default, rename = "sourceUrl")]
+    pub source_url: String,
+    #[serde(default, rename = "onDuplicate")]
+    pub on_duplicate: DuplicatePolicy,
+}
+
+impl From<ExtensionRecipeParams> for ChopperRequest {
+    fn from(params: ExtensionRecipeParams) -> Self {
+        let url = reqwest::Url::parse(&params.source_url).ok();
+        Self {
+            hostname: url.as_ref().and_then(|url| url.host_str()).unwrap_or_default().to_string(),
+            pathname: url.as_ref().map(|url| url.path().to_string()).unwrap_or_default(),
+            text: params.content,
+            source_url: params.source_url,
+            on_duplicate: params.on_duplicate,
+        }
+    }
 }
 
 mod post {
     use super::*;
 
-    pub async fn create_from_extension(
-        mut auth_session: AuthSession,
+    pub async fn create(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(pairs): Form<Vec<(String, String)>>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let units = match Unit::list(&state.db).await.map_err(e500) {
+            Ok(units) => units,
+            Err(err) => return err.into_response()
+        };
+        let form = RecipeForm::from_pairs(pairs);
+        let params = match form.validate(&units) {
+            Ok(params) => params,
+            Err(errors) => return render_form(&state, html_templates::RECIPES_NEW, form, &errors, None).await
+        };
+
+        match Recipe::create_full(&state.db, &user.id, &params).await.map_err(e500) {
+            Ok(recipe) => {
+                messages.success(format!("Created {}", recipe.name));
+                Redirect::to(&recipe_path(recipe.id)).into_response()
+            },
+            Err(err) => err.into_response()
+        }
+    }
+
+    pub async fn update(
+        auth_session: AuthSession,
         Extension(state): Extension<AppState>,
-        Json(recipe_params): Json<ExtensionRecipeParams>,
+        messages: Messages,
+        Path(recipe_id): Path<i32>,
+        Form(pairs): Form<Vec<(String, String)>>,
     ) -> impl IntoResponse {
         let user = match auth_session.user {
             Some(user) => user,
             None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
         };
+        let units = match Unit::list(&state.db).await.map_err(e500) {
+            Ok(units) => units,
+            Err(err) => return err.into_response()
+        };
+        let form = RecipeForm::from_pairs(pairs);
+        let params = match form.validate(&units) {
+            Ok(params) => params,
+            Err(errors) => return render_form(&state, html_templates::RECIPES_EDIT, form, &errors, Some(recipe_id)).await
+        };
 
+        match Recipe::update_full(&state.db, &user.id, recipe_id, &params).await.map_err(e500) {
+            Ok(Some(recipe)) => {
+                messages.success(format!("Saved {}", recipe.name));
+                Redirect::to(&recipe_path(recipe.id)).into_response()
+            },
+            Ok(None) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => err.into_response()
+        }
+    }
 
+    pub async fn delete(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(recipe_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
 
-        "".into_response()
+        match Recipe::delete(&state.db, &user.id, recipe_id).await.map_err(e500) {
+            Ok(true) => {
+                messages.success("Recipe deleted");
+                Redirect::to(route_paths::RECIPES).into_response()
+            },
+            Ok(false) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => err.into_response()
+        }
+    }
+
+    /// Takes the recipe's ingredients out of the user's pantry
+    pub async fn cooked(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(recipe_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+
+        match PantryItem::cook_recipe(&state.db, &user.id, recipe_id).await.map_err(e500) {
+            Ok(true) => {
+                messages.success("Took the ingredients out of your pantry");
+                Redirect::to(&recipe_path(recipe_id)).into_response()
+            },
+            Ok(false) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => err.into_response()
+        }
+    }
+
+    /// Parses the page with the chopper straight away and answers with the saved recipe
+    pub async fn create_from_extension(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        payload: Result<Json<ExtensionRecipeParams>, JsonRejection>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let user = auth_session.user.ok_or(ApiError::Unauthorized)?;
+        let Json(recipe_params) = payload?;
+        if recipe_params.content.trim().is_empty() {
+            return Err(ApiError::Validation(vec!["content can't be blank".to_string()]));
+        }
+
+        let request = ChopperRequest::from(recipe_params);
+        let chopped = state.recipe_parser.parse(&request).await?;
+        let (recipe, outcome) = chopped.save_for_user(&state.db, &user.id, request.on_duplicate).await?;
+        let recipe = Recipe::get_full_recipe_details(&state.db, &user.id, recipe.id).await?;
+        let status = match outcome {
+            ImportOutcome::Created | ImportOutcome::Copied => StatusCode::CREATED,
+            ImportOutcome::Skipped | ImportOutcome::Overwritten => StatusCode::OK,
+        };
+
+        Ok((
+            status,
+            [(axum::http::header::LOCATION, recipe_path(recipe.recipe_id))],
+            Json(recipe),
+        ))
+    }
+
+    /// Imports every recipe in the uploaded archives or Paprika, MealMaster or Mela
+    /// exports and shows how each one went
+    pub async fn import(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        multipart: Result<Multipart, MultipartRejection>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let upload = match multipart.map_err(ApiError::from) {
+            Ok(multipart) => LibraryUpload::read(multipart).await,
+            Err(err) => Err(err),
+        };
+        let upload = match upload {
+            Ok(upload) => upload,
+            Err(ApiError::Validation(errors)) => return render_import(&state, None, &errors),
+            Err(ApiError::BadRequest(message)) => return render_import(&state, None, &[message]),
+            Err(err) => return err.into_response(),
+        };
+
+        let mut report = ImportReport::default();
+        for (file_name, bytes) in &upload.files {
+            report.import_file(&state.db, &user.id, file_name, bytes, upload.on_duplicate).await;
+        }
+
+        render_import(&state, Some(&report), &[])
     }
 }
 
 mod get {
     use super::*;
 
-    pub async fn index(auth_session: AuthSession, Extension(state): Extension<AppState>) -> impl IntoResponse {
+    /// Lists the user's recipes, or the ones matching the search when there is one
+    pub async fn index(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Query(search): Query<RecipeSearchParams>,
+    ) -> impl IntoResponse {
         let user = match auth_session.user {
             Some(user) => user,
             None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
         };
-        let recipes = match user.get_recipes(&state.db).await.map_err(e500) {
-            Ok(recipes) => recipes,
-            Err(err) => return err.into_response()
-        };
-
         let mut context = tera::Context::new();
+        let mut errors = Vec::new();
+        if !search.is_search() {
+            match user.get_recipes(&state.db).await.map_err(e500) {
+                Ok(recipes) => context.insert("recipes", &recipes),
+                Err(err) => return err.into_response()
+            }
+        } else if let Err(search_errors) = search.validate() {
+            context.insert("recipes", &Vec::<Recipe>::new());
+            errors = search_errors;
+        } else {
+            match Recipe::search(&state.db, &user.id, &search).await.map_err(e500) {
+                Ok(recipes) => context.insert("recipes", &recipes),
+                Err(err) => return err.into_response()
+            }
+        }
+        let status = if errors.is_empty() { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+
         let boo = "FROM PROTECTED ROUTE";
-        context.insert("recipes", &recipes);
         context.insert("boo", &boo);
+        context.insert("search", &search);
+        context.insert("is_search", &search.is_search());
+        context.insert("errors", &errors);
         match render_content(
             &RenderTemplateParams::new(html_templates::RECIPES_INDEX, &state.tera)
             .with_context(&context)
         ) {
-            Ok(homepage_template) => Html(homepage_template).into_response(),
+            Ok(homepage_template) => (status, Html(homepage_template)).into_response(),
             Err(e) => e.into_response()
         }
     }
 
-    pub async fn show(
+    pub async fn new(Extension(state): Extension<AppState>) -> impl IntoResponse {
+        render_form(&state, html_templates::RECIPES_NEW, RecipeForm::default(), &[], None).await
+    }
+
+    pub async fn import(Extension(state): Extension<AppState>) -> impl IntoResponse {
+        render_import(&state, None, &[])
+    }
+
+    /// Downloads all of the user's recipes as an archive that `/recipes/import` restores
+    pub async fn export(auth_session: AuthSession, Extension(state): Extension<AppState>) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let exported_at = time::OffsetDateTime::now_utc().unix_timestamp();
+        match export_archive(&state.db, &user.id, exported_at).await.map_err(e500) {
+            Ok(archive) => (
+                [
+                    (axum::http::header::CONTENT_TYPE, "application/zip".to_string()),
+                    (axum::http::header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", ARCHIVE_FILE_NAME)),
+                ],
+                archive,
+            ).into_response(),
+            Err(err) => err.into_response()
+        }
+    }
+
+    pub async fn edit(
         auth_session: AuthSession,
         Extension(state): Extension<AppState>,
         Path(recipe_id): Path<i32>,
@@ -85,18 +442,63 @@ mod get {
         };
         let recipe = match Recipe::get_full_recipe_details(&state.db, &user.id, recipe_id).await {
             Ok(recipe_full_details) => recipe_full_details,
+            Err(crate::models::Error::Sqlx(sqlx::Error::RowNotFound)) => return StatusCode::NOT_FOUND.into_response(),
             Err(err) => return Html(err_500_template(&state.tera, err)).into_response()
         };
-        let mut context = tera::Context::new();
-        context.insert("recipe", &recipe);
-        match render_content(
-            &RenderTemplateParams::new(html_templates::RECIPES_SHOW, &state.tera)
-            .with_context(&context)
-        ).map_err(e500) {
-            Ok(homepage_template) => Html(homepage_template).into_response(),
-            Err(err) => {
-                return (StatusCode::INTERNAL_SERVER_ERROR, Html(err_500_template(&state.tera, err))).into_response()
-            }
+
+        render_form(&state, html_templates::RECIPES_EDIT, RecipeForm::from_recipe(&recipe), &[], Some(recipe_id)).await
+    }
+
+    /// The recipe page, or the recipe as Markdown when the id ends in `.md`
+    pub async fn show(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Path(recipe_file): Path<String>,
+        Query(params): Query<ShowRecipeParams>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let (recipe_id, format) = match parse_recipe_file(&recipe_file) {
+            Some(recipe_file) => recipe_file,
+            None => return StatusCode::NOT_FOUND.into_response()
+        };
+        let recipe = match load_recipe(&state, &user, recipe_id, &params).await {
+            Ok(recipe) => recipe,
+            Err(response) => return response
+        };
+        if format == RecipeFormat::Markdown {
+            return (
+                [(axum::http::header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+                to_markdown(&recipe.shown),
+            ).into_response();
         }
+
+        let nutrition = match RecipeNutrition::for_recipe(&state.db, &recipe.scaled).await {
+            Ok(nutrition) => nutrition,
+            Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, Html(err_500_template(&state.tera, err))).into_response()
+        };
+
+        render_recipe(&state, html_templates::RECIPES_SHOW, &recipe.shown, &recipe.unit_system, Some(&nutrition))
+    }
+
+    /// The recipe laid out to fit on one printed page
+    pub async fn print(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Path(recipe_id): Path<i32>,
+        Query(params): Query<ShowRecipeParams>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let recipe = match load_recipe(&state, &user, recipe_id, &params).await {
+            Ok(recipe) => recipe,
+            Err(response) => return response
+        };
+
+        render_recipe(&state, html_templates::RECIPES_PRINT, &recipe.shown, &recipe.unit_system, None)
     }
 }
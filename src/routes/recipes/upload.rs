@@ -0,0 +1,45 @@
+use axum::extract::Multipart;
+use crate::models::recipe::DuplicatePolicy;
+use crate::utils::ApiError;
+
+/// Paprika libraries carry every recipe's photos, so uploads get far more room than the
+/// default request body limit
+pub const MAX_UPLOAD_BYTES: usize = 100 * 1024 * 1024;
+
+/// The recipe library exports a user uploaded from other apps
+#[derive(Debug, Default)]
+pub struct LibraryUpload {
+    /// File names and contents
+    pub files: Vec<(String, Vec<u8>)>,
+    pub on_duplicate: DuplicatePolicy,
+}
+
+impl LibraryUpload {
+    /// Reads every `file` field, and `onDuplicate` as "skip", "overwrite" or "copy"
+    pub async fn read(mut multipart: Multipart) -> Result<Self, ApiError> {
+        let mut upload = Self::default();
+        while let Some(field) = multipart.next_field().await? {
+            match field.name().map(str::to_string).as_deref() {
+                Some("file") => {
+                    let file_name = field.file_name().unwrap_or("upload").to_string();
+                    let bytes = field.bytes().await?;
+                    if !bytes.is_empty() {
+                        upload.files.push((file_name, bytes.to_vec()));
+                    }
+                },
+                Some("onDuplicate") => {
+                    let value = field.text().await?;
+                    upload.on_duplicate = serde_json::from_value(serde_json::Value::String(value.trim().to_string()))
+                        .map_err(|_| ApiError::Validation(vec![format!("onDuplicate: {} is not skip, overwrite or copy", value)]))?;
+                },
+                _ => {},
+            }
+        }
+
+        if upload.files.is_empty() {
+            return Err(ApiError::Validation(vec!["file: choose a file to import".to_string()]));
+        }
+
+        Ok(upload)
+    }
+}
@@ -0,0 +1,124 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_messages::Messages;
+use serde::Deserialize;
+use crate::startup::AppState;
+use crate::template_helpers::{render_content, RenderTemplateParams};
+
+use crate::user::AuthSession;
+use crate::models::unit::UnitSystem;
+use crate::utils::e500;
+use crate::constants::{
+    route_paths,
+    html_templates,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct SettingsForm {
+    /// An empty value means recipes are shown in the units they were written in
+    pub preferred_unit_system: String,
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::show).post(self::post::update))
+        .route("/calendar_token", post(self::post::generate_calendar_token))
+        .route("/calendar_token/delete", post(self::post::revoke_calendar_token))
+}
+
+mod post {
+    use super::*;
+
+    pub async fn update(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(settings): Form<SettingsForm>,
+    ) -> impl IntoResponse {
+        let mut user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::UNAUTHORIZED.into_response()
+        };
+        let preferred_unit_system = match settings.preferred_unit_system.as_str() {
+            "" => None,
+            system => match system.parse::<UnitSystem>() {
+                Ok(system) => Some(system),
+                Err(err) => {
+                    messages.error(err);
+                    return Redirect::to(route_paths::SETTINGS).into_response();
+                }
+            },
+        };
+        if let Err(err) = user.update_preferred_unit_system(&state.db, preferred_unit_system).await.map_err(e500) {
+            return err.into_response();
+        }
+        messages.success("Settings saved");
+
+        Redirect::to(route_paths::SETTINGS).into_response()
+    }
+
+    /// Makes a new calendar feed link. A link made before stops working.
+    pub async fn generate_calendar_token(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let mut user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::UNAUTHORIZED.into_response()
+        };
+        if let Err(err) = user.generate_calendar_token(&state.db).await.map_err(e500) {
+            return err.into_response();
+        }
+        messages.success("New calendar link made");
+
+        Redirect::to(route_paths::SETTINGS).into_response()
+    }
+
+    pub async fn revoke_calendar_token(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let mut user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::UNAUTHORIZED.into_response()
+        };
+        if let Err(err) = user.revoke_calendar_token(&state.db).await.map_err(e500) {
+            return err.into_response();
+        }
+        messages.success("Calendar link turned off");
+
+        Redirect::to(route_paths::SETTINGS).into_response()
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn show(auth_session: AuthSession, Extension(state): Extension<AppState>) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::UNAUTHORIZED.into_response()
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("preferred_unit_system", &user.preferred_unit_system);
+        context.insert("calendar_url", &user.calendar_token.as_ref().map(|calendar_token| {
+            format!("{}{}/{}.ics", state.base_url.trim_end_matches('/'), route_paths::CALENDAR, calendar_token)
+        }));
+        match render_content(
+            &RenderTemplateParams::new(html_templates::SETTINGS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(settings_template) => Html(settings_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
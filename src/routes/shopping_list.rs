@@ -0,0 +1,71 @@
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use crate::startup::AppState;
+use crate::template_helpers::{render_content, RenderTemplateParams};
+
+use crate::user::AuthSession;
+use crate::models::shopping_list::ShoppingList;
+use crate::utils::e500;
+use crate::constants::{
+    route_paths,
+    html_templates,
+};
+
+pub fn routes() -> Router {
+    Router::new().route(route_paths::ROOT, get(self::get::show))
+}
+
+/// The recipe picker submits one `recipe_id` pair per checked recipe, which can't be
+/// deserialized into a struct, so the ids are pulled out of the raw pairs.
+fn selected_recipe_ids(params: &[(String, String)]) -> Vec<i32> {
+    let mut recipe_ids: Vec<i32> = params
+        .iter()
+        .filter(|(key, _)| key == "recipe_id")
+        .filter_map(|(_, value)| value.parse().ok())
+        .collect();
+    recipe_ids.sort_unstable();
+    recipe_ids.dedup();
+    recipe_ids
+}
+
+mod get {
+    use super::*;
+
+    pub async fn show(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Query(params): Query<Vec<(String, String)>>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        };
+        let recipes = match user.get_recipes(&state.db).await.map_err(e500) {
+            Ok(recipes) => recipes,
+            Err(err) => return err.into_response()
+        };
+        let recipe_ids = selected_recipe_ids(&params);
+        let shopping_list = match ShoppingList::from_recipes(&state.db, &user.id, &recipe_ids).await.map_err(e500) {
+            Ok(shopping_list) => shopping_list,
+            Err(err) => return err.into_response()
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("recipes", &recipes);
+        context.insert("shopping_list", &shopping_list);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::SHOPPING_LIST, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(shopping_list_template) => Html(shopping_list_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! The recipes a development database is seeded with. Kept here rather than in `bin/seeds.rs`
+//! so that tests can use them too.
+use crate::domain::Quantity;
+use crate::models::recipe::{
+    SaveRecipeComponentParams,
+    SaveRecipeIngredientParams,
+    SaveRecipeInstructionParams,
+    SaveRecipeParams,
+};
+
+pub struct SeedRecipe {
+    pub recipe: SaveRecipeParams,
+    pub tags: Vec<&'static str>,
+}
+
+pub fn seed_recipes() -> Vec<SeedRecipe> {
+    vec![habichuelas_guisadas(), kimchi_jjigae()]
+}
+
+fn habichuelas_guisadas() -> SeedRecipe {
+    let recipe = SaveRecipeParams {
+        name: "Habichuelas Guisadas (Puerto Rican Stewed Beans)".to_string(),
+        description: "Habichuelas Guisadas are a Puerto Rican staple, featuring beans stewed in a tomato-based broth infused with country ham, sofrito, sazón, and Mediterranean herbs. This earthy, complex dish pairs beautifully with rice but can also be enjoyed as a standalone meal.".to_string(),
+        is_public: true,
+        prep_time: Some(5),
+        cook_time: Some(40),
+        rest_time: Some(0),
+        servings: Some(4),
+        source_url: Some("https://jinz.co".to_string()),
+        components: vec![
+            component("Main Ingredients", &[
+                ("Olive oil", "tablespoon", 1, 1, false),
+                ("Country ham", "cup", 1, 4, true),
+                ("Puerto Rican sofrito", "cup", 1, 4, false),
+                ("Tomato sauce", "cup", 1, 4, false),
+                ("Sazón con achiote y culantro", "teaspoon", 3, 2, false),
+                ("Ground cumin", "teaspoon", 1, 4, false),
+                ("Dried oregano", "teaspoon", 1, 2, false),
+                ("Dried bay leaves", "piece", 2, 1, false),
+                ("Low sodium chicken broth", "cup", 2, 1, false),
+                ("Pink beans (habichuelas rosadas)", "can", 2, 1, false),
+                ("Potato", "cup", 1, 3, false),
+                ("Pimento-stuffed olives", "piece", 8, 1, false),
+                ("Fresh cilantro", "tablespoon", 2, 1, false),
+                ("Adobo seasoning", "to_taste", 1, 1, false),
+            ]),
+        ],
+        instructions: vec![
+            instruction("Prepare and Cook", &[
+                "In a medium-sized saucepan, heat the olive oil over medium heat. Add the chopped ham and sauté for 2-3 minutes until it starts to caramelize.",
+                "Add the sofrito and Sazón seasoning. Sauté for 2 minutes until fragrant.",
+                "Add the tomato sauce, oregano, bay leaves, and cumin. Sauté for 1 minute.",
+                "Add the chicken stock, beans (with their liquid), chopped potato, pumpkin or carrots, olives, and chopped cilantro. Cover and bring the mixture to a boil.",
+                "Once the mixture comes to a boil, reduce to a simmer and cook for 30-40 minutes, stirring occasionally. Allow the flavors to meld, the beans to become tender, and the pumpkin/carrots to cook. The mixture should be creamy, not soupy.",
+                "Check for seasoning and add Adobo or salt if needed.",
+                "Serve over white or yellow rice, with an extra sprinkle of cilantro if desired.",
+            ]),
+        ],
+    };
+
+    SeedRecipe { recipe, tags: vec!["Puerto Rican", "Beans", "Stew"] }
+}
+
+fn kimchi_jjigae() -> SeedRecipe {
+    let recipe = SaveRecipeParams {
+        name: "Kimchi Stew (Kimchi Jjigae)".to_string(),
+        description: "Kimchi Jjigae is a comforting and spicy stew made with kimchi, pork, tofu, and a savory broth. It's perfect for warming up on a cold day and is traditionally enjoyed with a bowl of rice.".to_string(),
+        is_public: true,
+        prep_time: Some(20),
+        cook_time: Some(35),
+        rest_time: Some(0),
+        servings: Some(4),
+        source_url: Some("https://kimchi.jinz.co".to_string()),
+        components: vec![
+            component("For the Kimchi Stew", &[
+                ("Kimchi", "pound", 1, 1, false),
+                ("Kimchi brine", "cup", 1, 4, false),
+                ("Pork shoulder or pork belly", "pound", 1, 2, false),
+                ("Tofu", "package", 1, 2, true),
+                ("Green onions", "piece", 3, 1, false),
+                ("Onion", "piece", 1, 1, false),
+                ("Kosher salt", "teaspoon", 1, 1, false),
+                ("Sugar", "teaspoon", 2, 1, false),
+                ("Gochugaru (Korean hot pepper flakes)", "teaspoon", 2, 1, false),
+                ("Gochujang (Korean hot pepper paste)", "tablespoon", 1, 1, false),
+                ("Toasted sesame oil", "teaspoon", 1, 1, false),
+                ("Anchovy stock (or chicken or beef broth)", "cup", 2, 1, false),
+            ]),
+            component("For the Anchovy Stock", &[
+                ("Dried anchovies", "piece", 7, 1, false),
+                ("Korean radish (or daikon radish)", "cup", 1, 3, false),
+                ("Dried kelp", "piece", 1, 1, false),
+                ("Water", "cup", 4, 1, false),
+            ]),
+        ],
+        instructions: vec![
+            instruction("Make Anchovy Stock", &[
+                "Combine dried anchovies, radish, green onion roots, and dried kelp in a saucepan.",
+                "Add water and bring to a boil over medium-high heat.",
+                "Reduce the heat and simmer for 20 minutes.",
+                "Lower the heat to low and simmer for another 5 minutes.",
+                "Strain the stock and set aside.",
+            ]),
+            instruction("Make Kimchi Stew", &[
+                "Place the kimchi and kimchi brine in a shallow pot. Add the pork and onion slices.",
+                "Slice 2 green onions diagonally and add them to the pot.",
+                "Add salt, sugar, gochugaru, and gochujang. Drizzle sesame oil over the ingredients.",
+                "Pour in the prepared anchovy stock.",
+                "Cover and cook for 10 minutes over medium-high heat.",
+                "Uncover and stir the stew, mixing in the seasonings. Lay the tofu slices over the top, if using.",
+                "Cover and cook for another 10-15 minutes over medium heat.",
+                "Chop the remaining green onion and sprinkle over the stew before serving.",
+                "Serve hot with rice.",
+            ]),
+        ],
+    };
+
+    SeedRecipe { recipe, tags: vec!["Korean", "Stew", "Spicy", "Kimchi"] }
+}
+
+/// Ingredients are (name, unit, numerator, denominator, is_optional)
+fn component(name: &str, ingredients: &[(&str, &str, i32, i32, bool)]) -> SaveRecipeComponentParams {
+    SaveRecipeComponentParams {
+        name: name.to_string(),
+        is_optional: false,
+        ingredients: ingredients
+            .iter()
+            .map(|&(name, unit, numerator, denominator, is_optional)| SaveRecipeIngredientParams {
+                name: name.to_string(),
+                description: String::new(),
+                unit: unit.to_string(),
+                quantity: Quantity::new(numerator, denominator).expect("Seed quantities are valid"),
+                is_optional,
+            })
+            .collect(),
+    }
+}
+
+fn instruction(title: &str, steps: &[&str]) -> SaveRecipeInstructionParams {
+    SaveRecipeInstructionParams {
+        title: title.to_string(),
+        steps: steps.iter().map(|step| step.to_string()).collect(),
+    }
+}
@@ -8,6 +8,7 @@ use axum::{Extension,Router};
 use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
 use std::sync::Arc;
+use std::time::Duration;
 use tera::Tera;
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::cors::{Any, CorsLayer};
@@ -31,8 +32,15 @@ use crate::routes::auth_routes;
 use crate::routes::protected_routes;
 use crate::routes::recipe_routes;
 use crate::routes::api_routes;
+use crate::routes::shopping_list_routes;
+use crate::routes::pantry_routes;
+use crate::routes::meal_plan_routes;
+use crate::routes::calendar_routes;
+use crate::routes::settings_routes;
 use crate::user::Backend;
 use crate::constants::strings;
+use crate::template_helpers;
+use crate::chopper::{self, PageFetcher, RecipeParser};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -40,17 +48,18 @@ pub struct AppState {
     pub hmac_secret: Secret<String>,
     pub tera: Arc<Tera>,
     pub email_settings: EmailSettings,
+    pub recipe_parser: Arc<dyn RecipeParser>,
+    pub page_fetcher: PageFetcher,
+    /// Where the app is reached from outside, for links in feeds
+    pub base_url: String,
 }
 
 pub struct Application {
     port: u16,
-    db_pool: PgPool,
-    tera: Arc<Tera>,
     listener: TcpListener,
-    base_url: String,
     redis_uri: Secret<String>,
-    hmac_secret: Secret<String>,
-    email_settings: EmailSettings,
+    app_state: AppState,
+    chopper_poll_interval: Duration,
 }
 
 impl Application {
@@ -73,17 +82,28 @@ impl Application {
             },
         };
         tera.autoescape_on(vec!["html", "sql"]);
+        tera.register_filter("quantity", template_helpers::QuantityFilter);
+        tera.register_filter("ingredient", template_helpers::IngredientFilter);
         let tera = Arc::new(tera);
+        let recipe_parser = chopper::parser_from_settings(&configuration.chopper)?;
+        let page_fetcher = PageFetcher::new(&configuration.chopper)?;
+
+        let app_state = AppState {
+            db: connection_pool,
+            hmac_secret: configuration.application.hmac_secret,
+            tera,
+            email_settings: configuration.email,
+            recipe_parser,
+            page_fetcher,
+            base_url: configuration.application.base_url,
+        };
 
         Ok(Self {
             port,
-            tera,
             listener,
-            db_pool: connection_pool,
-            base_url: configuration.application.base_url,
             redis_uri: configuration.redis_uri,
-            hmac_secret: configuration.application.hmac_secret,
-            email_settings: configuration.email,
+            app_state,
+            chopper_poll_interval: Duration::from_millis(configuration.chopper.poll_interval_milliseconds),
         })
     }
 
@@ -92,9 +112,7 @@ impl Application {
     }
 
     pub async fn run_until_stopped(self) -> Result<(), anyhow::Error> {
-        run(
-            self.db_pool, self.listener, self.base_url, self.redis_uri, self.hmac_secret, self.tera, self.email_settings
-            ).await
+        run(self.listener, self.redis_uri, self.app_state, self.chopper_poll_interval).await
     }
 }
 
@@ -108,7 +126,9 @@ pub fn get_connection_pool(
 
 pub struct ApplicationBaseUrl(pub String);
 
-pub async fn run(db_pool: PgPool, listener: TcpListener, _base_url: String, _redis_uri: Secret<String>, hmac_secret: Secret<String>, tera: Arc<Tera>, email_settings: EmailSettings) -> Result<(), anyhow::Error> {
+pub async fn run(listener: TcpListener, _redis_uri: Secret<String>, app_state: AppState, chopper_poll_interval: Duration) -> Result<(), anyhow::Error> {
+    let db_pool = app_state.db.clone();
+
     // Session layer.
     //
     // This uses `tower-sessions` to establish a layer that will provide the session
@@ -121,6 +141,10 @@ pub async fn run(db_pool: PgPool, listener: TcpListener, _base_url: String, _red
         .continuously_delete_expired(tokio::time::Duration::from_secs(60)),
     );
 
+    // Chopper imports are queued and parsed in the background
+    let chopper_worker_task = tokio::task::spawn(
+        chopper::run_worker(db_pool.clone(), app_state.recipe_parser.clone(), chopper_poll_interval),
+    );
 
     // Generate a cryptographic key to sign the session cookie.
     // let key = Key::generate();
@@ -133,14 +157,8 @@ pub async fn run(db_pool: PgPool, listener: TcpListener, _base_url: String, _red
     //
     // This combines the session layer with our backend to establish the auth
     // service which will provide the auth session as a request extension.
-    let backend = Backend::new(db_pool.clone());
+    let backend = Backend::new(db_pool);
     let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
-    let app_state = AppState {
-        db: db_pool,
-        hmac_secret,
-        tera,
-        email_settings,
-    };
 
     let app = api_router(&app_state)
         .layer(TraceLayer::new_for_http())
@@ -155,6 +173,7 @@ pub async fn run(db_pool: PgPool, listener: TcpListener, _base_url: String, _red
         .with_graceful_shutdown(shutdown_signal(deletion_task.abort_handle()))
         .await?;
 
+    chopper_worker_task.abort();
     deletion_task.await??;
     Ok(())
 }
@@ -179,6 +198,11 @@ fn api_router(app_state: &AppState) -> Router {
         .merge(protected_routes())
         .merge(auth_routes())
         .merge(recipe_routes())
+        .merge(shopping_list_routes())
+        .merge(pantry_routes())
+        .merge(meal_plan_routes())
+        .merge(calendar_routes())
+        .merge(settings_routes())
         .merge(api_routes(app_state))
         .layer(cors)
 }
@@ -1,4 +1,7 @@
 use std::sync::Arc;
+use crate::domain::Quantity;
+use crate::formats::markdown::ingredient_text;
+use crate::models::recipe::FullRecipeComponentIngredient;
 use crate::utils::{e500, ErrorResponse};
 use crate::constants::{
     strings,
@@ -86,3 +89,68 @@ pub fn round_hundreths(value: &tera::Value, _: &std::collections::HashMap<String
         None => Err("Failed to format value as f64 in round_hundreths".into()),
     }
 }
+
+/// Formats a serialized `Quantity` as a mixed number, e.g. `{{ rci.quantity | quantity }}` renders "1 1/2"
+pub fn quantity_format(value: &tera::Value, _: &std::collections::HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    match serde_json::from_value::<Quantity>(value.clone()) {
+        Ok(quantity) => Ok(tera::Value::String(quantity.to_string())),
+        Err(_) => Err("Failed to format value as quantity".into()),
+    }
+}
+
+/// `quantity_format` as a filter that isn't autoescaped, so that "1/2" isn't rendered as
+/// `1&#x2F;2`. Quantities are only ever digits, spaces and slashes.
+pub struct QuantityFilter;
+
+impl tera::Filter for QuantityFilter {
+    fn filter(&self, value: &tera::Value, args: &std::collections::HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        quantity_format(value, args)
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Formats a serialized `FullRecipeComponentIngredient` the way a cookbook would, e.g.
+/// `{{ rci | ingredient }}` renders "1 1/2 cup flour, sifted"
+pub fn ingredient_format(value: &tera::Value, _: &std::collections::HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    match serde_json::from_value::<FullRecipeComponentIngredient>(value.clone()) {
+        Ok(ingredient) => Ok(tera::Value::String(ingredient_text(&ingredient))),
+        Err(_) => Err("Failed to format value as ingredient".into()),
+    }
+}
+
+/// `ingredient_format` as a filter that escapes the names and notes itself, but not the slashes
+/// in quantities
+pub struct IngredientFilter;
+
+impl tera::Filter for IngredientFilter {
+    fn filter(&self, value: &tera::Value, args: &std::collections::HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        match ingredient_format(value, args)? {
+            tera::Value::String(text) => Ok(tera::Value::String(escape_text(&text))),
+            other => Ok(other),
+        }
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Escapes the characters that are special in HTML text and attribute values
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
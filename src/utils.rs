@@ -47,3 +47,96 @@ impl std::fmt::Display for ErrorResponse {
         write!(f, "Error ({}): {}", self.status_code, self.message)
     }
 }
+
+/// Errors returned by the JSON API. Every variant is sent as
+/// `{"error": {"code": "...", "message": "...", "details": [...]}}`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    Unauthorized,
+    NotFound(String),
+    BadRequest(String),
+    Validation(Vec<String>),
+    /// A service we depend on, such as the chopper's language model, failed
+    Upstream(String),
+    Internal(String),
+}
+
+#[derive(serde::Serialize)]
+struct ApiErrorBody {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    details: Vec<String>,
+}
+
+impl ApiError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Upstream(_) => "upstream_error",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status_code, code) = (self.status_code(), self.code());
+        let (message, details) = match self {
+            ApiError::Unauthorized => ("A valid API key is required".to_string(), Vec::new()),
+            ApiError::NotFound(message) | ApiError::BadRequest(message) | ApiError::Upstream(message) => (message, Vec::new()),
+            ApiError::Validation(details) => ("The request is invalid".to_string(), details),
+            // The details of internal errors are logged rather than sent to the client
+            ApiError::Internal(message) => {
+                tracing::error!("{}", message);
+                (crate::constants::strings::INTERNAL_SERVER_ERROR.to_string(), Vec::new())
+            },
+        };
+        let body = ApiErrorBody { code, message, details };
+
+        (status_code, axum::Json(serde_json::json!({ "error": body }))).into_response()
+    }
+}
+
+impl From<crate::models::Error> for ApiError {
+    fn from(err: crate::models::Error) -> Self {
+        match err {
+            crate::models::Error::NotFound | crate::models::Error::Sqlx(sqlx::Error::RowNotFound) => ApiError::NotFound("Resource not found".to_string()),
+            crate::models::Error::Quantity(err) => ApiError::BadRequest(err.to_string()),
+            crate::models::Error::UnknownUnit(unit) => ApiError::Validation(vec![format!("Unknown unit {}", unit)]),
+            err => ApiError::Internal(format!("{:?}", err)),
+        }
+    }
+}
+
+impl From<axum::extract::rejection::JsonRejection> for ApiError {
+    fn from(rejection: axum::extract::rejection::JsonRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<axum::extract::multipart::MultipartRejection> for ApiError {
+    fn from(rejection: axum::extract::multipart::MultipartRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<axum::extract::multipart::MultipartError> for ApiError {
+    fn from(err: axum::extract::multipart::MultipartError) -> Self {
+        ApiError::BadRequest(err.body_text())
+    }
+}
@@ -0,0 +1,239 @@
+use chopping_list::models::ingredient::Ingredient;
+use chopping_list::models::recipe::{CreateRecipeParams, FullRecipeDetails, Recipe, SaveRecipeParams};
+use chopping_list::models::unit;
+use chopping_list::seeds::seed_recipes;
+use reqwest::Method;
+use serde_json::{json, Value};
+use crate::helpers::{spawn_app, create_recipe};
+
+fn stew() -> Value {
+    json!({
+        "name": "Stew",
+        "servings": 4,
+        "components": [{
+            "name": "Main",
+            "ingredients": [
+                { "name": "Potato", "unit": "cup", "quantity": { "numerator": 3, "denominator": 2 } }
+            ]
+        }],
+        "instructions": [{ "title": "Cook", "steps": ["Chop", "Boil"] }]
+    })
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_api_key_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app.api_v1(Method::GET, "/recipes", "not-a-key").send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "unauthorized");
+}
+
+#[tokio::test]
+async fn recipes_can_be_created_read_updated_and_deleted() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+
+    let response = app.api_v1(Method::POST, "/recipes", &api_key).json(&stew()).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    let recipe_id = created["recipe_id"].as_i64().unwrap();
+    assert_eq!(created["recipe_components"][0]["component_ingredients"][0]["name"], "Potato");
+    assert_eq!(created["recipe_instructions"][0]["instruction_steps"][1]["content"], "Boil");
+
+    let response = app.api_v1(Method::GET, "/recipes", &api_key).send().await.unwrap();
+    let recipes: Value = response.json().await.unwrap();
+    assert_eq!(recipes.as_array().unwrap().len(), 1);
+
+    let mut soup = stew();
+    soup["name"] = json!("Soup");
+    let response = app.api_v1(Method::PUT, &format!("/recipes/{}", recipe_id), &api_key).json(&soup).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let updated: Value = response.json().await.unwrap();
+    assert_eq!(updated["name"], "Soup");
+
+    let response = app.api_v1(Method::DELETE, &format!("/recipes/{}", recipe_id), &api_key).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+    let response = app.api_v1(Method::GET, &format!("/recipes/{}", recipe_id), &api_key).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "not_found");
+}
+
+#[tokio::test]
+async fn get_recipe_scales_servings() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Stew".to_string(), String::new()).with_servings(4);
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Potato", "cup", 1, 3)]).await;
+
+    let response = app.api_v1(Method::GET, &format!("/recipes/{}?servings=6", recipe.id), &api_key).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["servings"], 6);
+    assert_eq!(body["recipe_components"][0]["component_ingredients"][0]["quantity"], json!({ "numerator": 1, "denominator": 2 }));
+}
+
+#[tokio::test]
+async fn get_recipe_uses_the_preferred_unit_system() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Stew".to_string(), String::new());
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Pork shoulder", "pound", 1, 2)]).await;
+    app.login_test_user().await;
+    app.post_settings(&json!({ "preferred_unit_system": "metric" })).await;
+
+    let response = app.api_v1(Method::GET, &format!("/recipes/{}", recipe.id), &api_key).send().await.unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["recipe_components"][0]["component_ingredients"][0]["unit"], "gram");
+
+    let response = app.api_v1(Method::GET, &format!("/recipes/{}?system=original", recipe.id), &api_key).send().await.unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["recipe_components"][0]["component_ingredients"][0]["unit"], "pound");
+}
+
+#[tokio::test]
+async fn invalid_recipes_are_rejected_with_details() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    let mut recipe = stew();
+    recipe["name"] = json!("");
+    recipe["components"][0]["ingredients"][0]["unit"] = json!("bushel");
+
+    let response = app.api_v1(Method::POST, "/recipes", &api_key).json(&recipe).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "validation_failed");
+    assert_eq!(body["error"]["details"].as_array().unwrap().len(), 2);
+
+    let response = app.api_v1(Method::POST, "/recipes", &api_key).body("not json").header("Content-Type", "application/json").send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn seeded_recipes_round_trip_through_cooklang() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+
+    for seed in seed_recipes() {
+        let recipe = Recipe::create_full(&app.db_pool, &app.test_user.user_id, &seed.recipe).await.unwrap();
+        let response = app.api_v1(Method::GET, &format!("/recipes/{}/cooklang", recipe.id), &api_key).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let cooklang = response.text().await.unwrap();
+
+        let response = app.api_v1(Method::POST, "/recipes/cooklang", &api_key).body(cooklang.clone()).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED, "{}", cooklang);
+        let imported: FullRecipeDetails = response.json().await.unwrap();
+        assert_ne!(imported.recipe_id, recipe.id);
+        assert_eq!(SaveRecipeParams::from(&imported), SaveRecipeParams { is_public: false, ..seed.recipe });
+    }
+}
+
+#[tokio::test]
+async fn cooklang_files_without_a_title_are_named_after_the_file() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+
+    let response = app.api_v1(Method::POST, "/recipes/cooklang?name=Toast.cook", &api_key)
+        .body("Toast @bread{2} and spread with @butter.")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let recipe: Value = response.json().await.unwrap();
+    assert_eq!(recipe["name"], "Toast");
+    assert_eq!(recipe["recipe_instructions"][0]["instruction_steps"][0]["content"], "Toast bread and spread with butter.");
+}
+
+#[tokio::test]
+async fn invalid_cooklang_is_rejected_with_details() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+
+    let response = app.api_v1(Method::POST, "/recipes/cooklang", &api_key)
+        .body(">> title: Soup\n\nAdd @water{2%cups")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["details"][0], "line 3: @water is missing its closing }");
+
+    let response = app.api_v1(Method::POST, "/recipes/cooklang", &api_key)
+        .body(">> title: Soup\n\nAdd @water{2%buckets}")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn recipes_are_ranked_by_the_ingredients_on_hand() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    let seeds = seed_recipes();
+    for seed in &seeds {
+        Recipe::create_full(&app.db_pool, &app.test_user.user_id, &seed.recipe).await.unwrap();
+    }
+
+    // Everything the beans need except the cumin, and none of the optional ham
+    let mut on_hand: Vec<Value> = seeds[0].recipe.components[0].ingredients
+        .iter()
+        .filter(|ingredient| !ingredient.is_optional && ingredient.name != "Ground cumin")
+        .map(|ingredient| json!(ingredient.name.to_lowercase()))
+        .collect();
+    let kimchi = Ingredient::find_by_name(&app.db_pool, "Kimchi").await.unwrap().unwrap();
+    on_hand.push(json!(kimchi.id));
+    on_hand.push(json!("Unicorn"));
+
+    let response = app.api_v1(Method::POST, "/recipes/cookable", &api_key)
+        .json(&json!({ "ingredients": on_hand }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["unknown_ingredients"], json!(["Unicorn"]));
+    let recipes = body["recipes"].as_array().unwrap();
+    assert_eq!(recipes[0]["name"], seeds[0].recipe.name);
+    assert_eq!(recipes[0]["missing"], json!([{
+        "ingredient_id": Ingredient::find_by_name(&app.db_pool, "Ground cumin").await.unwrap().unwrap().id,
+        "name": "Ground cumin",
+        "component": "Main Ingredients",
+    }]));
+    assert_eq!(recipes[0]["covered"].as_u64().unwrap() + 1, recipes[0]["required"].as_u64().unwrap());
+
+    assert_eq!(recipes[1]["name"], seeds[1].recipe.name);
+    assert_eq!(recipes[1]["covered"], 1);
+    let missing: Vec<&str> = recipes[1]["missing"].as_array().unwrap().iter().map(|m| m["name"].as_str().unwrap()).collect();
+    assert!(!missing.contains(&"Kimchi"));
+    assert!(!missing.contains(&"Tofu"));
+}
+
+#[tokio::test]
+async fn cookable_needs_some_ingredients() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+
+    let response = app.api_v1(Method::POST, "/recipes/cookable", &api_key)
+        .json(&json!({ "ingredients": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+}
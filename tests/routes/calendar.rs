@@ -0,0 +1,58 @@
+use chopping_list::models::recipe::CreateRecipeParams;
+use crate::helpers::{spawn_app, assert_is_redirect_to, create_recipe, TestApp};
+
+async fn calendar_token(app: &TestApp) -> Option<String> {
+    sqlx::query_scalar("SELECT calendar_token FROM users WHERE id = $1")
+        .bind(app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn planned_meals_are_in_the_calendar_feed() {
+    let app = spawn_app().await;
+    let stew = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Stew".to_string(), String::new()).with_prep_time(30).with_cook_time(120), &[]).await;
+    app.login_test_user().await;
+    let stew_id = stew.id.to_string();
+    let response = app.post_meal_plan(&[("recipe_id", &stew_id), ("planned_on", "2099-08-06"), ("meal", "dinner"), ("servings", "")]).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let response = app.post_calendar_token().await;
+    assert_is_redirect_to(&response, "/settings");
+    let calendar_token = calendar_token(&app).await.unwrap();
+    let settings = app.get_settings().await.text().await.unwrap();
+    assert!(settings.contains(&format!("{}.ics", calendar_token)));
+
+    let response = app.get_calendar(&format!("{}.ics", calendar_token)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "text/calendar; charset=utf-8");
+    let calendar = response.text().await.unwrap().replace("\r\n ", "");
+    assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(calendar.contains("SUMMARY:Dinner: Stew\r\n"));
+    assert!(calendar.contains("DTSTART:20990806T163000\r\n"));
+    assert!(calendar.contains("DTEND:20990806T190000\r\n"));
+    let url_line = calendar.lines().find(|line| line.starts_with("URL:")).unwrap();
+    assert!(url_line.ends_with(&format!("/recipes/{}", stew.id)));
+}
+
+#[tokio::test]
+async fn calendar_links_can_be_replaced_and_turned_off() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    app.post_calendar_token().await;
+    let old_token = calendar_token(&app).await.unwrap();
+
+    app.post_calendar_token().await;
+    let new_token = calendar_token(&app).await.unwrap();
+    assert_ne!(old_token, new_token);
+    assert_eq!(app.get_calendar(&format!("{}.ics", old_token)).await.status().as_u16(), 404);
+    assert_eq!(app.get_calendar(&format!("{}.ics", new_token)).await.status().as_u16(), 200);
+    assert_eq!(app.get_calendar(&new_token).await.status().as_u16(), 404);
+
+    let response = app.post_revoke_calendar_token().await;
+    assert_is_redirect_to(&response, "/settings");
+    assert_eq!(calendar_token(&app).await, None);
+    assert_eq!(app.get_calendar(&format!("{}.ics", new_token)).await.status().as_u16(), 404);
+    assert_eq!(app.get_calendar(".ics").await.status().as_u16(), 404);
+}
@@ -0,0 +1,356 @@
+use async_trait::async_trait;
+use chopping_list::chopper::{run_job, ChopperError, ChopperRecipe, FixtureParser, ParseError, RecipeParser, ChopperRequest};
+use chopping_list::domain::normalize_source_url;
+use chopping_list::models::chopper_job::{ChopperJob, ChopperJobStatus, MAX_ATTEMPTS};
+use chopping_list::models::recipe::{DuplicatePolicy, Recipe};
+use chopping_list::models::unit;
+use serde_json::Value;
+use crate::helpers::{spawn_app, TestApp};
+
+async fn recipe_count(db_pool: &sqlx::PgPool) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM recipes")
+        .fetch_one(db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn chopper_saves_the_parsed_recipe() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+
+    let response = app.post_chopper(&api_key, "A stew recipe", "https://example.com/stew").await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+    let body: Value = response.json().await.unwrap();
+    let job_id = body["id"].as_str().unwrap();
+    let job = app.wait_for_chopper_job(&api_key, job_id).await;
+    assert_eq!(job["status"], "succeeded");
+    assert_eq!(job["outcome"], "created");
+
+    let recipe = recipe_for_job(&app, &api_key, &job).await;
+    assert_eq!(recipe["name"], "Fixture Stew");
+    assert_eq!(recipe["source_url"], "https://example.com/stew");
+
+    let ingredient_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM recipe_component_ingredients")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(ingredient_count, 2);
+}
+
+#[tokio::test]
+async fn add_to_user_writes_nothing_when_part_of_the_recipe_is_invalid() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let mut recipe: ChopperRecipe = serde_json::from_str(include_str!("../fixtures/chopper_recipe.json")).unwrap();
+    recipe.components[0].ingredients[1].quantity_denominator = 0;
+
+    let result = recipe.add_to_user(&app.db_pool, &app.test_user.user_id).await;
+    assert!(result.is_err());
+    assert_eq!(recipe_count(&app.db_pool).await, 0);
+
+    let ingredient_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ingredients WHERE name = 'Potato'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(ingredient_count, 0);
+}
+
+#[tokio::test]
+async fn add_to_user_rolls_back_when_a_write_fails_partway_through() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let mut recipe: ChopperRecipe = serde_json::from_str(include_str!("../fixtures/chopper_recipe.json")).unwrap();
+    // Valid as far as the recipe can tell, but Postgres refuses NUL in text, so the very
+    // last write fails after the recipe, its components and ingredients are written
+    recipe.instructions[0].steps[1].content = "Simmer\0for an hour".to_string();
+    assert!(recipe.validate().is_ok());
+
+    let result = recipe.add_to_user(&app.db_pool, &app.test_user.user_id).await;
+    assert!(matches!(result, Err(ChopperError::Model(_)) | Err(ChopperError::Sqlx(_))));
+    assert_eq!(recipe_count(&app.db_pool).await, 0);
+    for table in ["recipe_components", "recipe_component_ingredients", "recipe_instructions", "recipe_instruction_steps"] {
+        let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0, "{} were written", table);
+    }
+    let ingredient_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ingredients WHERE name = 'Potato'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(ingredient_count, 0);
+}
+
+#[tokio::test]
+async fn add_to_user_keeps_optional_ingredients_optional() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let mut recipe: ChopperRecipe = serde_json::from_str(include_str!("../fixtures/chopper_recipe.json")).unwrap();
+    recipe.components[0].ingredients[1].is_optional = true;
+
+    let saved = recipe.add_to_user(&app.db_pool, &app.test_user.user_id).await.unwrap();
+    let details = Recipe::get_full_recipe_details(&app.db_pool, &app.test_user.user_id, saved.id).await.unwrap();
+    let optional: Vec<bool> = details.recipe_components[0].component_ingredients.iter().map(|ingredient| ingredient.is_optional).collect();
+    assert_eq!(optional, vec![false, true]);
+}
+
+#[tokio::test]
+async fn fixture_parser_uses_the_requested_source_url() {
+    let parser = FixtureParser::from_file("tests/fixtures/chopper_recipe.json").unwrap();
+    let request: ChopperRequest = serde_json::from_value(serde_json::json!({
+        "text": "",
+        "sourceUrl": "https://example.com/other",
+        "hostname": "example.com",
+        "pathname": "/other",
+    })).unwrap();
+
+    let recipe = parser.parse(&request).await.unwrap();
+    assert_eq!(recipe.name, "Fixture Stew");
+    assert_eq!(recipe.source_url, "https://example.com/other");
+}
+
+#[tokio::test]
+async fn chopper_reads_structured_data_without_the_model() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    let html = include_str!("../fixtures/json_ld/graph.html");
+
+    let response = app.post_chopper(&api_key, html, "https://example.com/pancakes").await;
+    let body: Value = response.json().await.unwrap();
+    let job = app.wait_for_chopper_job(&api_key, body["id"].as_str().unwrap()).await;
+
+    let recipe = recipe_for_job(&app, &api_key, &job).await;
+    assert_eq!(recipe["name"], "Fluffy Pancakes");
+    assert_eq!(recipe["servings"], 4);
+}
+
+#[tokio::test]
+async fn chopper_answers_with_the_job_to_poll() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+
+    let response = app.post_chopper(&api_key, "A stew recipe", "https://example.com/stew").await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let location = response.headers().get("Location").unwrap().to_str().unwrap().to_string();
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(location, format!("/api/chopper/jobs/{}", body["id"].as_str().unwrap()));
+    assert!(body["recipe_id"].is_null());
+}
+
+#[tokio::test]
+async fn failed_jobs_report_their_error() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+    let job_id = uuid::Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO chopper_jobs (id, user_id, text, source_url, hostname, pathname, status, error)
+        VALUES ($1, $2, '', '', '', '', 'failed', 'The provider answered with 500')"
+    )
+    .bind(job_id)
+    .bind(app.test_user.user_id)
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let job: Value = app.get_chopper_job(&api_key, &job_id.to_string()).await.json().await.unwrap();
+    assert_eq!(job["status"], "failed");
+    assert_eq!(job["error"], "The provider answered with 500");
+    assert!(job["recipe_id"].is_null());
+}
+
+struct PanickingParser;
+
+#[async_trait]
+impl RecipeParser for PanickingParser {
+    async fn parse(&self, _request: &ChopperRequest) -> Result<ChopperRecipe, ParseError> {
+        panic!("the page broke the parser")
+    }
+}
+
+#[tokio::test]
+async fn jobs_that_panic_fail_with_the_reason() {
+    let app = spawn_app().await;
+    let job = ChopperJob {
+        id: uuid::Uuid::new_v4(),
+        user_id: app.test_user.user_id,
+        text: String::new(),
+        source_url: "https://example.com/stew".to_string(),
+        hostname: "example.com".to_string(),
+        pathname: "/stew".to_string(),
+        status: ChopperJobStatus::Running,
+        on_duplicate: DuplicatePolicy::Skip,
+        recipe_id: None,
+        outcome: None,
+        error: None,
+        attempts: 1,
+    };
+
+    let result = run_job(&app.db_pool, &PanickingParser, &job).await;
+    assert_eq!(result.unwrap_err(), "The chopper crashed: the page broke the parser");
+}
+
+/// A job some worker picked up `attempts` times without finishing it
+async fn insert_abandoned_job(app: &TestApp, attempts: i32) -> uuid::Uuid {
+    let job_id = uuid::Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO chopper_jobs (id, user_id, text, source_url, hostname, pathname, status, attempts, updated_at)
+        VALUES ($1, $2, 'A stew recipe', 'https://example.com/stew', 'example.com', '/stew', 'running', $3, NOW() - INTERVAL '1 hour')"
+    )
+    .bind(job_id)
+    .bind(app.test_user.user_id)
+    .bind(attempts)
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    job_id
+}
+
+#[tokio::test]
+async fn abandoned_jobs_are_retried_until_they_run_out_of_attempts() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+
+    let retried = insert_abandoned_job(&app, 1).await;
+    let given_up = insert_abandoned_job(&app, MAX_ATTEMPTS).await;
+
+    let job = app.wait_for_chopper_job(&api_key, &retried.to_string()).await;
+    assert_eq!(job["status"], "succeeded");
+    let attempts: i32 = sqlx::query_scalar("SELECT attempts FROM chopper_jobs WHERE id = $1")
+        .bind(retried)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(attempts, 2);
+
+    let job = app.wait_for_chopper_job(&api_key, &given_up.to_string()).await;
+    assert_eq!(job["status"], "failed");
+    assert_eq!(job["error"], format!("Gave up after {} attempts", MAX_ATTEMPTS));
+    assert!(job["recipe_id"].is_null());
+}
+
+#[tokio::test]
+async fn unknown_jobs_are_not_found() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+
+    let response = app.get_chopper_job(&api_key, &uuid::Uuid::new_v4().to_string()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn chopping_the_same_page_again_skips_it() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+
+    let first = chop(&app, &api_key, "https://example.com/stew", None).await;
+    let second = chop(&app, &api_key, "http://www.example.com/stew/?utm_source=pinterest", None).await;
+
+    assert_eq!(second["status"], "succeeded");
+    assert_eq!(second["outcome"], "skipped");
+    assert_eq!(second["recipe_id"], first["recipe_id"]);
+    assert_eq!(recipe_count(&app.db_pool).await, 1);
+}
+
+#[tokio::test]
+async fn recipes_saved_before_urls_were_normalized_are_backfilled_like_imports_normalize() {
+    let app = spawn_app().await;
+    for source_url in [
+        " https://www.Example.com/stew/?utm_source=pinterest&fbclid=abc#comments ",
+        "http://example.com:80/stew?p=2&id=7&a1=x&a=y",
+        "https://example.com:8443/stew?ref=home&flag",
+        "http://localhost:8000/Stew/",
+        "Grandma's Cookbook",
+    ] {
+        let backfilled: String = sqlx::query_scalar("SELECT normalize_source_url($1)")
+            .bind(source_url)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+        assert_eq!(backfilled, normalize_source_url(source_url), "{}", source_url);
+    }
+}
+
+#[tokio::test]
+async fn importing_the_same_page_at_once_saves_it_once() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let mut recipe: ChopperRecipe = serde_json::from_str(include_str!("../fixtures/chopper_recipe.json")).unwrap();
+    recipe.source_url = "https://example.com/stew".to_string();
+
+    let (first, second) = tokio::join!(
+        recipe.save_for_user(&app.db_pool, &app.test_user.user_id, DuplicatePolicy::Skip),
+        recipe.save_for_user(&app.db_pool, &app.test_user.user_id, DuplicatePolicy::Skip),
+    );
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert_eq!(first.0.id, second.0.id);
+    assert_eq!(recipe_count(&app.db_pool).await, 1);
+}
+
+#[tokio::test]
+async fn chopping_the_same_page_can_overwrite_the_recipe() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+
+    let first = chop(&app, &api_key, "https://example.com/stew", None).await;
+    sqlx::query("UPDATE recipes SET name = 'Edited Stew'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let second = chop(&app, &api_key, "https://example.com/stew#method", Some("overwrite")).await;
+
+    assert_eq!(second["outcome"], "overwritten");
+    assert_eq!(second["recipe_id"], first["recipe_id"]);
+    assert_eq!(recipe_count(&app.db_pool).await, 1);
+
+    let recipe = recipe_for_job(&app, &api_key, &second).await;
+    assert_eq!(recipe["name"], "Fixture Stew");
+    let ingredient_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM recipe_component_ingredients")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(ingredient_count, 2);
+}
+
+#[tokio::test]
+async fn chopping_the_same_page_can_keep_a_copy() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+
+    let first = chop(&app, &api_key, "https://example.com/stew", None).await;
+    let second = chop(&app, &api_key, "https://example.com/stew", Some("copy")).await;
+
+    assert_eq!(second["outcome"], "copied");
+    assert_ne!(second["recipe_id"], first["recipe_id"]);
+    assert_eq!(recipe_count(&app.db_pool).await, 2);
+}
+
+async fn chop(app: &TestApp, api_key: &str, source_url: &str, on_duplicate: Option<&str>) -> Value {
+    let response = match on_duplicate {
+        Some(on_duplicate) => app.post_chopper_on_duplicate(api_key, "A stew recipe", source_url, on_duplicate).await,
+        None => app.post_chopper(api_key, "A stew recipe", source_url).await,
+    };
+    let body: Value = response.json().await.unwrap();
+    app.wait_for_chopper_job(api_key, body["id"].as_str().unwrap()).await
+}
+
+async fn recipe_for_job(app: &TestApp, api_key: &str, job: &Value) -> Value {
+    let recipe_id = job["recipe_id"].as_i64().unwrap();
+    app.api_v1(reqwest::Method::GET, &format!("/recipes/{}", recipe_id), api_key)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
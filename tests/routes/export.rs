@@ -0,0 +1,184 @@
+use chopping_list::formats::archive::{write_archive, ArchiveRecipe, ArchiveUnit};
+use chopping_list::models::recipe::{FullRecipeDetails, Recipe, SaveRecipeParams};
+use chopping_list::models::tag::Tag;
+use chopping_list::models::unit::{self, Unit, UnitDimension, UnitSystem};
+use chopping_list::seeds::seed_recipes;
+use reqwest::Method;
+use serde_json::Value;
+use crate::helpers::{multipart_body, spawn_app};
+
+#[tokio::test]
+async fn exported_recipes_restore_into_another_instance() {
+    let source = spawn_app().await;
+    unit::create_default_units(&source.db_pool).await.unwrap();
+    let source_key = source.generate_api_key().await;
+    for seed in seed_recipes() {
+        let recipe = Recipe::create_full(&source.db_pool, &source.test_user.user_id, &seed.recipe).await.unwrap();
+        for tag in &seed.tags {
+            recipe.add_tag(&source.db_pool, tag).await.unwrap();
+        }
+    }
+
+    let response = source.api_v1(Method::GET, "/recipes/export", &source_key).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "application/zip");
+    let archive = response.bytes().await.unwrap();
+
+    let target = spawn_app().await;
+    unit::create_default_units(&target.db_pool).await.unwrap();
+    let target_key = target.generate_api_key().await;
+    let (content_type, body) = multipart_body(&[("chopping-list-recipes.zip", &archive[..])], &[]);
+    let report: Value = target.api_v1(Method::POST, "/recipes/import", &target_key)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!((report["imported"].as_u64(), report["failed"].as_u64()), (Some(2), Some(0)), "{}", report);
+
+    for (seed, item) in seed_recipes().into_iter().zip(report["recipes"].as_array().unwrap()) {
+        let recipe_id = item["recipe_id"].as_i64().unwrap() as i32;
+        let restored: FullRecipeDetails = target.api_v1(Method::GET, &format!("/recipes/{}", recipe_id), &target_key)
+            .send().await.unwrap()
+            .json().await.unwrap();
+        assert_eq!(SaveRecipeParams::from(&restored), seed.recipe);
+
+        let mut tags: Vec<String> = Tag::find_by_recipe_id(&target.db_pool, recipe_id).await.unwrap().into_iter().map(|tag| tag.name).collect();
+        let mut expected: Vec<String> = seed.tags.iter().map(|tag| tag.to_string()).collect();
+        tags.sort();
+        expected.sort();
+        assert_eq!(tags, expected);
+    }
+}
+
+fn archived_recipe(name: &str, unit: &str) -> ArchiveRecipe {
+    let recipe: FullRecipeDetails = serde_json::from_value(serde_json::json!({
+        "recipe_id": 1,
+        "name": name,
+        "description": "",
+        "is_public": false,
+        "prep_time": null,
+        "cook_time": null,
+        "rest_time": null,
+        "servings": null,
+        "source_url": null,
+        "recipe_components": [{
+            "name": "Main",
+            "is_optional": false,
+            "component_ingredients": [{
+                "ingredient_id": 1,
+                "name": "Flour",
+                "description": "",
+                "unit": unit,
+                "quantity": { "numerator": 1, "denominator": 1 },
+                "is_optional": false,
+            }],
+        }],
+        "recipe_instructions": [],
+    })).unwrap();
+
+    ArchiveRecipe { recipe, tags: vec!["Baking".to_string(), "Baking".to_string()], images: Vec::new() }
+}
+
+#[tokio::test]
+async fn archive_units_are_matched_to_existing_units_and_never_created() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    let units = vec![
+        ArchiveUnit {
+            name: "cups".to_string(),
+            abbreviation: Some("cup".to_string()),
+            system: UnitSystem::Imperial,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(1.0),
+        },
+        ArchiveUnit {
+            name: "smidgen".to_string(),
+            abbreviation: None,
+            system: UnitSystem::Universal,
+            dimension: UnitDimension::Volume,
+            base_factor: Some(0.1),
+        },
+    ];
+    let archive = write_archive(&units, &[archived_recipe("Bread", "cups"), archived_recipe("Pinch pie", "smidgen")], 0).unwrap();
+
+    let (content_type, body) = multipart_body(&[("backup.zip", &archive[..])], &[]);
+    let report: Value = app.api_v1(Method::POST, "/recipes/import", &api_key)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!((report["imported"].as_u64(), report["failed"].as_u64()), (Some(1), Some(2)), "{}", report);
+    let failed: Vec<&str> = report["recipes"].as_array().unwrap()
+        .iter()
+        .filter(|item| item["status"] == "failed")
+        .map(|item| item["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(failed, vec!["smidgen", "Pinch pie"]);
+    assert!(Unit::find_by_name(&app.db_pool, "smidgen").await.unwrap().is_none());
+    assert!(Unit::find_by_name(&app.db_pool, "cups").await.unwrap().is_none());
+    assert_eq!(Unit::find_by_name(&app.db_pool, "cup").await.unwrap().unwrap().base_factor, Some(236.5882365));
+
+    let bread_id = report["recipes"][1]["recipe_id"].as_i64().unwrap() as i32;
+    let bread = Recipe::get_full_recipe_details(&app.db_pool, &app.test_user.user_id, bread_id).await.unwrap();
+    assert_eq!(bread.recipe_components[0].component_ingredients[0].unit, "cup");
+    let tags: Vec<String> = Tag::find_by_recipe_id(&app.db_pool, bread_id).await.unwrap().into_iter().map(|tag| tag.name).collect();
+    assert_eq!(tags, vec!["Baking"]);
+}
+
+#[tokio::test]
+async fn restoring_an_archive_twice_skips_what_is_already_there() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    for seed in seed_recipes() {
+        Recipe::create_full(&app.db_pool, &app.test_user.user_id, &seed.recipe).await.unwrap();
+    }
+
+    let archive = app.api_v1(Method::GET, "/recipes/export", &api_key).send().await.unwrap().bytes().await.unwrap();
+    let (content_type, body) = multipart_body(&[("backup.zip", &archive[..])], &[]);
+    let report: Value = app.api_v1(Method::POST, "/recipes/import", &api_key)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!((report["imported"].as_u64(), report["skipped"].as_u64()), (Some(0), Some(2)));
+}
+
+#[tokio::test]
+async fn restoring_recipes_without_a_source_url_twice_skips_them() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    let mut changed = archived_recipe("Bread", "cup");
+    changed.recipe.recipe_components[0].component_ingredients[0].name = "Rye flour".to_string();
+    let first = write_archive(&[], &[archived_recipe("Bread", "cup")], 0).unwrap();
+    let second = write_archive(&[], &[archived_recipe("Bread", "cup"), changed], 0).unwrap();
+
+    let mut reports = Vec::new();
+    for archive in [first, second] {
+        let (content_type, body) = multipart_body(&[("backup.zip", &archive[..])], &[]);
+        let report: Value = app.api_v1(Method::POST, "/recipes/import", &api_key)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body)
+            .send().await.unwrap()
+            .json().await.unwrap();
+        reports.push((report["imported"].as_u64(), report["skipped"].as_u64()));
+    }
+    assert_eq!(reports, vec![(Some(1), Some(0)), (Some(1), Some(1))]);
+    assert_eq!(Recipe::list_for_user(&app.db_pool, &app.test_user.user_id).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn the_export_page_downloads_an_archive() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.api_client
+        .get(&format!("{}/recipes/export", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["content-disposition"].to_str().unwrap().starts_with("attachment"));
+}
@@ -1,7 +1,13 @@
 use sqlx::{PgConnection, Executor, Connection};
-use chopping_list::configuration::{get_configuration, DatabaseSettings};
+use chopping_list::configuration::{get_configuration, ChopperProvider, DatabaseSettings};
 use chopping_list::telemetry::{get_subscriber, init_subscriber};
 use chopping_list::startup::Application;
+use chopping_list::domain::Quantity;
+use chopping_list::models::ingredient::{Ingredient, CreateIngredientParams};
+use chopping_list::models::recipe::{Recipe, CreateRecipeParams};
+use chopping_list::models::recipe_component::{RecipeComponent, CreateRecipeComponentParams};
+use chopping_list::models::recipe_component_ingredient::{RecipeComponentIngredient, CreateRecipeComponentIngredientParams};
+use chopping_list::models::unit::Unit;
 use sqlx::PgPool;
 use once_cell::sync::Lazy;
 use uuid::Uuid;
@@ -42,10 +48,11 @@ impl TestUser {
         let email = self.email.clone();
         let password_hash = password_auth::generate_hash(self.password.clone());
         sqlx::query!(
-            "INSERT INTO users (id, email, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (id, email, username, password_hash)
+            VALUES ($1, $2, $3, $4)",
             self.user_id,
             email,
+            self.user_id.to_string(),
             password_hash,
         )
         .execute(pool)
@@ -129,6 +136,280 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn login_test_user(&self) {
+        let body = serde_json::json!({
+            "email": self.test_user.email,
+            "password": self.test_user.password,
+        });
+        let response = self.post_login(&body).await;
+        assert_is_redirect_to(&response, "/");
+    }
+
+    pub async fn get_shopping_list(&self, query_params: &[(&str, &str)]) -> reqwest::Response {
+        let query_string = serde_urlencoded::to_string(query_params).expect("Failed to serialize query params");
+        self.api_client
+            .get(&format!("{}/shopping_list?{}", &self.address, query_string))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_settings(&self) -> reqwest::Response {
+        self.api_client
+            .get(&format!("{}/settings", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_settings<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        self.api_client
+            .post(&format!("{}/settings", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_recipe(&self, recipe_id: i32, query_params: &[(&str, &str)]) -> reqwest::Response {
+        let query_string = serde_urlencoded::to_string(query_params).expect("Failed to serialize query params");
+        self.api_client
+            .get(&format!("{}/recipes/{}?{}", &self.address, recipe_id, query_string))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recipe(&self, body: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/recipes", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_update_recipe(&self, recipe_id: i32, body: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/recipes/{}", &self.address, recipe_id))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_recipe(&self, recipe_id: i32) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/recipes/{}/delete", &self.address, recipe_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_cooked(&self, recipe_id: i32) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/recipes/{}/cooked", &self.address, recipe_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_pantry(&self) -> reqwest::Response {
+        self.api_client
+            .get(&format!("{}/pantry", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_pantry_item(&self, body: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/pantry", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_update_pantry_item(&self, pantry_item_id: i32, body: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/pantry/{}", &self.address, pantry_item_id))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_pantry_item(&self, pantry_item_id: i32) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/pantry/{}/delete", &self.address, pantry_item_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_meal_plans(&self, query_params: &[(&str, &str)]) -> reqwest::Response {
+        let query_string = serde_urlencoded::to_string(query_params).expect("Failed to serialize query params");
+        self.api_client
+            .get(&format!("{}/meal_plans?{}", &self.address, query_string))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_meal_plans_month(&self, query_params: &[(&str, &str)]) -> reqwest::Response {
+        let query_string = serde_urlencoded::to_string(query_params).expect("Failed to serialize query params");
+        self.api_client
+            .get(&format!("{}/meal_plans/month?{}", &self.address, query_string))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_meal_plans_shopping_list(&self, query_params: &[(&str, &str)]) -> reqwest::Response {
+        let query_string = serde_urlencoded::to_string(query_params).expect("Failed to serialize query params");
+        self.api_client
+            .get(&format!("{}/meal_plans/shopping_list?{}", &self.address, query_string))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_meal_plan(&self, body: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/meal_plans", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_meal_plan(&self, meal_plan_id: i32) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/meal_plans/{}/delete", &self.address, meal_plan_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_calendar_token(&self) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/settings/calendar_token", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_calendar_token(&self) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/settings/calendar_token/delete", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Without the session, the way a calendar app fetches the feed
+    pub async fn get_calendar(&self, calendar_file: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(&format!("{}/calendar/{}", &self.address, calendar_file))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recipe_from_extension(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/recipes/extension", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Gives the test user an API key, as `POST /generate_api_key` would
+    pub async fn generate_api_key(&self) -> String {
+        let api_key = Uuid::new_v4().to_string();
+        sqlx::query("UPDATE users SET api_key = $1 WHERE id = $2")
+            .bind(&api_key)
+            .bind(self.test_user.user_id)
+            .execute(&self.db_pool)
+            .await
+            .expect("Failed to store api key.");
+        api_key
+    }
+
+    /// Starts a request to `/api/v1{path}` authenticated with `api_key`
+    pub fn api_v1(&self, method: reqwest::Method, path: &str, api_key: &str) -> reqwest::RequestBuilder {
+        self.api_client
+            .request(method, &format!("{}/api/v1{}", &self.address, path))
+            .bearer_auth(api_key)
+    }
+
+    pub async fn post_chopper(&self, api_key: &str, text: &str, source_url: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/api/chopper", &self.address))
+            .bearer_auth(api_key)
+            .json(&serde_json::json!({
+                "text": text,
+                "sourceUrl": source_url,
+                "hostname": "example.com",
+                "pathname": "/stew",
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_chopper_on_duplicate(&self, api_key: &str, text: &str, source_url: &str, on_duplicate: &str) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/api/chopper", &self.address))
+            .bearer_auth(api_key)
+            .json(&serde_json::json!({
+                "text": text,
+                "sourceUrl": source_url,
+                "hostname": "example.com",
+                "pathname": "/stew",
+                "onDuplicate": on_duplicate,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_import_url(&self, api_key: &str, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/api/import/url", &self.address))
+            .bearer_auth(api_key)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_chopper_job(&self, api_key: &str, job_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(&format!("{}/api/chopper/jobs/{}", &self.address, job_id))
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Polls the chopper job until the worker has finished with it
+    pub async fn wait_for_chopper_job(&self, api_key: &str, job_id: &str) -> serde_json::Value {
+        for _ in 0..100 {
+            let job: serde_json::Value = self.get_chopper_job(api_key, job_id).await.json().await.unwrap();
+            if job["status"] != "pending" && job["status"] != "running" {
+                return job;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        panic!("Chopper job {} did not finish", job_id);
+    }
+
     pub async fn get_protected(&self) -> reqwest::Response {
         self.api_client
             .get(&format!("{}/protected", &self.address))
@@ -150,6 +431,12 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         // Use a random OS port
         c.application.port = 0;
+        // Never call a real model from the tests
+        c.chopper.provider = ChopperProvider::Fixture;
+        c.chopper.fixture_path = Some("tests/fixtures/chopper_recipe.json".to_string());
+        c.chopper.poll_interval_milliseconds = 20;
+        // The pages imported by URL are served by wiremock on 127.0.0.1
+        c.chopper.fetch_private_addresses = true;
         c
     };
 
@@ -202,6 +489,69 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
     connection_pool
 }
 
+/// Stores a recipe with a single component holding `ingredients` as
+/// (ingredient name, unit name, quantity numerator, quantity denominator)
+pub async fn create_recipe(db: &PgPool, recipe_params: CreateRecipeParams, ingredients: &[(&str, &str, i32, i32)]) -> Recipe {
+    let recipe = Recipe::create(db, &recipe_params).await.unwrap().unwrap();
+    let component_params = CreateRecipeComponentParams::new(recipe.id, "Main".to_string(), false);
+    let component = RecipeComponent::create(db, &component_params).await.unwrap().unwrap();
+
+    for (ingredient_name, unit_name, quantity_numerator, quantity_denominator) in ingredients {
+        let ingredient = match Ingredient::find_by_name(db, ingredient_name).await.unwrap() {
+            Some(ingredient) => ingredient,
+            None => {
+                let ingredient_params = CreateIngredientParams::new(ingredient_name.to_string(), String::new());
+                Ingredient::create(db, &ingredient_params).await.unwrap()
+            }
+        };
+        let unit = Unit::find_by_name(db, unit_name).await.unwrap().unwrap();
+        let quantity = Quantity::new(*quantity_numerator, *quantity_denominator).unwrap();
+        let params = CreateRecipeComponentIngredientParams::new(component.id, ingredient.id, unit.id, quantity);
+        RecipeComponentIngredient::create(db, &params).await.unwrap();
+    }
+    Recipe::refresh_search(db, recipe.id).await.unwrap();
+
+    recipe
+}
+
+/// Stores another user besides the test user, for checking that their data stays theirs
+pub async fn create_other_user(db: &PgPool) -> Uuid {
+    let user_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO users (id, email, username, password_hash) VALUES ($1, $2, $3, 'hash')")
+        .bind(user_id)
+        .bind(format!("{}@example.com", user_id))
+        .bind(user_id.to_string())
+        .execute(db)
+        .await
+        .unwrap();
+
+    user_id
+}
+
+/// Encodes `files` as (file name, contents) and `fields` as (name, value) into a
+/// multipart/form-data body, answering the content type to send it with
+pub fn multipart_body(files: &[(&str, &[u8])], fields: &[(&str, &str)]) -> (String, Vec<u8>) {
+    let boundary = Uuid::new_v4().simple().to_string();
+    let mut body = Vec::new();
+    for (file_name, contents) in files {
+        body.extend_from_slice(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+            boundary, file_name,
+        ).as_bytes());
+        body.extend_from_slice(contents);
+        body.extend_from_slice(b"\r\n");
+    }
+    for (name, value) in fields {
+        body.extend_from_slice(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            boundary, name, value,
+        ).as_bytes());
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
@@ -0,0 +1,158 @@
+use chopping_list::chopper::{PageFetcher, ParseError};
+use chopping_list::configuration::ChopperSettings;
+use chopping_list::models::unit;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use crate::helpers::{spawn_app, TestApp};
+
+async fn serve_page(server: &MockServer, page_path: &str, body: &str) {
+    Mock::given(method("GET"))
+        .and(path(page_path))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body.to_string(), "text/html; charset=utf-8"))
+        .mount(server)
+        .await;
+}
+
+async fn job_count(app: &TestApp) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM chopper_jobs")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn recipe_for_job(app: &TestApp, api_key: &str, job: &Value) -> Value {
+    let recipe_id = job["recipe_id"].as_i64().unwrap();
+    app.api_v1(reqwest::Method::GET, &format!("/recipes/{}", recipe_id), api_key)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn pages_are_fetched_and_imported() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    let server = MockServer::start().await;
+    serve_page(&server, "/pancakes", include_str!("../fixtures/json_ld/graph.html")).await;
+    let page_url = format!("{}/pancakes", server.uri());
+
+    let response = app.post_import_url(&api_key, &json!({ "url": page_url })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    assert!(response.headers().get("Location").is_some());
+
+    let body: Value = response.json().await.unwrap();
+    let job = app.wait_for_chopper_job(&api_key, body["id"].as_str().unwrap()).await;
+    assert_eq!(job["status"], "succeeded");
+
+    let recipe = recipe_for_job(&app, &api_key, &job).await;
+    assert_eq!(recipe["name"], "Fluffy Pancakes");
+    assert_eq!(recipe["source_url"], page_url);
+}
+
+#[tokio::test]
+async fn pages_without_structured_data_are_read_by_the_parser() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let api_key = app.generate_api_key().await;
+    let server = MockServer::start().await;
+    let html = "<html><body><nav>Home</nav><article><h1>Stew</h1><p>Simmer it.</p></article></body></html>";
+    serve_page(&server, "/stew", html).await;
+
+    let response = app.post_import_url(&api_key, &json!({ "url": format!("{}/stew", server.uri()) })).await;
+    let body: Value = response.json().await.unwrap();
+    let job = app.wait_for_chopper_job(&api_key, body["id"].as_str().unwrap()).await;
+
+    let recipe = recipe_for_job(&app, &api_key, &job).await;
+    assert_eq!(recipe["name"], "Fixture Stew");
+}
+
+#[tokio::test]
+async fn pages_that_cannot_be_fetched_are_rejected() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/photo.jpg"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(vec![0u8; 16], "image/jpeg"))
+        .mount(&server)
+        .await;
+
+    for page_path in ["/missing", "/photo.jpg"] {
+        let response = app.post_import_url(&api_key, &json!({ "url": format!("{}{}", server.uri(), page_path) })).await;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST, "{}", page_path);
+    }
+    assert_eq!(job_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn urls_must_be_web_pages() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+
+    let response = app.post_import_url(&api_key, &json!({ "url": "not a url" })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = app.post_import_url(&api_key, &json!({ "url": "file:///etc/passwd" })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(job_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn importing_by_url_requires_an_api_key() {
+    let app = spawn_app().await;
+
+    let response = app.post_import_url("not-a-key", &json!({ "url": "https://example.com/stew" })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn pages_on_internal_addresses_are_not_fetched() {
+    let fetcher = PageFetcher::new(&ChopperSettings::default()).unwrap();
+    let server = MockServer::start().await;
+    serve_page(&server, "/admin", "<html></html>").await;
+    let port = server.address().port();
+
+    for url in [
+        format!("http://127.0.0.1:{}/admin", port),
+        format!("http://localhost:{}/admin", port),
+        format!("http://[::1]:{}/admin", port),
+        "http://169.254.169.254/latest/meta-data".to_string(),
+    ] {
+        let result = fetcher.fetch(&reqwest::Url::parse(&url).unwrap()).await;
+        assert!(matches!(result, Err(ParseError::Page(ref err)) if err.contains("not a public address")), "{} was fetched: {:?}", url, result.map(|page| page.url));
+    }
+    assert!(server.received_requests().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn chunked_pages_stop_being_read_once_too_big() {
+    let fetcher = PageFetcher::new(&ChopperSettings { fetch_private_addresses: true, ..ChopperSettings::default() }).unwrap();
+    // A page without a length that never ends
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request).await;
+        let headers = "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nTransfer-Encoding: chunked\r\n\r\n";
+        if stream.write_all(headers.as_bytes()).await.is_err() {
+            return;
+        }
+        let chunk = format!("{:x}\r\n{}\r\n", 64 * 1024, "a".repeat(64 * 1024));
+        while stream.write_all(chunk.as_bytes()).await.is_ok() {}
+    });
+
+    let result = fetcher.fetch(&reqwest::Url::parse(&format!("http://127.0.0.1:{}/stew", port)).unwrap()).await;
+    assert!(matches!(result, Err(ParseError::Page(ref err)) if err == "the page is too big"), "{:?}", result.map(|page| page.html.len()));
+}
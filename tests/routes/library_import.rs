@@ -0,0 +1,142 @@
+use std::io::Write;
+use flate2::{write::GzEncoder, Compression};
+use reqwest::Method;
+use serde_json::{json, Value};
+use zip::write::{SimpleFileOptions, ZipWriter};
+use crate::helpers::{multipart_body, spawn_app, TestApp};
+
+const MEALMASTER: &str = "\
+MMMMM----- Recipe via Meal-Master (tm) v8.05
+
+      Title: Cornbread
+      Yield: 8 servings
+
+      1 c  Cornmeal
+      1 c  Flour
+      2 ts Baking powder
+
+  Mix everything and bake for 25 minutes.
+
+MMMMM
+";
+
+fn mela_library() -> Vec<u8> {
+    let mut archive = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    archive.start_file("Tacos.melarecipe", SimpleFileOptions::default()).unwrap();
+    archive.write_all(json!({
+        "title": "Tacos",
+        "yield": "4",
+        "ingredients": "# Filling\n500 g beef\n2 tomatoes",
+        "instructions": "Brown the beef.\nChop the tomatoes.",
+        "link": "https://example.com/tacos",
+    }).to_string().as_bytes()).unwrap();
+    archive.start_file("Broken.melarecipe", SimpleFileOptions::default()).unwrap();
+    archive.write_all(b"{").unwrap();
+
+    archive.finish().unwrap().into_inner()
+}
+
+fn paprika_recipe(recipe: &Value) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(recipe.to_string().as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+async fn import(app: &TestApp, api_key: &str, files: &[(&str, &[u8])], fields: &[(&str, &str)]) -> reqwest::Response {
+    let (content_type, body) = multipart_body(files, fields);
+    app.api_v1(Method::POST, "/recipes/import", api_key)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn every_recipe_in_the_upload_is_reported_on() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+
+    let mela = mela_library();
+    let response = import(&app, &api_key, &[
+        ("Library.melarecipes", &mela[..]),
+        ("BREADS.MMF", MEALMASTER.as_bytes()),
+        ("notes.txt", &b"Buy milk"[..]),
+    ], &[]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let report: Value = response.json().await.unwrap();
+    assert_eq!((report["imported"].as_u64(), report["skipped"].as_u64(), report["failed"].as_u64()), (Some(2), Some(0), Some(2)));
+    let results: Vec<(&str, &str)> = report["recipes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| (item["name"].as_str().unwrap(), item["status"].as_str().unwrap()))
+        .collect();
+    assert_eq!(results, [("Tacos", "imported"), ("Broken", "failed"), ("Cornbread", "imported"), ("notes", "failed")]);
+
+    let recipe_id = report["recipes"][2]["recipe_id"].as_i64().unwrap();
+    let recipe: Value = app.api_v1(Method::GET, &format!("/recipes/{}", recipe_id), &api_key)
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!(recipe["servings"], 8);
+}
+
+#[tokio::test]
+async fn recipes_from_a_page_already_imported_follow_on_duplicate() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+    let mela = mela_library();
+    import(&app, &api_key, &[("Library.melarecipes", &mela[..])], &[]).await;
+
+    let paprika = paprika_recipe(&json!({
+        "name": "Tacos al pastor",
+        "ingredients": "1 pineapple",
+        "directions": "Grill.",
+        "source_url": "https://www.example.com/tacos/",
+    }));
+    let report: Value = import(&app, &api_key, &[("Tacos.paprikarecipe", &paprika[..])], &[]).await.json().await.unwrap();
+    assert_eq!(report["recipes"][0]["status"], "skipped");
+
+    let report: Value = import(&app, &api_key, &[("Tacos.paprikarecipe", &paprika[..])], &[("onDuplicate", "copy")]).await.json().await.unwrap();
+    assert_eq!(report["recipes"][0]["status"], "imported");
+}
+
+#[tokio::test]
+async fn uploads_without_files_are_rejected() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+
+    let response = import(&app, &api_key, &[], &[("onDuplicate", "skip")]).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = import(&app, &api_key, &[("BREADS.MMF", MEALMASTER.as_bytes())], &[("onDuplicate", "merge")]).await;
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn the_import_page_shows_the_report() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.api_client
+        .get(&format!("{}/recipes/import", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (content_type, body) = multipart_body(&[("BREADS.MMF", MEALMASTER.as_bytes())], &[]);
+    let response = app.api_client
+        .post(&format!("{}/recipes/import", &app.address))
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Imported 1, skipped 0, failed 0"));
+    assert!(page.contains("Cornbread"));
+}
@@ -3,3 +3,15 @@ mod health_check;
 mod homepage;
 mod auth;
 mod protected;
+mod shopping_list;
+mod pantry;
+mod meal_plans;
+mod calendar;
+mod settings;
+mod recipes;
+mod api_recipes;
+mod chopper;
+mod import;
+mod library_import;
+mod export;
+mod search;
@@ -0,0 +1,106 @@
+use chopping_list::models::recipe::CreateRecipeParams;
+use chopping_list::models::unit;
+use crate::helpers::{spawn_app, assert_is_redirect_to, create_other_user, create_recipe, TestApp};
+
+async fn meal_plan_id(app: &TestApp) -> i32 {
+    sqlx::query_scalar("SELECT id FROM meal_plans WHERE user_id = $1 ORDER BY id DESC LIMIT 1")
+        .bind(app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn plan(app: &TestApp, recipe_id: i32, planned_on: &str, meal: &str, servings: &str) {
+    let recipe_id = recipe_id.to_string();
+    let response = app.post_meal_plan(&[("recipe_id", &recipe_id), ("planned_on", planned_on), ("meal", meal), ("servings", servings)]).await;
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+#[tokio::test]
+async fn planned_meals_are_shown_on_the_week_and_month_and_removed() {
+    let app = spawn_app().await;
+    let soup = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Soup".to_string(), String::new()).with_servings(4), &[]).await;
+    app.login_test_user().await;
+    let soup_link = format!(r#"<a href="/recipes/{}">Soup</a>"#, soup.id);
+
+    let soup_id = soup.id.to_string();
+    let response = app.post_meal_plan(&[("recipe_id", &soup_id), ("planned_on", "2025-08-06"), ("meal", "dinner"), ("servings", "6")]).await;
+    assert_is_redirect_to(&response, "/meal_plans?date=2025-08-04");
+
+    let week = app.get_meal_plans(&[("date", "2025-08-04")]).await.text().await.unwrap();
+    assert!(week.contains("Wednesday 2025-08-06"));
+    assert!(week.contains(&soup_link));
+    assert!(week.contains("(6 servings)"));
+    let next_week = app.get_meal_plans(&[("date", "2025-08-11")]).await.text().await.unwrap();
+    assert!(!next_week.contains(&soup_link));
+    let month = app.get_meal_plans_month(&[("date", "2025-08-20")]).await.text().await.unwrap();
+    assert!(month.contains("August 2025"));
+    assert!(month.contains(&format!("Dinner: {}", soup_link)));
+
+    let response = app.post_delete_meal_plan(meal_plan_id(&app).await).await;
+    assert_is_redirect_to(&response, "/meal_plans?date=2025-08-04");
+    let week = app.get_meal_plans(&[("date", "2025-08-04")]).await.text().await.unwrap();
+    assert!(!week.contains(&soup_link));
+}
+
+#[tokio::test]
+async fn invalid_meals_are_shown_again_with_errors() {
+    let app = spawn_app().await;
+    let other_user_id = create_other_user(&app.db_pool).await;
+    let theirs = create_recipe(&app.db_pool, CreateRecipeParams::new(other_user_id, "Their Soup".to_string(), String::new()), &[]).await;
+    app.login_test_user().await;
+
+    let response = app.post_meal_plan(&[("recipe_id", "x"), ("planned_on", "2025-08-06"), ("meal", "brunch"), ("servings", "0")]).await;
+    assert_eq!(response.status().as_u16(), 422);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Pick a recipe"));
+    assert!(page.contains("Invalid meal: brunch"));
+    assert!(page.contains("Servings must be a whole number of at least 1"));
+
+    let theirs_id = theirs.id.to_string();
+    let response = app.post_meal_plan(&[("recipe_id", &theirs_id), ("planned_on", "2025-08-06"), ("meal", "lunch"), ("servings", "")]).await;
+    assert_eq!(response.status().as_u16(), 422);
+    assert!(response.text().await.unwrap().contains("Pick one of your recipes"));
+
+    let response = app.get_meal_plans(&[("date", "someday")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn other_users_planned_meals_are_not_found() {
+    let app = spawn_app().await;
+    let soup = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Soup".to_string(), String::new()), &[]).await;
+    app.login_test_user().await;
+    plan(&app, soup.id, "2025-08-06", "dinner", "").await;
+    let meal_plan_id = meal_plan_id(&app).await;
+    let other_user_id = create_other_user(&app.db_pool).await;
+    sqlx::query("UPDATE meal_plans SET user_id = $1 WHERE id = $2")
+        .bind(other_user_id)
+        .bind(meal_plan_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = app.post_delete_meal_plan(meal_plan_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+    let week = app.get_meal_plans(&[("date", "2025-08-04")]).await.text().await.unwrap();
+    assert!(!week.contains(&format!(r#"<a href="/recipes/{}">Soup</a>"#, soup.id)));
+}
+
+#[tokio::test]
+async fn the_weeks_shopping_list_adds_up_every_planned_meal() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let soup = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Soup".to_string(), String::new()).with_servings(4), &[("Milk", "cup", 1, 1), ("Salt", "pinch", 1, 1)]).await;
+    let toast = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Toast".to_string(), String::new()), &[("Bread", "piece", 2, 1)]).await;
+    app.login_test_user().await;
+    plan(&app, soup.id, "2025-08-04", "dinner", "").await;
+    plan(&app, soup.id, "2025-08-08", "lunch", "2").await;
+    plan(&app, toast.id, "2025-08-11", "breakfast", "").await;
+
+    let page = app.get_meal_plans_shopping_list(&[("date", "2025-08-06")]).await.text().await.unwrap();
+    assert!(page.contains("2025-08-04 to 2025-08-10"));
+    assert!(page.contains("Milk 1 1/2 cup"));
+    assert!(page.contains("Salt 2 pinch"));
+    assert!(!page.contains("Bread"));
+}
@@ -0,0 +1,147 @@
+use chopping_list::models::recipe::CreateRecipeParams;
+use chopping_list::models::unit;
+use crate::helpers::{spawn_app, assert_is_redirect_to, create_other_user, create_recipe, TestApp};
+
+/// The test user's pantry as (ingredient, unit, numerator, denominator), by ingredient
+async fn pantry(app: &TestApp) -> Vec<(String, String, i32, i32)> {
+    sqlx::query_as(
+        "SELECT i.name, u.name, p.quantity_numerator, p.quantity_denominator
+         FROM pantry_items p
+         JOIN ingredients i ON i.id = p.ingredient_id
+         JOIN units u ON u.id = p.unit_id
+         WHERE p.user_id = $1
+         ORDER BY i.name, p.id"
+    )
+    .bind(app.test_user.user_id)
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+async fn pantry_item_id(app: &TestApp) -> i32 {
+    sqlx::query_scalar("SELECT id FROM pantry_items WHERE user_id = $1 ORDER BY id DESC LIMIT 1")
+        .bind(app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn stock(app: &TestApp, ingredient: &str, quantity: &str, unit: &str) {
+    let response = app.post_pantry_item(&[("ingredient", ingredient), ("quantity", quantity), ("unit", unit), ("expires_on", "")]).await;
+    assert_is_redirect_to(&response, "/pantry");
+}
+
+#[tokio::test]
+async fn pantry_items_are_added_edited_and_removed() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    app.login_test_user().await;
+
+    let response = app.post_pantry_item(&[("ingredient", "Rice"), ("quantity", "1 1/2"), ("unit", "kilogram"), ("expires_on", "2030-05-01")]).await;
+    assert_is_redirect_to(&response, "/pantry");
+    let page = app.get_pantry().await.text().await.unwrap();
+    assert!(page.contains("Rice 1 1/2 kilogram"));
+    assert!(page.contains("expires 2030-05-01"));
+
+    let pantry_item_id = pantry_item_id(&app).await;
+    let response = app.post_update_pantry_item(pantry_item_id, &[("ingredient", "Rice"), ("quantity", "500"), ("unit", "gram"), ("expires_on", "")]).await;
+    assert_is_redirect_to(&response, "/pantry");
+    assert_eq!(pantry(&app).await, [("Rice".to_string(), "gram".to_string(), 500, 1)]);
+
+    let response = app.post_delete_pantry_item(pantry_item_id).await;
+    assert_is_redirect_to(&response, "/pantry");
+    assert!(pantry(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn invalid_pantry_items_are_shown_again_with_errors() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    app.login_test_user().await;
+
+    let response = app.post_pantry_item(&[("ingredient", "Rice"), ("quantity", "0"), ("unit", "bushel"), ("expires_on", "soon")]).await;
+    assert_eq!(response.status().as_u16(), 422);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Quantity must be more than 0"));
+    assert!(page.contains("Unknown unit bushel"));
+    assert!(page.contains("soon is not a date"));
+    assert!(page.contains(r#"value="Rice""#));
+    assert!(pantry(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn other_users_pantry_items_are_not_found() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    app.login_test_user().await;
+    stock(&app, "Saffron", "1", "gram").await;
+    let pantry_item_id = pantry_item_id(&app).await;
+    let other_user_id = create_other_user(&app.db_pool).await;
+    sqlx::query("UPDATE pantry_items SET user_id = $1 WHERE id = $2")
+        .bind(other_user_id)
+        .bind(pantry_item_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = app.post_update_pantry_item(pantry_item_id, &[("ingredient", "Saffron"), ("quantity", "9"), ("unit", "gram"), ("expires_on", "")]).await;
+    assert_eq!(response.status().as_u16(), 404);
+    let response = app.post_delete_pantry_item(pantry_item_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+    assert!(!app.get_pantry().await.text().await.unwrap().contains("Saffron"));
+}
+
+#[tokio::test]
+async fn shopping_list_subtracts_pantry_stock() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let soup = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Soup".to_string(), String::new()), &[("Milk", "cup", 1, 1), ("Salt", "teaspoon", 1, 1), ("Onion", "piece", 2, 1)]).await;
+    app.login_test_user().await;
+    stock(&app, "Milk", "120", "milliliter").await;
+    stock(&app, "Salt", "1", "tablespoon").await;
+
+    let soup_id = soup.id.to_string();
+    let page = app.get_shopping_list(&[("recipe_id", &soup_id)]).await.text().await.unwrap();
+    let (to_buy, from_pantry) = page.split_once("Already in your pantry").unwrap();
+    assert!(to_buy.contains("Milk 1/2 cup"));
+    assert!(to_buy.contains("Onion 2 piece"));
+    assert!(!to_buy.contains("Salt"));
+    assert!(from_pantry.contains("Milk 1/2 cup"));
+    assert!(from_pantry.contains("Salt 1 teaspoon"));
+}
+
+#[tokio::test]
+async fn cooking_a_recipe_takes_its_ingredients_from_the_pantry() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let bread = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Bread".to_string(), String::new()), &[("Flour", "gram", 200, 1), ("Salt", "tablespoon", 1, 1)]).await;
+    app.login_test_user().await;
+    stock(&app, "Flour", "1", "kilogram").await;
+    stock(&app, "Salt", "1", "teaspoon").await;
+    stock(&app, "Yeast", "1", "package").await;
+
+    let response = app.post_cooked(bread.id).await;
+    assert_is_redirect_to(&response, &format!("/recipes/{}", bread.id));
+    assert_eq!(pantry(&app).await, [
+        ("Flour".to_string(), "kilogram".to_string(), 4, 5),
+        ("Yeast".to_string(), "package".to_string(), 1, 1),
+    ]);
+
+    let response = app.post_cooked(bread.id + 1000).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn cooking_at_the_same_time_takes_from_the_pantry_once_each() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let bread = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Bread".to_string(), String::new()), &[("Flour", "gram", 200, 1)]).await;
+    app.login_test_user().await;
+    stock(&app, "Flour", "1", "kilogram").await;
+
+    let responses = futures::future::join_all((0..4).map(|_| app.post_cooked(bread.id))).await;
+    for response in &responses {
+        assert_is_redirect_to(response, &format!("/recipes/{}", bread.id));
+    }
+    assert_eq!(pantry(&app).await, [("Flour".to_string(), "kilogram".to_string(), 1, 5)]);
+}
@@ -0,0 +1,302 @@
+use chopping_list::models::recipe::CreateRecipeParams;
+use chopping_list::models::unit;
+use chopping_list::models::nutrition;
+use serde_json::{json, Value};
+use crate::helpers::{spawn_app, create_recipe, assert_is_redirect_to, TestApp};
+
+const STEW_FORM: &[(&str, &str)] = &[
+    ("name", "Stew"),
+    ("servings", "4"),
+    ("components[0].name", "Main"),
+    ("components[0].ingredients[0].name", "Potato"),
+    ("components[0].ingredients[0].quantity", "1 1/2"),
+    ("components[0].ingredients[0].unit", "cup"),
+    ("components[0].ingredients[1].name", ""),
+    ("components[0].ingredients[1].quantity", ""),
+    ("components[0].ingredients[1].unit", "cup"),
+    ("instructions[0].title", "Cook"),
+    ("instructions[0].steps", "Chop the potatoes\nBoil them"),
+];
+
+async fn recipe_count(app: &TestApp) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM recipes WHERE user_id = $1")
+        .bind(app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn show_recipe_scales_to_requested_servings() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Stew".to_string(), String::new()).with_servings(4);
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Potato", "cup", 1, 3), ("Salt", "to_taste", 1, 1)]).await;
+    app.login_test_user().await;
+
+    let response = app.get_recipe(recipe.id, &[("servings", "6")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("Potato 1/2 cup"));
+    assert!(html_page.contains("Salt 1 to_taste"));
+}
+
+#[tokio::test]
+async fn show_recipe_has_nutrition_per_serving() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    nutrition::create_default_nutrition_facts(&app.db_pool).await.unwrap();
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Pancakes".to_string(), String::new()).with_servings(2);
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Flour", "cup", 1, 1), ("Eggs", "piece", 2, 1), ("Sofrito", "cup", 1, 1)]).await;
+    app.login_test_user().await;
+
+    let response = app.get_recipe(recipe.id, &[]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("Nutrition per serving"));
+    assert!(html_page.contains("<tr><td>Calories</td><td>299</td></tr>"));
+    assert!(html_page.contains("Sofrito: no nutrition facts"));
+}
+
+#[tokio::test]
+async fn nutrition_is_the_same_in_every_unit_system() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    nutrition::create_default_nutrition_facts(&app.db_pool).await.unwrap();
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Meatballs".to_string(), String::new()).with_servings(1);
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Ground beef", "pound", 3, 1)]).await;
+    app.login_test_user().await;
+
+    let mut calories = Vec::new();
+    for system in ["metric", "imperial"] {
+        let html_page = app.get_recipe(recipe.id, &[("system", system)]).await.text().await.unwrap();
+        let (_, row) = html_page.split_once("<tr><td>Calories</td><td>").unwrap();
+        calories.push(row.split_once('<').unwrap().0.to_string());
+    }
+    assert_eq!(calories[0], calories[1]);
+    assert_eq!(calories[0], "3456");
+}
+
+#[tokio::test]
+async fn show_recipe_rejects_zero_servings() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Stew".to_string(), String::new()).with_servings(4);
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Potato", "cup", 1, 3)]).await;
+    app.login_test_user().await;
+
+    let response = app.get_recipe(recipe.id, &[("servings", "0")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn show_recipe_converts_to_requested_unit_system() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Stew".to_string(), String::new());
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Pork shoulder", "pound", 1, 2)]).await;
+    app.login_test_user().await;
+
+    let response = app.get_recipe(recipe.id, &[("system", "metric")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("Pork shoulder 227 gram"));
+}
+
+#[tokio::test]
+async fn recipes_are_rendered_as_markdown() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Stew".to_string(), String::new()).with_servings(4);
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Potato", "cup", 1, 1), ("Salt", "to_taste", 1, 1)]).await;
+    app.login_test_user().await;
+
+    let response = app.api_client
+        .get(&format!("{}/recipes/{}.md?servings=6", &app.address, recipe.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()[reqwest::header::CONTENT_TYPE], "text/markdown; charset=utf-8");
+
+    let markdown = response.text().await.expect("Failed to read the response body");
+    assert!(markdown.starts_with("# Stew\n"));
+    assert!(markdown.contains("- **Servings:** 6"));
+    assert!(markdown.contains("### Main\n\n- 1 1/2 cup Potato\n- Salt, to taste\n"));
+}
+
+#[tokio::test]
+async fn printable_card_is_scaled_to_requested_servings() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Stew".to_string(), String::new()).with_servings(4);
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Potato", "cup", 1, 3), ("Salt & pepper", "to_taste", 1, 1)]).await;
+    app.login_test_user().await;
+
+    let response = app.api_client
+        .get(&format!("{}/recipes/{}/print?servings=6", &app.address, recipe.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("<li>1/2 cup Potato</li>"));
+    assert!(html_page.contains("<li>Salt &amp; pepper, to taste</li>"));
+    assert!(html_page.contains("Servings: 6"));
+}
+
+#[tokio::test]
+async fn missing_recipes_are_not_found() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    for path in ["/recipes/999999", "/recipes/999999.md", "/recipes/999999/print", "/recipes/stew.md"] {
+        let response = app.api_client
+            .get(&format!("{}{}", &app.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn create_recipe_saves_components_and_instructions() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    app.login_test_user().await;
+
+    let response = app.post_recipe(STEW_FORM).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response.headers().get("Location").unwrap().to_str().unwrap().to_string();
+    let recipe_id: i32 = location.trim_start_matches("/recipes/").parse().unwrap();
+
+    let html_page = app.get_recipe(recipe_id, &[]).await.text().await.unwrap();
+    assert!(html_page.contains("Potato 1 1/2 cup"));
+    assert!(html_page.contains("Chop the potatoes"));
+    assert!(html_page.contains("Boil them"));
+}
+
+#[tokio::test]
+async fn pasted_ingredient_lines_are_parsed() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    app.login_test_user().await;
+
+    let response = app.post_recipe(&[
+        ("name", "Stew"),
+        ("components[0].name", "Main"),
+        ("components[0].pasted", "1½ cups potato, diced\n2 Tbsp. butter"),
+    ]).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response.headers().get("Location").unwrap().to_str().unwrap().to_string();
+    let recipe_id: i32 = location.trim_start_matches("/recipes/").parse().unwrap();
+
+    let html_page = app.get_recipe(recipe_id, &[]).await.text().await.unwrap();
+    assert!(html_page.contains("potato 1 1/2 cup"));
+    assert!(html_page.contains("butter 2 tablespoon"));
+}
+
+#[tokio::test]
+async fn invalid_recipe_is_shown_again_with_errors() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    app.login_test_user().await;
+
+    let response = app.post_recipe(&[
+        ("name", "Stew"),
+        ("components[0].name", "Main"),
+        ("components[0].ingredients[0].name", "Potato"),
+        ("components[0].ingredients[0].quantity", "lots"),
+        ("components[0].ingredients[0].unit", "cup"),
+    ]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("lots is not a valid quantity"));
+    assert_eq!(recipe_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn update_recipe_replaces_its_contents() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Soup".to_string(), String::new());
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Onion", "cup", 1, 2)]).await;
+    app.login_test_user().await;
+
+    let response = app.post_update_recipe(recipe.id, STEW_FORM).await;
+    assert_is_redirect_to(&response, &format!("/recipes/{}", recipe.id));
+
+    let html_page = app.get_recipe(recipe.id, &[]).await.text().await.unwrap();
+    assert!(html_page.contains("Stew"));
+    assert!(html_page.contains("Potato 1 1/2 cup"));
+    assert!(!html_page.contains("Onion"));
+    assert_eq!(recipe_count(&app).await, 1);
+}
+
+#[tokio::test]
+async fn delete_recipe_removes_it() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let recipe_params = CreateRecipeParams::new(app.test_user.user_id, "Soup".to_string(), String::new());
+    let recipe = create_recipe(&app.db_pool, recipe_params, &[("Onion", "cup", 1, 2)]).await;
+    app.login_test_user().await;
+
+    let response = app.post_delete_recipe(recipe.id).await;
+    assert_is_redirect_to(&response, "/recipes");
+    assert_eq!(recipe_count(&app).await, 0);
+
+    let response = app.post_delete_recipe(recipe.id).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn extension_pages_are_chopped_for_the_logged_in_user() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    app.login_test_user().await;
+
+    let response = app.post_recipe_from_extension(&json!({
+        "content": "A stew recipe",
+        "sourceUrl": "https://example.com/stew",
+    })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+    let recipe: Value = response.json().await.unwrap();
+    assert_eq!(recipe["name"], "Fixture Stew");
+    assert_eq!(recipe["source_url"], "https://example.com/stew");
+    assert_eq!(recipe_count(&app).await, 1);
+
+    // The same page again is skipped unless told otherwise
+    let response = app.post_recipe_from_extension(&json!({
+        "content": "A stew recipe",
+        "sourceUrl": "https://www.example.com/stew/",
+    })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let skipped: Value = response.json().await.unwrap();
+    assert_eq!(skipped["recipe_id"], recipe["recipe_id"]);
+    assert_eq!(recipe_count(&app).await, 1);
+}
+
+#[tokio::test]
+async fn extension_pages_need_a_session() {
+    let app = spawn_app().await;
+
+    let response = app.post_recipe_from_extension(&json!({ "content": "A stew recipe" })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(recipe_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn extension_pages_need_content() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.post_recipe_from_extension(&json!({ "content": "  " })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+}
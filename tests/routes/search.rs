@@ -0,0 +1,136 @@
+use chopping_list::models::recipe::{Recipe, SaveRecipeParams};
+use chopping_list::models::unit;
+use chopping_list::seeds::seed_recipes;
+use reqwest::Method;
+use serde_json::{json, Value};
+use crate::helpers::{spawn_app, TestApp};
+
+const HABICHUELAS: &str = "Habichuelas Guisadas (Puerto Rican Stewed Beans)";
+const KIMCHI_JJIGAE: &str = "Kimchi Stew (Kimchi Jjigae)";
+
+/// Saves the seed recipes with their tags, answering their ids in order
+async fn seed(app: &TestApp) -> Vec<i32> {
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let mut recipe_ids = Vec::new();
+    for seed in seed_recipes() {
+        let recipe = Recipe::create_full(&app.db_pool, &app.test_user.user_id, &seed.recipe).await.unwrap();
+        for tag in &seed.tags {
+            recipe.add_tag(&app.db_pool, tag).await.unwrap();
+        }
+        recipe_ids.push(recipe.id);
+    }
+
+    recipe_ids
+}
+
+async fn search(app: &TestApp, api_key: &str, query: &[(&str, &str)]) -> Vec<Value> {
+    let query_string = serde_urlencoded::to_string(query).unwrap();
+    let response = app.api_v1(Method::GET, &format!("/recipes/search?{}", query_string), api_key).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<Vec<Value>>().await.unwrap()
+}
+
+fn names(results: &[Value]) -> Vec<&str> {
+    results.iter().map(|result| result["name"].as_str().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn words_are_found_in_names_ingredients_and_steps() {
+    let app = spawn_app().await;
+    seed(&app).await;
+    let api_key = app.generate_api_key().await;
+
+    let results = search(&app, &api_key, &[("q", "anchovy")]).await;
+    assert_eq!(names(&results), [KIMCHI_JJIGAE]);
+    assert!(results[0]["rank"].as_f64().unwrap() > 0.0);
+    let matches: Vec<&str> = results[0]["snippet"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|part| part["matched"] == true)
+        .map(|part| part["text"].as_str().unwrap())
+        .collect();
+    assert!(matches.iter().all(|text| text.to_lowercase().starts_with("anchov")), "{:?}", matches);
+    assert!(!matches.is_empty());
+
+    assert_eq!(names(&search(&app, &api_key, &[("q", "cumin")]).await), [HABICHUELAS]);
+    assert_eq!(names(&search(&app, &api_key, &[("q", "stew -kimchi")]).await), [HABICHUELAS]);
+    assert_eq!(search(&app, &api_key, &[("q", "lasagna")]).await.len(), 0);
+}
+
+#[tokio::test]
+async fn names_rank_above_passing_mentions() {
+    let app = spawn_app().await;
+    let api_key = app.generate_api_key().await;
+    for (name, step) in [("Pasta", "Fry the garlic in butter."), ("Garlic bread", "Toast the bread.")] {
+        let recipe = json!({ "name": name, "instructions": [{ "title": "Cook", "steps": [step] }] });
+        app.api_v1(Method::POST, "/recipes", &api_key).json(&recipe).send().await.unwrap();
+    }
+
+    let results = search(&app, &api_key, &[("q", "garlic")]).await;
+    assert_eq!(names(&results), ["Garlic bread", "Pasta"]);
+    assert!(results[0]["rank"].as_f64() > results[1]["rank"].as_f64());
+}
+
+#[tokio::test]
+async fn results_are_filtered_by_tag_time_and_ingredient() {
+    let app = spawn_app().await;
+    seed(&app).await;
+    let api_key = app.generate_api_key().await;
+
+    assert_eq!(names(&search(&app, &api_key, &[("tag", "korean")]).await), [KIMCHI_JJIGAE]);
+    assert_eq!(names(&search(&app, &api_key, &[("max_time", "50")]).await), [HABICHUELAS]);
+    assert_eq!(names(&search(&app, &api_key, &[("ingredient", "tofu")]).await), [KIMCHI_JJIGAE]);
+    let mut stews = names(&search(&app, &api_key, &[("q", "stew"), ("tag", "Stew"), ("max_time", "60")]).await)
+        .into_iter()
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    stews.sort();
+    assert_eq!(stews, [HABICHUELAS, KIMCHI_JJIGAE]);
+    assert_eq!(search(&app, &api_key, &[("tag", "Korean"), ("max_time", "50")]).await.len(), 0);
+
+    // Wildcards are matched as themselves rather than matching every ingredient
+    assert_eq!(search(&app, &api_key, &[("ingredient", "%")]).await.len(), 0);
+    assert_eq!(search(&app, &api_key, &[("ingredient", "t_fu")]).await.len(), 0);
+
+    let response = app.api_v1(Method::GET, "/recipes/search?max_time=-5", &api_key).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn edited_recipes_are_searched_by_their_new_contents() {
+    let app = spawn_app().await;
+    let recipe_ids = seed(&app).await;
+    let api_key = app.generate_api_key().await;
+
+    let mut params = seed_recipes().remove(0).recipe;
+    params.instructions[0].steps.push("Finish with a squeeze of lime.".to_string());
+    Recipe::update_full(&app.db_pool, &app.test_user.user_id, recipe_ids[0], &params).await.unwrap();
+    assert_eq!(names(&search(&app, &api_key, &[("q", "lime")]).await), [HABICHUELAS]);
+
+    // Only the ingredient list mentions the beans' Spanish name
+    assert_eq!(names(&search(&app, &api_key, &[("q", "rosadas")]).await), [HABICHUELAS]);
+    let params = SaveRecipeParams { components: Vec::new(), ..params };
+    Recipe::update_full(&app.db_pool, &app.test_user.user_id, recipe_ids[0], &params).await.unwrap();
+    assert_eq!(search(&app, &api_key, &[("q", "rosadas")]).await.len(), 0);
+}
+
+#[tokio::test]
+async fn the_recipes_page_highlights_matches() {
+    let app = spawn_app().await;
+    seed(&app).await;
+    app.login_test_user().await;
+
+    let response = app.api_client
+        .get(&format!("{}/recipes?q=gochujang&tag=&max_time=&ingredient=", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let page = response.text().await.unwrap();
+    assert!(page.contains(KIMCHI_JJIGAE));
+    assert!(!page.contains(HABICHUELAS));
+    assert!(page.to_lowercase().contains("<mark>gochujang</mark>"));
+}
@@ -0,0 +1,27 @@
+use crate::helpers::{spawn_app, assert_is_redirect_to};
+
+#[tokio::test]
+async fn post_settings_saves_preferred_unit_system() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.post_settings(&serde_json::json!({ "preferred_unit_system": "metric" })).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let preferred_unit_system: Option<String> = sqlx::query_scalar("SELECT preferred_unit_system FROM users WHERE id = $1")
+        .bind(app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(preferred_unit_system.as_deref(), Some("metric"));
+
+    let response = app.post_settings(&serde_json::json!({ "preferred_unit_system": "" })).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let preferred_unit_system: Option<String> = sqlx::query_scalar("SELECT preferred_unit_system FROM users WHERE id = $1")
+        .bind(app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(preferred_unit_system, None);
+}
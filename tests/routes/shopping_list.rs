@@ -0,0 +1,38 @@
+use chopping_list::models::recipe::CreateRecipeParams;
+use chopping_list::models::unit;
+use crate::helpers::{spawn_app, create_other_user, create_recipe};
+
+#[tokio::test]
+async fn shopping_list_sums_ingredients_across_recipes() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let soup = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Soup".to_string(), String::new()), &[("Onion", "cup", 1, 2), ("Salt", "teaspoon", 1, 1)]).await;
+    let stew = create_recipe(&app.db_pool, CreateRecipeParams::new(app.test_user.user_id, "Stew".to_string(), String::new()), &[("Onion", "cup", 1, 4), ("Salt", "tablespoon", 1, 1)]).await;
+    app.login_test_user().await;
+
+    let soup_id = soup.id.to_string();
+    let stew_id = stew.id.to_string();
+    let response = app.get_shopping_list(&[("recipe_id", &soup_id), ("recipe_id", &stew_id)]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("Onion 3/4 cup"));
+    assert!(html_page.contains("Salt 1 teaspoon"));
+    assert!(html_page.contains("Salt 1 tablespoon"));
+}
+
+#[tokio::test]
+async fn shopping_list_ignores_other_users_recipes() {
+    let app = spawn_app().await;
+    unit::create_default_units(&app.db_pool).await.unwrap();
+    let other_user_id = create_other_user(&app.db_pool).await;
+    let recipe = create_recipe(&app.db_pool, CreateRecipeParams::new(other_user_id, "Secret Soup".to_string(), String::new()), &[("Saffron", "teaspoon", 1, 1)]).await;
+    app.login_test_user().await;
+
+    let recipe_id = recipe.id.to_string();
+    let response = app.get_shopping_list(&[("recipe_id", &recipe_id)]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(!html_page.contains("Saffron"));
+}